@@ -0,0 +1,252 @@
+//! Interactive client for poking a signalling server by hand.
+//!
+//! Reads commands from stdin, or from a script file with `--script`, and
+//! prints every signal and error the connected peers receive.
+
+use futures::channel::mpsc;
+use futures::StreamExt;
+use signalling_client::{ClientError, Signal, SignalClient, SignalStream};
+use signalling_server::signal::HangupMessage;
+use std::io::BufRead;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const PEER_LABELS: [&str; 2] = ["a", "b"];
+
+const HELP: &str = "\
+commands, optionally prefixed with @a or @b to pick the sending peer:
+  offer <target> <sdp>
+  answer <target> <sdp>
+  candidate <target> <candidate>
+  hangup <target>
+  raw <json>
+  wait <milliseconds>
+  peers
+  help
+  quit
+targets @a and @b stand for the names assigned to the peers,
+\\n and \\r in an sdp stand for line breaks";
+
+#[actix_rt::main]
+async fn main() {
+    let matches = app().get_matches();
+    let url = matches.value_of("url").unwrap_or_default();
+    let peer_count = if matches.is_present("two-peers") {
+        2
+    } else {
+        1
+    };
+
+    let mut peers = Vec::new();
+    for label in &PEER_LABELS[..peer_count] {
+        match SignalClient::connect(url).await {
+            Ok((client, signals)) => {
+                print_event(label, &format!("connected as {}", client.name()));
+                actix_rt::spawn(print_signals(label, signals));
+                peers.push(client);
+            }
+            Err(err) => {
+                eprintln!("couldn't connect peer {}: {}", label, err);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let script = matches.value_of("script").map(str::to_owned);
+    if script.is_none() {
+        println!("type help for the list of commands");
+    }
+    let mut lines = read_lines(script);
+    while let Some(line) = lines.next().await {
+        match run(&mut peers, &line).await {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(err) => eprintln!("{}", err),
+        }
+    }
+
+    for peer in peers {
+        let _ = peer.close().await;
+    }
+}
+
+/// Runs a command line, returning whether to keep reading commands.
+async fn run(peers: &mut [SignalClient], line: &str) -> Result<bool, String> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(true);
+    }
+
+    let (index, line) = match line.strip_prefix('@') {
+        Some(line) => {
+            let (label, line) = split_word(line);
+            let index = PEER_LABELS[..peers.len()]
+                .iter()
+                .position(|peer_label| *peer_label == label)
+                .ok_or_else(|| format!("there is no peer {}", label))?;
+            (index, line)
+        }
+        None => (0, line),
+    };
+    let names: Vec<String> = peers.iter().map(|peer| peer.name().to_owned()).collect();
+    let (command, arguments) = split_word(line);
+    let (target, rest) = split_word(arguments);
+    let target = resolve(target, &names);
+    let peer = &mut peers[index];
+
+    let result = match command {
+        "offer" => peer.send_offer(&target, &unescape(rest)).await,
+        "answer" => peer.send_answer(&target, &unescape(rest)).await,
+        "candidate" => peer.send_candidate(&target, rest).await,
+        "hangup" => {
+            let hangup_message = HangupMessage::new(target, peer.name().to_owned());
+            peer.send(&Signal::Hangup(hangup_message)).await
+        }
+        "raw" => peer.send_raw(arguments).await,
+        "wait" => {
+            let milliseconds = arguments
+                .parse()
+                .map_err(|_| format!("couldn't parse milliseconds {}", arguments))?;
+            actix_rt::time::delay_for(Duration::from_millis(milliseconds)).await;
+            Ok(())
+        }
+        "peers" => {
+            for (label, name) in PEER_LABELS.iter().zip(&names) {
+                println!("@{} {}", label, name);
+            }
+            Ok(())
+        }
+        "help" => {
+            println!("{}", HELP);
+            Ok(())
+        }
+        "quit" | "exit" => return Ok(false),
+        _ => {
+            return Err(format!(
+                "unknown command {}, type help for the list",
+                command
+            ))
+        }
+    };
+
+    result
+        .map(|()| true)
+        .map_err(|err| format!("couldn't send: {}", err))
+}
+
+async fn print_signals(label: &'static str, mut signals: SignalStream) {
+    while let Some(signal) = signals.next().await {
+        let event = match signal {
+            Ok(signal) => format!(
+                "<- {}",
+                serde_json::to_string_pretty(&signal).unwrap_or_default()
+            ),
+            Err(ClientError::ServerError(error_message)) => format!("<- error {}", error_message),
+            Err(err) => format!("<- {}", err),
+        };
+        print_event(label, &event);
+    }
+    print_event(label, "connection closed");
+}
+
+fn print_event(label: &str, event: &str) {
+    println!("[{}] {} {}", timestamp(), label, event);
+}
+
+/// Current UTC time of day, e.g. `13:37:00.042`.
+fn timestamp() -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let seconds = now.as_secs();
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        seconds / 3600 % 24,
+        seconds / 60 % 60,
+        seconds % 60,
+        now.subsec_millis()
+    )
+}
+
+/// Lines of the script, or of stdin if there is none, read on their own
+/// thread so incoming signals keep printing while waiting for input.
+fn read_lines(script: Option<String>) -> mpsc::UnboundedReceiver<String> {
+    let (line_sender, line_receiver) = mpsc::unbounded();
+    std::thread::spawn(move || {
+        let reader: Box<dyn BufRead> = match script {
+            Some(script) => match std::fs::File::open(&script) {
+                Ok(file) => Box::new(std::io::BufReader::new(file)),
+                Err(err) => {
+                    eprintln!("couldn't open script {}: {}", script, err);
+                    return;
+                }
+            },
+            None => Box::new(std::io::BufReader::new(std::io::stdin())),
+        };
+        for line in reader.lines().map_while(Result::ok) {
+            if line_sender.unbounded_send(line).is_err() {
+                break;
+            }
+        }
+    });
+    line_receiver
+}
+
+fn split_word(text: &str) -> (&str, &str) {
+    let text = text.trim_start();
+    match text.find(char::is_whitespace) {
+        Some(index) => (&text[..index], text[index..].trim_start()),
+        None => (text, ""),
+    }
+}
+
+/// Replaces the `@a`/`@b` aliases with the names of the peers.
+fn resolve(target: &str, names: &[String]) -> String {
+    target
+        .strip_prefix('@')
+        .and_then(|label| {
+            PEER_LABELS
+                .iter()
+                .position(|peer_label| *peer_label == label)
+        })
+        .and_then(|index| names.get(index))
+        .cloned()
+        .unwrap_or_else(|| target.to_owned())
+}
+
+fn unescape(sdp: &str) -> String {
+    sdp.replace("\\r", "\r").replace("\\n", "\n")
+}
+
+fn app() -> clap::App<'static, 'static> {
+    clap::App::new("signal-cli")
+        .about("connects to a signalling server and sends signals by hand")
+        .arg(
+            clap::Arg::with_name("url")
+                .help("signal endpoint, e.g. ws://localhost:8080/signal")
+                .required(true),
+        )
+        .arg(
+            clap::Arg::with_name("script")
+                .long("script")
+                .help("file of commands to run instead of reading stdin")
+                .takes_value(true),
+        )
+        .arg(
+            clap::Arg::with_name("two-peers")
+                .long("two-peers")
+                .help("connects a second peer, @b, to test routing end to end"),
+        )
+}
+
+#[test]
+fn test_parsing_command_line() {
+    let names = vec!["caller".to_owned(), "callee".to_owned()];
+
+    let (command, arguments) = split_word("  offer @b v=0\\r\\no=-");
+    let (target, sdp) = split_word(arguments);
+
+    assert_eq!(command, "offer");
+    assert_eq!(resolve(target, &names), "callee");
+    assert_eq!(resolve("someone", &names), "someone");
+    assert_eq!(unescape(sdp), "v=0\r\no=-");
+}
@@ -0,0 +1,393 @@
+//! Load generator running offer/answer/trickle ICE exchanges between pairs
+//! of clients and reporting what the server could take.
+//!
+//! Every offer and answer carries its send time in the session id of its
+//! `o=` line, every candidate in its foundation, so latencies are measured
+//! without any field the server would have to pass through.
+
+use futures::future::join_all;
+use futures::StreamExt;
+use signalling_client::{ClientError, Signal, SignalClient, SignalStream};
+use signalling_server::signal::HangupMessage;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+/// How long a peer waits for a signal before the call counts as failed.
+const RECEIVE_TIMEOUT: Duration = Duration::from_secs(10);
+
+struct Scenario {
+    calls: usize,
+    candidates: usize,
+    sdp_size: usize,
+}
+
+#[derive(Default)]
+struct Stats {
+    latencies: Vec<Duration>,
+    errors: BTreeMap<String, u64>,
+    completed_calls: u64,
+    failed_calls: u64,
+}
+
+impl Stats {
+    fn count_error(&mut self, kind: &str) {
+        *self.errors.entry(kind.to_owned()).or_insert(0) += 1;
+    }
+}
+
+struct Peer {
+    client: SignalClient,
+    signals: SignalStream,
+}
+
+#[actix_rt::main]
+async fn main() {
+    let matches = app().get_matches();
+    let url = matches.value_of("url").unwrap_or_default().to_owned();
+    let clients = parse_arg(&matches, "clients", 100) / 2 * 2;
+    let scenario = Scenario {
+        calls: parse_arg(&matches, "calls", 10),
+        candidates: parse_arg(&matches, "candidates", 8),
+        sdp_size: parse_arg(&matches, "sdp-size", 4096),
+    };
+    let pid = matches.value_of("pid");
+    let memory_before = pid.and_then(process_memory);
+
+    let connecting_started = Instant::now();
+    let connections = join_all((0..clients).map(|_| SignalClient::connect(&url))).await;
+    let connecting_time = connecting_started.elapsed();
+
+    let stats = Rc::new(RefCell::new(Stats::default()));
+    let mut peers = Vec::new();
+    for connection in connections {
+        match connection {
+            Ok((client, signals)) => peers.push(Peer { client, signals }),
+            Err(err) => stats.borrow_mut().count_error(&error_kind(&err)),
+        }
+    }
+    let connected = peers.len();
+    println!(
+        "connections: {} opened, {} failed in {:.2?} ({:.1}/s)",
+        connected,
+        clients - connected,
+        connecting_time,
+        connected as f64 / connecting_time.as_secs_f64()
+    );
+
+    let started = Instant::now();
+    let mut pairs = Vec::new();
+    while peers.len() >= 2 {
+        let callee = peers.pop().unwrap();
+        let caller = peers.pop().unwrap();
+        pairs.push(run_calls(caller, callee, &scenario, started, stats.clone()));
+    }
+    let calling_started = Instant::now();
+    for (caller, callee) in join_all(pairs).await {
+        let _ = caller.client.close().await;
+        let _ = callee.client.close().await;
+    }
+    let calling_time = calling_started.elapsed();
+
+    let mut stats = stats.borrow_mut();
+    println!(
+        "calls: {} completed, {} failed in {:.2?} ({:.1}/s)",
+        stats.completed_calls,
+        stats.failed_calls,
+        calling_time,
+        stats.completed_calls as f64 / calling_time.as_secs_f64()
+    );
+    stats.latencies.sort();
+    println!(
+        "latency of {} messages: p50 {:.2?}, p90 {:.2?}, p99 {:.2?}, max {:.2?}",
+        stats.latencies.len(),
+        percentile(&stats.latencies, 50.0),
+        percentile(&stats.latencies, 90.0),
+        percentile(&stats.latencies, 99.0),
+        stats.latencies.last().cloned().unwrap_or_default()
+    );
+    println!("errors:");
+    for (kind, count) in &stats.errors {
+        println!("  {}: {}", kind, count);
+    }
+    if let Some(pid) = pid {
+        match (memory_before, process_memory(pid)) {
+            (Some(before), Some(after)) => println!(
+                "memory of {}: VmRSS {} kB (before {} kB), VmHWM {} kB",
+                pid, after.0, before.0, after.1
+            ),
+            _ => println!("memory of {}: couldn't read /proc/{}/status", pid, pid),
+        }
+    }
+}
+
+/// Runs the scenario's calls between two peers one after another, stopping
+/// at the first failed call.
+async fn run_calls(
+    mut caller: Peer,
+    mut callee: Peer,
+    scenario: &Scenario,
+    started: Instant,
+    stats: Rc<RefCell<Stats>>,
+) -> (Peer, Peer) {
+    for _ in 0..scenario.calls {
+        match run_call(&mut caller, &mut callee, scenario, started, &stats).await {
+            Ok(()) => stats.borrow_mut().completed_calls += 1,
+            Err(kind) => {
+                let mut stats = stats.borrow_mut();
+                stats.failed_calls += 1;
+                stats.count_error(&kind);
+                break;
+            }
+        }
+    }
+    (caller, callee)
+}
+
+async fn run_call(
+    caller: &mut Peer,
+    callee: &mut Peer,
+    scenario: &Scenario,
+    started: Instant,
+    stats: &Rc<RefCell<Stats>>,
+) -> Result<(), String> {
+    let caller_name = caller.client.name().to_owned();
+    let callee_name = callee.client.name().to_owned();
+
+    send_session(caller, &callee_name, true, scenario, started).await?;
+    receive(callee, 1 + scenario.candidates, started, stats).await?;
+    send_session(callee, &caller_name, false, scenario, started).await?;
+    receive(caller, 1 + scenario.candidates, started, stats).await?;
+
+    let hangup_message = HangupMessage::new(callee_name, caller_name);
+    caller
+        .client
+        .send(&Signal::Hangup(hangup_message))
+        .await
+        .map_err(|err| error_kind(&err))?;
+    receive(callee, 1, started, stats).await
+}
+
+/// Sends an offer or answer followed by the scenario's candidates.
+async fn send_session(
+    peer: &mut Peer,
+    target: &str,
+    offer: bool,
+    scenario: &Scenario,
+    started: Instant,
+) -> Result<(), String> {
+    let sdp = session_description(elapsed_micros(started), scenario.sdp_size);
+    let result = if offer {
+        peer.client.send_offer(target, &sdp).await
+    } else {
+        peer.client.send_answer(target, &sdp).await
+    };
+    result.map_err(|err| error_kind(&err))?;
+
+    for component in 0..scenario.candidates {
+        let candidate = candidate(elapsed_micros(started), component);
+        peer.client
+            .send_candidate(target, &candidate)
+            .await
+            .map_err(|err| error_kind(&err))?;
+    }
+    Ok(())
+}
+
+/// Waits for `count` signals, recording the latency of each.
+async fn receive(
+    peer: &mut Peer,
+    count: usize,
+    started: Instant,
+    stats: &Rc<RefCell<Stats>>,
+) -> Result<(), String> {
+    for _ in 0..count {
+        let signal = match actix_rt::time::timeout(RECEIVE_TIMEOUT, peer.signals.next()).await {
+            Ok(Some(Ok(signal))) => signal,
+            Ok(Some(Err(err))) => return Err(error_kind(&err)),
+            Ok(None) => return Err("connection closed".to_owned()),
+            Err(_) => return Err("receive timeout".to_owned()),
+        };
+        if let Some(sent) = sent_micros(&signal) {
+            let latency = elapsed_micros(started).saturating_sub(sent);
+            stats
+                .borrow_mut()
+                .latencies
+                .push(Duration::from_micros(latency));
+        }
+    }
+    Ok(())
+}
+
+fn error_kind(err: &ClientError) -> String {
+    match err {
+        ClientError::ServerError(error_message) => error_message.kind().to_owned(),
+        ClientError::ConnectError(_) => "connect error".to_owned(),
+        ClientError::ProtocolError(_) => "protocol error".to_owned(),
+        ClientError::ParseError(_) | ClientError::UnknownMessage(_) => "parse error".to_owned(),
+        ClientError::HandshakeError(_) => "handshake error".to_owned(),
+        ClientError::ConnectionClosed => "connection closed".to_owned(),
+    }
+}
+
+fn elapsed_micros(started: Instant) -> u64 {
+    started.elapsed().as_micros() as u64
+}
+
+/// Browser-like SDP with an audio and a video section, padded with codec
+/// lines up to about `size` bytes. The send time is the session id.
+fn session_description(sent_micros: u64, size: usize) -> String {
+    let mut sdp = format!(
+        "v=0\r\no=- {} 2 IN IP4 127.0.0.1\r\ns=-\r\nt=0 0\r\na=group:BUNDLE 0 1\r\n\
+         a=msid-semantic: WMS stream\r\n\
+         m=audio 9 UDP/TLS/RTP/SAVPF 111 103 104 9 0 8\r\nc=IN IP4 0.0.0.0\r\n\
+         a=rtcp:9 IN IP4 0.0.0.0\r\na=ice-ufrag:Lx8d\r\na=ice-pwd:Zq3GZ9hWnVdOy0cRqLdcbd3u\r\n\
+         a=ice-options:trickle\r\na=fingerprint:sha-256 \
+         D2:FA:0E:C3:22:59:5E:14:95:69:92:3D:13:B4:84:24:2C:C2:A2:C0:3E:FD:34:8E:5E:EA:6F:AF:52:CE:E6:0F\r\n\
+         a=setup:actpass\r\na=mid:0\r\na=sendrecv\r\na=rtcp-mux\r\n\
+         a=rtpmap:111 opus/48000/2\r\na=fmtp:111 minptime=10;useinbandfec=1\r\n\
+         m=video 9 UDP/TLS/RTP/SAVPF 96 97 98 99\r\nc=IN IP4 0.0.0.0\r\na=mid:1\r\n\
+         a=sendrecv\r\na=rtcp-mux\r\na=rtcp-rsize\r\n",
+        sent_micros
+    );
+    let mut payload_type = 100;
+    while sdp.len() < size {
+        sdp.push_str(&format!(
+            "a=rtpmap:{0} VP8/90000\r\na=rtcp-fb:{0} goog-remb\r\na=rtcp-fb:{0} transport-cc\r\n\
+             a=rtcp-fb:{0} ccm fir\r\na=rtcp-fb:{0} nack\r\na=rtcp-fb:{0} nack pli\r\n",
+            payload_type
+        ));
+        payload_type += 1;
+    }
+    sdp
+}
+
+/// Host candidate whose foundation is the send time.
+fn candidate(sent_micros: u64, component: usize) -> String {
+    format!(
+        "candidate:{} 1 udp 2122260223 192.0.2.{} {} typ host generation 0 ufrag Lx8d network-id 1",
+        sent_micros,
+        component % 254 + 1,
+        50000 + component
+    )
+}
+
+fn sent_micros(signal: &Signal) -> Option<u64> {
+    let field = match signal {
+        Signal::Offer(sdp_signal) | Signal::Answer(sdp_signal) => sdp_signal
+            .sdp()
+            .lines()
+            .find_map(|line| line.strip_prefix("o=- "))?
+            .split(' ')
+            .next()?,
+        Signal::NewIceCandidate(ice_candidate) => ice_candidate
+            .candidate()
+            .strip_prefix("candidate:")?
+            .split(' ')
+            .next()?,
+        _ => return None,
+    };
+    field.parse().ok()
+}
+
+fn percentile(sorted: &[Duration], percentile: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::default();
+    }
+    let index = ((sorted.len() as f64 * percentile / 100.0).ceil() as usize).max(1) - 1;
+    sorted[index.min(sorted.len() - 1)]
+}
+
+/// `VmRSS` and `VmHWM` of a process in kB, from `/proc/<pid>/status`.
+fn process_memory(pid: &str) -> Option<(u64, u64)> {
+    let status = std::fs::read_to_string(format!("/proc/{}/status", pid)).ok()?;
+    let field = |name: &str| {
+        status
+            .lines()
+            .find_map(|line| line.strip_prefix(name))?
+            .trim()
+            .trim_end_matches("kB")
+            .trim()
+            .parse()
+            .ok()
+    };
+    Some((field("VmRSS:")?, field("VmHWM:")?))
+}
+
+fn parse_arg(matches: &clap::ArgMatches, name: &str, default: usize) -> usize {
+    matches
+        .value_of(name)
+        .map(usize::from_str)
+        .unwrap_or(Ok(default))
+        .unwrap_or_else(|_| panic!("couldn't parse {}", name))
+}
+
+fn app() -> clap::App<'static, 'static> {
+    clap::App::new("signal-load")
+        .about("runs offer/answer/trickle ICE exchanges between pairs of clients")
+        .arg(
+            clap::Arg::with_name("url")
+                .help("signal endpoint, e.g. ws://localhost:8080/signal")
+                .required(true),
+        )
+        .arg(
+            clap::Arg::with_name("clients")
+                .long("clients")
+                .help("number of clients to connect and pair up, defaults to 100")
+                .takes_value(true),
+        )
+        .arg(
+            clap::Arg::with_name("calls")
+                .long("calls")
+                .help("calls each pair makes one after another, defaults to 10")
+                .takes_value(true),
+        )
+        .arg(
+            clap::Arg::with_name("candidates")
+                .long("candidates")
+                .help("candidates trickled per offer and answer, defaults to 8")
+                .takes_value(true),
+        )
+        .arg(
+            clap::Arg::with_name("sdp-size")
+                .long("sdp-size")
+                .help("approximate size of offers and answers in bytes, defaults to 4096")
+                .takes_value(true),
+        )
+        .arg(
+            clap::Arg::with_name("pid")
+                .long("pid")
+                .help("process id of the server, to report its memory")
+                .takes_value(true),
+        )
+}
+
+#[test]
+fn test_reading_send_time() {
+    let offer = Signal::Offer(signalling_client::SessionDescriptionMessage::new(
+        "callee".to_owned(),
+        "caller".to_owned(),
+        session_description(1234, 4096),
+    ));
+    let ice_candidate = Signal::NewIceCandidate(signalling_client::IceCandidate::new(
+        "callee".to_owned(),
+        candidate(5678, 3),
+    ));
+
+    if let Signal::Offer(offer) = &offer {
+        assert!(offer.sdp().len() >= 4096);
+    }
+    assert_eq!(sent_micros(&offer), Some(1234));
+    assert_eq!(sent_micros(&ice_candidate), Some(5678));
+}
+
+#[test]
+fn test_percentile() {
+    let latencies: Vec<Duration> = (1..=100).map(Duration::from_millis).collect();
+
+    assert_eq!(percentile(&latencies, 50.0), Duration::from_millis(50));
+    assert_eq!(percentile(&latencies, 99.0), Duration::from_millis(99));
+    assert_eq!(percentile(&[], 99.0), Duration::default());
+}
@@ -0,0 +1,261 @@
+//! Client for the `/signal` WebSocket of the signalling server.
+//!
+//! ```no_run
+//! use futures::StreamExt;
+//! use signalling_client::{Signal, SignalClient};
+//!
+//! #[actix_rt::main]
+//! async fn main() -> Result<(), signalling_client::ClientError> {
+//!     let (mut client, mut signals) = SignalClient::connect("ws://localhost:8080/signal").await?;
+//!     println!("connected as {}", client.name());
+//!
+//!     while let Some(signal) = signals.next().await {
+//!         if let Signal::Offer(offer) = signal? {
+//!             client.send_answer(&offer.name, "v=0 ...").await?;
+//!         }
+//!     }
+//!     Ok(())
+//! }
+//! ```
+
+use actix_codec::Framed;
+use awc::error::{WsClientError, WsProtocolError};
+use awc::ws::{Codec, Frame, Message};
+use awc::BoxedSocket;
+use futures::stream::{SplitSink, SplitStream};
+use futures::{SinkExt, Stream, StreamExt};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+pub use signalling_server::signal::{
+    Feature, Hello, IceCandidate, SessionDescriptionMessage, Signal,
+};
+pub use signalling_server::ErrorMessage;
+
+type Connection = Framed<BoxedSocket, Codec>;
+
+#[derive(Debug)]
+pub enum ClientError {
+    ConnectError(WsClientError),
+    ProtocolError(WsProtocolError),
+    ParseError(serde_json::Error),
+    /// Error the server reported for a signal sent by this client.
+    ServerError(ErrorMessage),
+    /// Frame of the server that is neither a signal nor an error message.
+    UnknownMessage(String),
+    /// The server didn't answer with the name assignment and a `hello`.
+    HandshakeError(Box<Signal>),
+    ConnectionClosed,
+}
+
+impl From<WsClientError> for ClientError {
+    fn from(err: WsClientError) -> ClientError {
+        Self::ConnectError(err)
+    }
+}
+
+impl From<WsProtocolError> for ClientError {
+    fn from(err: WsProtocolError) -> ClientError {
+        Self::ProtocolError(err)
+    }
+}
+
+impl From<serde_json::Error> for ClientError {
+    fn from(err: serde_json::Error) -> ClientError {
+        Self::ParseError(err)
+    }
+}
+
+impl std::fmt::Display for ClientError {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ConnectError(err) => write!(formatter, "ConnectError({})", err),
+            Self::ProtocolError(err) => write!(formatter, "ProtocolError({})", err),
+            Self::ParseError(err) => write!(formatter, "ParseError({})", err),
+            Self::ServerError(error_message) => write!(formatter, "ServerError({})", error_message),
+            Self::UnknownMessage(text) => write!(formatter, "UnknownMessage({})", text),
+            Self::HandshakeError(signal) => {
+                write!(formatter, "HandshakeError(first signal: {:?})", signal)
+            }
+            Self::ConnectionClosed => write!(formatter, "ConnectionClosed"),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+/// Sending half of a connection, named by the server.
+pub struct SignalClient {
+    name: String,
+    sink: SplitSink<Connection, Message>,
+}
+
+impl SignalClient {
+    /// Connects to the `/signal` endpoint at `url`, e.g.
+    /// `ws://localhost:8080/signal`, waits for the name the server assigns
+    /// and negotiates hangups and notices. Must be called from within a
+    /// running actix system.
+    pub async fn connect(url: &str) -> Result<(SignalClient, SignalStream), ClientError> {
+        let (_, connection) = awc::Client::new().ws(url).connect().await?;
+        let (sink, stream) = connection.split();
+        let mut signals = SignalStream { stream };
+
+        let name = match signals.next().await {
+            Some(Ok(Signal::Assign(name))) => name,
+            Some(Ok(signal)) => return Err(ClientError::HandshakeError(Box::new(signal))),
+            Some(Err(err)) => return Err(err),
+            None => return Err(ClientError::ConnectionClosed),
+        };
+        let mut client = SignalClient { name, sink };
+
+        let hello = Hello::new(vec![Feature::Relay, Feature::Hangup, Feature::Notice]);
+        client.send(&Signal::Hello(hello)).await?;
+        match signals.next().await {
+            Some(Ok(Signal::Hello(_))) => {}
+            Some(Ok(signal)) => return Err(ClientError::HandshakeError(Box::new(signal))),
+            Some(Err(err)) => return Err(err),
+            None => return Err(ClientError::ConnectionClosed),
+        }
+        Ok((client, signals))
+    }
+
+    /// Name other peers address this client by.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub async fn send(&mut self, signal: &Signal) -> Result<(), ClientError> {
+        let text = serde_json::to_string(signal)?;
+        Ok(self.sink.send(Message::Text(text)).await?)
+    }
+
+    /// Sends `text` as is, e.g. to see how the server handles malformed
+    /// or future signals.
+    pub async fn send_raw(&mut self, text: &str) -> Result<(), ClientError> {
+        Ok(self.sink.send(Message::Text(text.to_owned())).await?)
+    }
+
+    pub async fn send_offer(&mut self, target: &str, sdp: &str) -> Result<(), ClientError> {
+        let offer = self.session_description(target, sdp);
+        self.send(&Signal::Offer(offer)).await
+    }
+
+    pub async fn send_answer(&mut self, target: &str, sdp: &str) -> Result<(), ClientError> {
+        let answer = self.session_description(target, sdp);
+        self.send(&Signal::Answer(answer)).await
+    }
+
+    pub async fn send_candidate(
+        &mut self,
+        target: &str,
+        candidate: &str,
+    ) -> Result<(), ClientError> {
+        let ice_candidate = IceCandidate::new(target.to_owned(), candidate.to_owned());
+        self.send(&Signal::NewIceCandidate(ice_candidate)).await
+    }
+
+    pub async fn close(mut self) -> Result<(), ClientError> {
+        Ok(self.sink.send(Message::Close(None)).await?)
+    }
+
+    fn session_description(&self, target: &str, sdp: &str) -> SessionDescriptionMessage {
+        SessionDescriptionMessage::new(target.to_owned(), self.name.clone(), sdp.to_owned())
+    }
+}
+
+/// Receiving half of a connection: the signals routed to the client and
+/// the errors the server reports, until the connection closes.
+pub struct SignalStream {
+    stream: SplitStream<Connection>,
+}
+
+impl Stream for SignalStream {
+    type Item = Result<Signal, ClientError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            let frame = match Pin::new(&mut self.stream).poll_next(context) {
+                Poll::Ready(Some(Ok(frame))) => frame,
+                Poll::Ready(Some(Err(err))) => return Poll::Ready(Some(Err(err.into()))),
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            };
+
+            match frame {
+                Frame::Text(bytes) | Frame::Binary(bytes) => {
+                    return Poll::Ready(Some(decode(&bytes)))
+                }
+                Frame::Close(_) => return Poll::Ready(None),
+                _ => {}
+            }
+        }
+    }
+}
+
+fn decode(bytes: &[u8]) -> Result<Signal, ClientError> {
+    serde_json::from_slice(bytes).map_err(|_| match serde_json::from_slice(bytes) {
+        Ok(error_message) => ClientError::ServerError(error_message),
+        Err(_) => ClientError::UnknownMessage(String::from_utf8_lossy(bytes).into_owned()),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ClientError, Signal, SignalClient};
+    use actix_web::{test, App};
+    use futures::StreamExt;
+    use signalling_server::SignalServer;
+
+    #[actix_rt::test]
+    async fn test_exchanging_offer_and_answer() {
+        //given
+        let signal_server = SignalServer::builder().build();
+        let server = test::start(move || {
+            let signal_server = signal_server.clone();
+            App::new().configure(move |config| signal_server.configure(config))
+        });
+        let url = server.url("/signal").replacen("http", "ws", 1);
+        let (mut caller, mut caller_signals) = SignalClient::connect(&url).await.unwrap();
+        let (mut callee, mut callee_signals) = SignalClient::connect(&url).await.unwrap();
+
+        //when
+        caller.send_offer(callee.name(), "offer sdp").await.unwrap();
+        let offer = match callee_signals.next().await {
+            Some(Ok(Signal::Offer(offer))) => offer,
+            other => panic!("expected an offer, got {:?}", other),
+        };
+        callee.send_answer(&offer.name, "answer sdp").await.unwrap();
+
+        //then
+        match caller_signals.next().await {
+            Some(Ok(Signal::Answer(answer))) => {
+                assert_eq!(answer.name, callee.name());
+                assert_eq!(answer.sdp(), "answer sdp");
+            }
+            other => panic!("expected an answer, got {:?}", other),
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_surfacing_server_error() {
+        //given
+        let signal_server = SignalServer::builder().build();
+        let server = test::start(move || {
+            let signal_server = signal_server.clone();
+            App::new().configure(move |config| signal_server.configure(config))
+        });
+        let url = server.url("/signal").replacen("http", "ws", 1);
+        let (mut caller, mut caller_signals) = SignalClient::connect(&url).await.unwrap();
+
+        //when
+        caller.send_offer("nobody", "offer sdp").await.unwrap();
+
+        //then
+        match caller_signals.next().await {
+            Some(Err(ClientError::ServerError(error_message))) => {
+                assert_eq!(error_message.kind(), "target user not found")
+            }
+            other => panic!("expected a server error, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,7 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use signalling_server::signal::Signal;
+
+fuzz_target!(|text: &str| {
+    let _ = serde_json::from_str::<Signal>(text);
+});
@@ -0,0 +1,14 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use signalling_server::signal::Signal;
+
+// any signal the server accepts must survive being relayed: serializing it
+// and reading it back yields the same signal
+fuzz_target!(|text: &str| {
+    if let Ok(signal) = serde_json::from_str::<Signal>(text) {
+        let serialized = serde_json::to_string(&signal).expect("couldn't serialize");
+        let deserialized = serde_json::from_str::<Signal>(&serialized)
+            .unwrap_or_else(|err| panic!("couldn't read back {}: {}", serialized, err));
+        assert_eq!(deserialized, signal);
+    }
+});
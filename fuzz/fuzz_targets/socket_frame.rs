@@ -0,0 +1,76 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use signalling_server::frame_handler::{Action, FrameHandler};
+use signalling_server::signal::{Encoding, Frame};
+use signalling_server::Error;
+
+const ENCODINGS: [Encoding; 4] = [
+    Encoding::Json,
+    Encoding::JsonRpc,
+    Encoding::MessagePack,
+    Encoding::Cbor,
+];
+
+/// Stands in for the router: answers the routed signals and room joins in
+/// turn with success and the errors routing can fail with.
+fn route(count: usize) -> Result<(), Error> {
+    match count % 4 {
+        0 => Ok(()),
+        1 => Err(Error::TargetNotFound("callee".to_owned())),
+        2 => Err(Error::ConnectionClosed),
+        _ => Err(Error::ServiceTimeout),
+    }
+}
+
+fn check_reply(encoding: Encoding, frame: &Frame) {
+    if let (Encoding::Json, Frame::Text(text)) | (Encoding::JsonRpc, Frame::Text(text)) =
+        (encoding, frame)
+    {
+        if text != "couldn't parse your message" {
+            serde_json::from_str::<serde_json::Value>(text)
+                .unwrap_or_else(|err| panic!("reply {} isn't JSON: {}", text, err));
+        }
+    }
+}
+
+// drives the `FrameHandler` of a `SignalSocket` rather than the actor itself;
+// the first byte picks the encoding, the rest is a session of frames
+// separated by newlines, so a hello can change how later frames are handled
+fuzz_target!(|data: &[u8]| {
+    let (encoding, frames) = match data.split_first() {
+        Some((selector, frames)) => (ENCODINGS[*selector as usize % ENCODINGS.len()], frames),
+        None => return,
+    };
+
+    let mut frame_handler = FrameHandler::new(encoding);
+    let mut routed_count = 0;
+    for frame in frames.split(|byte| *byte == b'\n') {
+        match frame_handler
+            .handle_frame(frame)
+            .expect("couldn't handle frame")
+        {
+            Some(Action::Reply(reply)) | Some(Action::Close(Some(reply))) => {
+                check_reply(encoding, &reply)
+            }
+            Some(Action::Route(id, signal)) => {
+                let result = route(routed_count);
+                routed_count += 1;
+                if let Some(reply) = frame_handler.routed(id, result).expect("couldn't reply") {
+                    check_reply(encoding, &reply);
+                }
+                frame_handler.deliver(&signal).ok();
+            }
+            Some(Action::JoinRoom(id, room)) => {
+                let result = route(routed_count).map(|()| vec![room.clone()]);
+                routed_count += 1;
+                if let Some(reply) = frame_handler
+                    .joined_room(id, &room, result)
+                    .expect("couldn't reply")
+                {
+                    check_reply(encoding, &reply);
+                }
+            }
+            Some(Action::Close(None)) | None => {}
+        }
+    }
+});
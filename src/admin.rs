@@ -0,0 +1,200 @@
+use actix_web::http::header;
+use actix_web::{web, HttpRequest, HttpResponse};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::info;
+
+use super::signal_router::{
+    BroadcastMessage, DisconnectMessage, MemberInfo, MemberInfoMessage, MemberInfosMessage,
+};
+use super::signal_socket::into_service_releated_error;
+use super::{Error, Signal, SignalServerStateData};
+
+const DEFAULT_DISCONNECT_REASON: &str = "disconnected by the server operator";
+/// Room for the reason in a close frame, whose payload of at most 125 bytes
+/// starts with the close code.
+const MAX_CLOSE_REASON_SIZE: usize = 123;
+
+/// Connected user as listed by the admin API.
+#[derive(serde::Serialize)]
+pub(crate) struct User {
+    pub name: String,
+    protocol: &'static str,
+    remote_addr: Option<String>,
+    /// Seconds since the Unix epoch.
+    connected_at: u64,
+    signals_sent: u64,
+    signals_received: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metadata: Option<serde_json::Value>,
+}
+
+impl From<MemberInfo> for User {
+    fn from(member_info: MemberInfo) -> Self {
+        User {
+            name: member_info.name,
+            protocol: member_info.protocol,
+            remote_addr: member_info.remote_addr.map(|addr| addr.to_string()),
+            connected_at: unix_seconds(member_info.connected_at),
+            signals_sent: member_info.signals_sent,
+            signals_received: member_info.signals_received,
+            metadata: member_info.metadata,
+        }
+    }
+}
+
+pub(crate) fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|since_epoch| since_epoch.as_secs())
+        .unwrap_or_default()
+}
+
+#[derive(serde::Deserialize)]
+pub struct DisconnectRequest {
+    reason: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct NoticeRequest {
+    message: String,
+}
+
+/// `GET /admin/users`: every connected user, sorted by name.
+pub async fn users(
+    state: SignalServerStateData,
+    request: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    authorize(&state, &request)?;
+    let member_infos = state
+        .signal_router
+        .send(MemberInfosMessage)
+        .await
+        .map(Ok)
+        .unwrap_or_else(into_service_releated_error)?;
+    let users: Vec<User> = member_infos.into_iter().map(User::from).collect();
+    Ok(HttpResponse::Ok().json(users))
+}
+
+/// `GET /admin/users/{name}`
+pub async fn user(
+    state: SignalServerStateData,
+    request: HttpRequest,
+    name: web::Path<String>,
+) -> Result<HttpResponse, Error> {
+    authorize(&state, &request)?;
+    let name = name.into_inner();
+    let member_info = state
+        .signal_router
+        .send(MemberInfoMessage(name.clone()))
+        .await
+        .map(|member_info| member_info.ok_or(Error::TargetNotFound(name)))
+        .unwrap_or_else(into_service_releated_error)?;
+    Ok(HttpResponse::Ok().json(User::from(member_info)))
+}
+
+/// `POST /admin/users/{name}/disconnect`: closes the user's connection, with
+/// the `reason` of the optional JSON body as close reason, cut to fit into
+/// the close frame.
+pub async fn disconnect(
+    state: SignalServerStateData,
+    request: HttpRequest,
+    name: web::Path<String>,
+    body: Option<web::Json<DisconnectRequest>>,
+) -> Result<HttpResponse, Error> {
+    authorize(&state, &request)?;
+    let name = name.into_inner();
+    let reason = body
+        .and_then(|body| body.into_inner().reason)
+        .map(close_reason)
+        .unwrap_or_else(|| DEFAULT_DISCONNECT_REASON.to_owned());
+
+    info!(user_name = %name, %reason, "disconnecting user");
+    state
+        .signal_router
+        .send(DisconnectMessage::new(name, reason))
+        .await
+        .unwrap_or_else(into_service_releated_error)?;
+    Ok(HttpResponse::Accepted().finish())
+}
+
+/// `POST /admin/notices`: sends the `message` of the JSON body to every
+/// connected user as a `notice` signal.
+pub async fn notices(
+    state: SignalServerStateData,
+    request: HttpRequest,
+    body: web::Json<NoticeRequest>,
+) -> Result<HttpResponse, Error> {
+    authorize(&state, &request)?;
+    let notice = Signal::notice(body.into_inner().message);
+    let recipients = state
+        .signal_router
+        .send(BroadcastMessage(notice))
+        .await
+        .unwrap_or_else(into_service_releated_error)?;
+
+    info!(recipients, "notice broadcast");
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "recipients": recipients })))
+}
+
+/// Cuts `reason` to `MAX_CLOSE_REASON_SIZE` bytes at a character boundary.
+fn close_reason(mut reason: String) -> String {
+    if reason.len() > MAX_CLOSE_REASON_SIZE {
+        let mut size = MAX_CLOSE_REASON_SIZE;
+        while !reason.is_char_boundary(size) {
+            size -= 1;
+        }
+        reason.truncate(size);
+    }
+    reason
+}
+
+fn authorize(state: &SignalServerStateData, request: &HttpRequest) -> Result<(), Error> {
+    match bearer_token(request) {
+        Some(token) if is_admin_token(state, token) => Ok(()),
+        _ => Err(Error::Unauthorized),
+    }
+}
+
+/// Token of an `Authorization: Bearer <token>` header.
+pub(crate) fn bearer_token(request: &HttpRequest) -> Option<&str> {
+    request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|authorization| authorization.to_str().ok())
+        .and_then(|authorization| authorization.strip_prefix("Bearer "))
+}
+
+pub(crate) fn is_admin_token(state: &SignalServerStateData, token: &str) -> bool {
+    match &state.admin_token {
+        Some(admin_token) => constant_time_eq(token.as_bytes(), admin_token.as_bytes()),
+        None => false,
+    }
+}
+
+/// Compares without revealing through timing how much of a guessed token
+/// is right.
+pub(crate) fn constant_time_eq(left: &[u8], right: &[u8]) -> bool {
+    left.len() == right.len()
+        && left
+            .iter()
+            .zip(right)
+            .fold(0, |difference, (left, right)| difference | (left ^ right))
+            == 0
+}
+
+#[test]
+fn test_comparing_tokens() {
+    assert!(constant_time_eq(b"s3cret", b"s3cret"));
+    assert!(!constant_time_eq(b"s3cret", b"s3creT"));
+    assert!(!constant_time_eq(b"s3cret", b"s3cret-but-longer"));
+    assert!(!constant_time_eq(b"", b"s3cret"));
+}
+
+#[test]
+fn test_cutting_close_reason_at_char_boundary() {
+    let reason = format!("{}é and more", "a".repeat(122));
+
+    let cut_reason = close_reason(reason);
+
+    assert_eq!(cut_reason, "a".repeat(122));
+    assert_eq!(close_reason("restarting".to_owned()), "restarting");
+}
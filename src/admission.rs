@@ -0,0 +1,148 @@
+//! Optional HTTP hook deciding who may join the router: `/signal` and
+//! `/signal/events` connections, PeerJS ids, WHIP and WHEP sessions and the
+//! users SIP endpoints register.
+//!
+//! Before the client joins, the hook's endpoint is POSTed the request
+//! headers, the remote IP and the name requested, e.g. with `?name=`:
+//! `{"remote_ip":"192.0.2.1","requested_name":"alice","headers":{"cookie":"…"}}`.
+//! It answers `{"allow":false,"reason":"…"}` to refuse the connection, or
+//! `{"allow":true}` to admit it, optionally with the `name` to assign
+//! instead of a random one and `metadata` kept with the session.
+
+use actix_web::client::Client;
+use actix_web::HttpRequest;
+use serde_json::{json, Map, Value};
+use std::time::Duration;
+use tracing::warn;
+
+use super::Error;
+
+/// Endpoint asked to admit every client joining the router.
+#[derive(Clone, Debug)]
+pub struct AdmissionHook {
+    url: String,
+    timeout: Duration,
+}
+
+/// Answer of the endpoint.
+#[derive(Debug, Default, PartialEq, serde::Deserialize)]
+pub(crate) struct Admission {
+    pub allow: bool,
+    #[serde(default)]
+    pub reason: Option<String>,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub metadata: Option<Value>,
+}
+
+impl AdmissionHook {
+    pub fn new<T: Into<String>>(url: T) -> Self {
+        AdmissionHook {
+            url: url.into(),
+            timeout: Duration::from_secs(2),
+        }
+    }
+
+    /// How long the endpoint may take to answer, after which connections
+    /// are refused.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Asks whether to admit `request`. Fails with `ServiceUnavailable` if
+    /// the endpoint can't be asked or gives no valid answer.
+    pub(crate) async fn admit(
+        &self,
+        request: &AdmissionRequest,
+        requested_name: Option<&str>,
+    ) -> Result<Admission, Error> {
+        let body = json!({
+            "remote_ip": request.remote_ip,
+            "requested_name": requested_name,
+            "headers": request.headers,
+        });
+        let mut response = Client::default()
+            .post(&self.url)
+            .timeout(self.timeout)
+            .send_json(&body)
+            .await
+            .map_err(|error| {
+                warn!(%error, "couldn't ask admission hook");
+                Error::ServiceUnavailable
+            })?;
+        if !response.status().is_success() {
+            warn!(status = %response.status(), "admission hook failed");
+            return Err(Error::ServiceUnavailable);
+        }
+        response.json().await.map_err(|error| {
+            warn!(%error, "admission hook answered invalid JSON");
+            Error::ServiceUnavailable
+        })
+    }
+}
+
+/// What the endpoint is told about a connection, kept by adapters asking
+/// it after the WebSocket started, e.g. when a SIP endpoint registers.
+#[derive(Clone, Debug)]
+pub(crate) struct AdmissionRequest {
+    remote_ip: Option<String>,
+    headers: Map<String, Value>,
+}
+
+impl AdmissionRequest {
+    pub fn new(request: &HttpRequest) -> Self {
+        AdmissionRequest {
+            remote_ip: request.peer_addr().map(|addr| addr.ip().to_string()),
+            headers: headers(request),
+        }
+    }
+}
+
+/// Headers by lowercase name, repeated ones joined with commas.
+fn headers(request: &HttpRequest) -> Map<String, Value> {
+    let mut headers = Map::new();
+    for name in request.headers().keys() {
+        let values: Vec<&str> = request
+            .headers()
+            .get_all(name)
+            .filter_map(|value| value.to_str().ok())
+            .collect();
+        headers.insert(name.as_str().to_owned(), Value::String(values.join(", ")));
+    }
+    headers
+}
+
+#[test]
+fn test_collecting_headers() {
+    let request = actix_web::test::TestRequest::default()
+        .header("Authorization", "Bearer tenant")
+        .header("X-Tenant", "a")
+        .header("X-Tenant", "b")
+        .to_http_request();
+
+    let headers = headers(&request);
+    // actix-http may swap the first two values of a repeated header
+    let mut tenants: Vec<_> = headers["x-tenant"].as_str().unwrap().split(", ").collect();
+    tenants.sort_unstable();
+
+    assert_eq!(headers["authorization"], "Bearer tenant");
+    assert_eq!(tenants, ["a", "b"]);
+}
+
+#[test]
+fn test_reading_denial() {
+    let admission: Admission =
+        serde_json::from_str(r#"{"allow":false,"reason":"tenant is suspended"}"#).unwrap();
+
+    assert_eq!(
+        admission,
+        Admission {
+            allow: false,
+            reason: Some("tenant is suspended".to_owned()),
+            name: None,
+            metadata: None,
+        }
+    );
+}
@@ -0,0 +1,194 @@
+//! Built-in dashboard of the `/admin` API: `/admin/dashboard` serves a page
+//! that watches `/admin/stream`, a WebSocket pushing a snapshot of the
+//! router every second.
+
+use actix::fut::{wrap_future, ActorFuture};
+use actix::prelude::{
+    Actor, ActorContext, Addr, AsyncContext, Context, Handler, Message, Recipient, StreamHandler,
+};
+use actix_web::{web, HttpRequest, HttpResponse};
+use actix_web_actors::ws;
+use std::time::Duration;
+use tracing::{debug, info, trace, warn};
+
+use super::admin::is_admin_token;
+use super::signal_router::{RouterEvent, SubscribeMessage};
+use super::{SignalRouter, SignalServerStateData};
+use statistics::Statistics;
+
+mod statistics;
+
+const PAGE: &str = include_str!("index.html");
+const UPDATE_INTERVAL: Duration = Duration::from_secs(1);
+const AUTHORIZATION_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Keeps the statistics of the router up to date from its events and
+/// pushes them to the watching dashboards.
+pub struct Dashboard {
+    signal_router: Addr<SignalRouter>,
+    statistics: Statistics,
+    viewers: Vec<Recipient<SnapshotMessage>>,
+}
+
+impl Dashboard {
+    pub fn new(signal_router: Addr<SignalRouter>) -> Self {
+        Dashboard {
+            signal_router,
+            statistics: Statistics::new(Vec::new()),
+            viewers: Vec::new(),
+        }
+    }
+
+    fn update(&mut self) {
+        if !self.viewers.is_empty() {
+            let snapshot = self.statistics.snapshot().to_string();
+            self.viewers
+                .retain(|viewer| viewer.do_send(SnapshotMessage(snapshot.clone())).is_ok());
+        }
+        self.statistics.tick();
+    }
+}
+
+impl Actor for Dashboard {
+    type Context = Context<Self>;
+
+    fn started(&mut self, context: &mut Self::Context) {
+        let subscribing_fut = self
+            .signal_router
+            .send(SubscribeMessage(context.address().recipient()));
+        context.wait(wrap_future(subscribing_fut).map(
+            |subscribing_result, dashboard: &mut Self, _| match subscribing_result {
+                Ok(members) => dashboard.statistics = Statistics::new(members),
+                Err(error) => warn!(%error, "couldn't subscribe to router"),
+            },
+        ));
+        context.run_interval(UPDATE_INTERVAL, |dashboard, _| dashboard.update());
+    }
+}
+
+impl Handler<RouterEvent> for Dashboard {
+    type Result = ();
+
+    fn handle(&mut self, event: RouterEvent, _: &mut Self::Context) -> Self::Result {
+        self.statistics.apply(event);
+    }
+}
+
+/// Adds a viewer, which gets the current snapshot right away.
+pub struct WatchMessage(pub Recipient<SnapshotMessage>);
+
+impl Message for WatchMessage {
+    type Result = ();
+}
+
+impl Handler<WatchMessage> for Dashboard {
+    type Result = ();
+
+    fn handle(&mut self, message: WatchMessage, _: &mut Self::Context) -> Self::Result {
+        let snapshot = self.statistics.snapshot().to_string();
+        if message.0.do_send(SnapshotMessage(snapshot)).is_ok() {
+            self.viewers.push(message.0);
+        }
+    }
+}
+
+/// Snapshot of the router as JSON text.
+pub struct SnapshotMessage(pub String);
+
+impl Message for SnapshotMessage {
+    type Result = ();
+}
+
+/// WebSocket of a dashboard. Its first text frame must be the admin token,
+/// after which it receives snapshots until closed.
+struct DashboardSocket {
+    state: SignalServerStateData,
+    dashboard: Addr<Dashboard>,
+    authorized: bool,
+}
+
+impl DashboardSocket {
+    fn new(state: SignalServerStateData, dashboard: Addr<Dashboard>) -> Self {
+        DashboardSocket {
+            state,
+            dashboard,
+            authorized: false,
+        }
+    }
+
+    fn reject(context: &mut ws::WebsocketContext<Self>) {
+        info!("dashboard not authorized");
+        context.close(Some((ws::CloseCode::Policy, "unauthorized").into()));
+        context.stop();
+    }
+}
+
+impl Actor for DashboardSocket {
+    type Context = ws::WebsocketContext<Self>;
+
+    fn started(&mut self, context: &mut Self::Context) {
+        context.run_later(AUTHORIZATION_TIMEOUT, |socket, context| {
+            if !socket.authorized {
+                Self::reject(context);
+            }
+        });
+    }
+}
+
+impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for DashboardSocket {
+    fn handle(
+        &mut self,
+        message: Result<ws::Message, ws::ProtocolError>,
+        context: &mut Self::Context,
+    ) {
+        match message {
+            Ok(ws::Message::Close(reason)) => {
+                debug!(?reason, "dashboard closed");
+                context.stop();
+            }
+            Ok(ws::Message::Text(token)) if !self.authorized => {
+                if is_admin_token(&self.state, token.trim()) {
+                    self.authorized = true;
+                    self.dashboard
+                        .do_send(WatchMessage(context.address().recipient()));
+                    info!("dashboard opened");
+                } else {
+                    Self::reject(context);
+                }
+            }
+            Ok(message) => trace!(?message, "dashboard frame ignored"),
+            Err(error) => warn!(%error, "couldn't receive dashboard frame"),
+        }
+    }
+}
+
+impl Handler<SnapshotMessage> for DashboardSocket {
+    type Result = ();
+
+    fn handle(&mut self, message: SnapshotMessage, context: &mut Self::Context) -> Self::Result {
+        context.text(message.0);
+    }
+}
+
+/// `GET /admin/dashboard`: the page itself, which asks for the token.
+pub async fn page() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(PAGE)
+}
+
+/// `GET /admin/stream`
+pub async fn stream(
+    state: SignalServerStateData,
+    request: HttpRequest,
+    stream: web::Payload,
+) -> Result<HttpResponse, actix_web::Error> {
+    match state.dashboard.clone() {
+        Some(dashboard) => ws::start(
+            DashboardSocket::new(state.clone(), dashboard),
+            &request,
+            stream,
+        ),
+        None => Ok(HttpResponse::NotFound().finish()),
+    }
+}
@@ -0,0 +1,333 @@
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::time::SystemTime;
+
+use super::super::admin::{unix_seconds, User};
+use super::super::signal_router::{MemberInfo, Routed, RouterEvent};
+
+/// Seconds over which message rates are averaged, once that many have
+/// passed.
+const RATE_WINDOW: usize = 10;
+const RECENT_ERRORS: usize = 20;
+
+/// Live view of the router built from its events.
+pub struct Statistics {
+    users: HashMap<String, MemberInfo>,
+    /// Calls keyed by the names of both peers, sorted.
+    calls: HashMap<(String, String), Call>,
+    /// Signals routed per type and second, the current second last.
+    rates: VecDeque<HashMap<&'static str, u64>>,
+    errors: HashMap<&'static str, u64>,
+    recent_errors: VecDeque<RecentError>,
+}
+
+struct Call {
+    caller: String,
+    callee: String,
+    answered: bool,
+    since: SystemTime,
+}
+
+struct RecentError {
+    at: SystemTime,
+    error: &'static str,
+    routed: Routed,
+}
+
+impl Statistics {
+    pub fn new(members: Vec<MemberInfo>) -> Self {
+        Statistics {
+            users: members
+                .into_iter()
+                .map(|member| (member.name.clone(), member))
+                .collect(),
+            calls: HashMap::new(),
+            rates: VecDeque::from(vec![HashMap::new()]),
+            errors: HashMap::new(),
+            recent_errors: VecDeque::new(),
+        }
+    }
+
+    pub fn apply(&mut self, event: RouterEvent) {
+        match event {
+            RouterEvent::Joined(member) => {
+                self.users.insert(member.name.clone(), member);
+            }
+            RouterEvent::Exited(name) => {
+                self.users.remove(&name);
+                self.calls
+                    .retain(|(left, right), _| *left != name && *right != name);
+            }
+            RouterEvent::JoinedRoom { user_name, room } => {
+                if let Some(user) = self.users.get_mut(&user_name) {
+                    user.rooms.insert(room);
+                }
+            }
+            RouterEvent::Routed(routed) => self.apply_routed(routed),
+        }
+    }
+
+    fn apply_routed(&mut self, routed: Routed) {
+        let current_second = self.rates.back_mut().expect("rates are never empty");
+        *current_second.entry(routed.kind).or_default() += 1;
+        if let Some(user) = routed
+            .sender
+            .as_ref()
+            .and_then(|name| self.users.get_mut(name))
+        {
+            user.signals_sent += 1;
+        }
+
+        if let Some(error) = routed.error {
+            *self.errors.entry(error).or_default() += 1;
+            if self.recent_errors.len() == RECENT_ERRORS {
+                self.recent_errors.pop_front();
+            }
+            self.recent_errors.push_back(RecentError {
+                at: SystemTime::now(),
+                error,
+                routed,
+            });
+            return;
+        }
+
+        if let Some(user) = routed
+            .target
+            .as_ref()
+            .and_then(|name| self.users.get_mut(name))
+        {
+            user.signals_received += 1;
+        }
+        let (sender, target) = match (routed.sender, routed.target) {
+            (Some(sender), Some(target)) => (sender, target),
+            _ => return,
+        };
+        let key = if sender < target {
+            (sender.clone(), target.clone())
+        } else {
+            (target.clone(), sender.clone())
+        };
+        match routed.kind {
+            "offer" => {
+                self.calls.entry(key).or_insert_with(|| Call {
+                    caller: sender,
+                    callee: target,
+                    answered: false,
+                    since: SystemTime::now(),
+                });
+            }
+            "answer" => {
+                if let Some(call) = self.calls.get_mut(&key) {
+                    call.answered = true;
+                }
+            }
+            "hangup" => {
+                self.calls.remove(&key);
+            }
+            _ => {}
+        }
+    }
+
+    /// Starts the next second of the message rates.
+    pub fn tick(&mut self) {
+        if self.rates.len() == RATE_WINDOW {
+            self.rates.pop_front();
+        }
+        self.rates.push_back(HashMap::new());
+    }
+
+    pub fn snapshot(&self) -> Value {
+        let mut by_protocol: BTreeMap<&str, u64> = BTreeMap::new();
+        for user in self.users.values() {
+            *by_protocol.entry(user.protocol).or_default() += 1;
+        }
+
+        let mut calls: Vec<&Call> = self.calls.values().collect();
+        calls.sort_by_key(|call| call.since);
+        let answered = calls.iter().filter(|call| call.answered).count();
+
+        let mut counts: BTreeMap<&str, u64> = BTreeMap::new();
+        for second in &self.rates {
+            for (kind, count) in second {
+                *counts.entry(kind).or_default() += count;
+            }
+        }
+        let rates: BTreeMap<&str, f64> = counts
+            .into_iter()
+            .map(|(kind, count)| (kind, count as f64 / self.rates.len() as f64))
+            .collect();
+
+        let mut rooms: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+        for user in self.users.values() {
+            for room in &user.rooms {
+                rooms.entry(room).or_default().insert(&user.name);
+            }
+        }
+
+        let mut users: Vec<User> = self.users.values().cloned().map(User::from).collect();
+        users.sort_by(|left, right| left.name.cmp(&right.name));
+
+        json!({
+            "type": "snapshot",
+            "connections": { "total": self.users.len(), "by_protocol": by_protocol },
+            "calls": {
+                "active": answered,
+                "ringing": calls.len() - answered,
+                "list": calls.iter().map(|call| json!({
+                    "caller": call.caller,
+                    "callee": call.callee,
+                    "state": if call.answered { "active" } else { "ringing" },
+                    "since": unix_seconds(call.since),
+                })).collect::<Vec<_>>(),
+            },
+            "rooms": rooms.into_iter().map(|(name, members)| json!({
+                "name": name,
+                "members": members,
+            })).collect::<Vec<_>>(),
+            "rates": rates,
+            "errors": {
+                "by_variant": self.errors.iter().collect::<BTreeMap<_, _>>(),
+                "recent": self.recent_errors.iter().rev().map(|recent| json!({
+                    "at": unix_seconds(recent.at),
+                    "error": recent.error,
+                    "signal_type": recent.routed.kind,
+                    "sender": recent.routed.sender,
+                    "target": recent.routed.target,
+                })).collect::<Vec<_>>(),
+            },
+            "users": users,
+        })
+    }
+}
+
+#[cfg(test)]
+fn member(name: &str) -> MemberInfo {
+    MemberInfo {
+        name: name.to_owned(),
+        protocol: "websocket",
+        remote_addr: None,
+        connected_at: SystemTime::now(),
+        signals_sent: 0,
+        signals_received: 0,
+        metadata: None,
+        rooms: BTreeSet::new(),
+    }
+}
+
+#[cfg(test)]
+fn routed(kind: &'static str, sender: &str, target: &str, error: Option<&'static str>) -> Routed {
+    Routed {
+        kind,
+        sender: Some(sender.to_owned()),
+        target: Some(target.to_owned()),
+        error,
+    }
+}
+
+#[test]
+fn test_following_call_from_offer_to_exit() {
+    //given
+    let mut statistics = Statistics::new(vec![member("caller")]);
+    statistics.apply(RouterEvent::Joined(member("callee")));
+
+    //when
+    statistics.apply(RouterEvent::Routed(routed(
+        "offer", "caller", "callee", None,
+    )));
+    let ringing = statistics.snapshot();
+    statistics.apply(RouterEvent::Routed(routed(
+        "answer", "callee", "caller", None,
+    )));
+    let active = statistics.snapshot();
+    statistics.apply(RouterEvent::Exited("callee".to_owned()));
+    let ended = statistics.snapshot();
+
+    //then
+    assert_eq!(ringing["calls"]["ringing"], 1);
+    assert_eq!(ringing["calls"]["list"][0]["caller"], "caller");
+    assert_eq!(active["calls"]["active"], 1);
+    assert_eq!(active["calls"]["ringing"], 0);
+    assert_eq!(active["users"][0]["signals_received"], 1);
+    assert_eq!(active["users"][0]["signals_sent"], 1);
+    assert_eq!(ended["calls"]["list"], json!([]));
+    assert_eq!(ended["connections"]["total"], 1);
+}
+
+#[test]
+fn test_averaging_rates_and_counting_errors() {
+    //given
+    let mut statistics = Statistics::new(vec![member("caller")]);
+
+    //when
+    for _ in 0..RECENT_ERRORS + 5 {
+        statistics.apply(RouterEvent::Routed(routed(
+            "new_ice_candidate",
+            "caller",
+            "callee",
+            None,
+        )));
+        statistics.apply(RouterEvent::Routed(routed(
+            "offer",
+            "caller",
+            "nobody",
+            Some("TargetNotFound"),
+        )));
+        statistics.tick();
+    }
+    let snapshot = statistics.snapshot();
+
+    //then
+    assert_eq!(snapshot["rates"]["new_ice_candidate"], 0.9);
+    assert_eq!(
+        snapshot["errors"]["by_variant"]["TargetNotFound"],
+        RECENT_ERRORS as u64 + 5
+    );
+    assert_eq!(
+        snapshot["errors"]["recent"].as_array().unwrap().len(),
+        RECENT_ERRORS
+    );
+    assert_eq!(snapshot["errors"]["recent"][0]["target"], "nobody");
+}
+
+#[test]
+fn test_averaging_rates_over_seconds_passed() {
+    //given
+    let mut statistics = Statistics::new(vec![member("caller")]);
+
+    //when
+    for _ in 0..3 {
+        statistics.apply(RouterEvent::Routed(routed(
+            "offer", "caller", "callee", None,
+        )));
+    }
+    statistics.tick();
+    let snapshot = statistics.snapshot();
+
+    //then
+    assert_eq!(snapshot["rates"]["offer"], 1.5);
+}
+
+#[test]
+fn test_listing_rooms_until_members_exit() {
+    //given
+    let mut statistics = Statistics::new(vec![member("caller"), member("callee")]);
+
+    //when
+    for user_name in &["callee", "caller"] {
+        statistics.apply(RouterEvent::JoinedRoom {
+            user_name: user_name.to_string(),
+            room: "lobby".to_owned(),
+        });
+    }
+    let joined = statistics.snapshot();
+    statistics.apply(RouterEvent::Exited("caller".to_owned()));
+    statistics.apply(RouterEvent::Exited("callee".to_owned()));
+    let exited = statistics.snapshot();
+
+    //then
+    assert_eq!(
+        joined["rooms"],
+        json!([{ "name": "lobby", "members": ["callee", "caller"] }])
+    );
+    assert_eq!(exited["rooms"], json!([]));
+}
@@ -1,11 +1,61 @@
+use actix_web::http::{header, StatusCode};
+use actix_web::{HttpResponse, ResponseError};
+use std::borrow::Cow;
+
+use super::signal::{Feature, PROTOCOL_VERSION};
+
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum Error {
     ParseError(serde_json::Error),
+    MessagePackDecodeError(CodecError),
+    MessagePackEncodeError(CodecError),
+    CborError(CodecError),
     ConnectionClosed,
     ConnectionTimeout,
     TargetNotFound(String),
     ServiceUnavailable,
     ServiceTimeout,
+    UnsupportedVersion(u32),
+    FeatureNotNegotiated(Feature),
+    PayloadTooLarge(usize),
+    KindNotAllowed(String),
+    NegotiationTimeout,
+    SessionNotFound(String),
+    Unauthorized,
+    Forbidden(String),
+    ServerSignal(&'static str),
+    TrickleNotSupported(String),
+    RequestPending(String),
+}
+
+impl Error {
+    /// Name of the variant, e.g. `TargetNotFound`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::ParseError(_) => "ParseError",
+            Self::MessagePackDecodeError(_) => "MessagePackDecodeError",
+            Self::MessagePackEncodeError(_) => "MessagePackEncodeError",
+            Self::CborError(_) => "CborError",
+            Self::ConnectionClosed => "ConnectionClosed",
+            Self::ConnectionTimeout => "ConnectionTimeout",
+            Self::TargetNotFound(_) => "TargetNotFound",
+            Self::ServiceUnavailable => "ServiceUnavailable",
+            Self::ServiceTimeout => "ServiceTimeout",
+            Self::UnsupportedVersion(_) => "UnsupportedVersion",
+            Self::FeatureNotNegotiated(_) => "FeatureNotNegotiated",
+            Self::PayloadTooLarge(_) => "PayloadTooLarge",
+            Self::KindNotAllowed(_) => "KindNotAllowed",
+            Self::NegotiationTimeout => "NegotiationTimeout",
+            Self::SessionNotFound(_) => "SessionNotFound",
+            Self::Unauthorized => "Unauthorized",
+            Self::Forbidden(_) => "Forbidden",
+            Self::ServerSignal(_) => "ServerSignal",
+            Self::TrickleNotSupported(_) => "TrickleNotSupported",
+            Self::RequestPending(_) => "RequestPending",
+        }
+    }
 }
 
 impl From<serde_json::Error> for Error {
@@ -14,10 +64,40 @@ impl From<serde_json::Error> for Error {
     }
 }
 
+/// Error of a binary codec, kept opaque so that the codec crates can be
+/// upgraded without breaking `Error`.
+#[derive(Debug)]
+pub struct CodecError(Box<dyn std::error::Error + Send + Sync>);
+
+impl CodecError {
+    pub(crate) fn new<E: std::error::Error + Send + Sync + 'static>(err: E) -> Self {
+        CodecError(Box::new(err))
+    }
+}
+
+impl std::fmt::Display for CodecError {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(formatter)
+    }
+}
+
+impl std::error::Error for CodecError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.0.source()
+    }
+}
+
 impl std::fmt::Display for Error {
     fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::ParseError(err) => write!(formatter, "ParseError({})", err),
+            Self::MessagePackDecodeError(err) => {
+                write!(formatter, "MessagePackDecodeError({})", err)
+            }
+            Self::MessagePackEncodeError(err) => {
+                write!(formatter, "MessagePackEncodeError({})", err)
+            }
+            Self::CborError(err) => write!(formatter, "CborError({})", err),
             Self::ConnectionClosed => write!(formatter, "ConnectionClosed"),
             Self::ConnectionTimeout => write!(formatter, "ConnectionTimeout"),
             Self::TargetNotFound(target_user_name) => write!(
@@ -27,6 +107,41 @@ impl std::fmt::Display for Error {
             ),
             Self::ServiceUnavailable => write!(formatter, "ServiceUnavailable"),
             Self::ServiceTimeout => write!(formatter, "ServiceTemporaryUnavailable"),
+            Self::UnsupportedVersion(version) => {
+                write!(formatter, "UnsupportedVersion(version: {})", version)
+            }
+            Self::FeatureNotNegotiated(feature) => {
+                write!(formatter, "FeatureNotNegotiated(feature: {})", feature)
+            }
+            Self::PayloadTooLarge(max_payload_size) => write!(
+                formatter,
+                "PayloadTooLarge(max_payload_size: {})",
+                max_payload_size
+            ),
+            Self::KindNotAllowed(kind) => write!(formatter, "KindNotAllowed(kind: {})", kind),
+            Self::NegotiationTimeout => write!(formatter, "NegotiationTimeout"),
+            Self::SessionNotFound(session_name) => {
+                write!(formatter, "SessionNotFound(session_name: {})", session_name)
+            }
+            Self::Unauthorized => write!(formatter, "Unauthorized"),
+            Self::Forbidden(target_user_name) => write!(
+                formatter,
+                "Forbidden(target_user_name: {})",
+                target_user_name
+            ),
+            Self::ServerSignal(signal_type) => {
+                write!(formatter, "ServerSignal(signal_type: {})", signal_type)
+            }
+            Self::TrickleNotSupported(target_user_name) => write!(
+                formatter,
+                "TrickleNotSupported(target_user_name: {})",
+                target_user_name
+            ),
+            Self::RequestPending(target_user_name) => write!(
+                formatter,
+                "RequestPending(target_user_name: {})",
+                target_user_name
+            ),
         }
     }
 }
@@ -35,7 +150,152 @@ impl std::error::Error for Error {
     fn cause(&self) -> Option<&dyn std::error::Error> {
         match self {
             Self::ParseError(err) => Some(err),
+            Self::MessagePackDecodeError(err) => Some(err),
+            Self::MessagePackEncodeError(err) => Some(err),
+            Self::CborError(err) => Some(err),
             _ => None,
         }
     }
 }
+
+/// Error reported back to the client that caused it.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ErrorMessage {
+    r#type: Cow<'static, str>,
+    message: String,
+}
+
+impl ErrorMessage {
+    /// Short error category, e.g. `target user not found`.
+    pub fn kind(&self) -> &str {
+        &self.r#type
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl std::fmt::Display for ErrorMessage {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(formatter, "{}: {}", self.r#type, self.message)
+    }
+}
+
+impl From<&Error> for ErrorMessage {
+    fn from(message_send_error: &Error) -> Self {
+        match message_send_error {
+            Error::ParseError(parse_error) => ErrorMessage {
+                r#type: "parse error".into(),
+                message: format!("{}", parse_error),
+            },
+            Error::MessagePackDecodeError(_)
+            | Error::MessagePackEncodeError(_)
+            | Error::CborError(_) => ErrorMessage {
+                r#type: "parse error".into(),
+                message: format!("{}", message_send_error),
+            },
+            Error::ConnectionClosed => ErrorMessage {
+                r#type: "connection closed".into(),
+                message: "target user's connection is closed".to_owned(),
+            },
+            Error::ConnectionTimeout => ErrorMessage {
+                r#type: "timeout".into(),
+                message: "timeout occurres during send message to target user".to_owned(),
+            },
+            Error::TargetNotFound(target_user_name) => ErrorMessage {
+                r#type: "target user not found".into(),
+                message: format!("user {} is not in connection", target_user_name),
+            },
+            Error::ServiceUnavailable => ErrorMessage {
+                r#type: "service unavailable".into(),
+                message: "service is unavailable, please contact to service provider".to_owned(),
+            },
+            Error::ServiceTimeout => ErrorMessage {
+                r#type: "service timeout".into(),
+                message: "service is busy. try after".to_owned(),
+            },
+            Error::UnsupportedVersion(version) => ErrorMessage {
+                r#type: "unsupported version".into(),
+                message: format!(
+                    "protocol version {} is not supported, server speaks version {}",
+                    version, PROTOCOL_VERSION
+                ),
+            },
+            Error::FeatureNotNegotiated(feature) => ErrorMessage {
+                r#type: "feature not negotiated".into(),
+                message: format!("feature {} was not enabled in hello", feature),
+            },
+            Error::PayloadTooLarge(max_payload_size) => ErrorMessage {
+                r#type: "payload too large".into(),
+                message: format!("relay payload exceeds {} bytes", max_payload_size),
+            },
+            Error::KindNotAllowed(kind) => ErrorMessage {
+                r#type: "kind not allowed".into(),
+                message: format!("relay kind '{}' is not allowed", kind),
+            },
+            Error::NegotiationTimeout => ErrorMessage {
+                r#type: "negotiation timeout".into(),
+                message: "target user didn't answer in time".to_owned(),
+            },
+            Error::SessionNotFound(session_name) => ErrorMessage {
+                r#type: "session not found".into(),
+                message: format!("session {} does not exist", session_name),
+            },
+            Error::Unauthorized => ErrorMessage {
+                r#type: "unauthorized".into(),
+                message: "a valid bearer token is required".to_owned(),
+            },
+            Error::Forbidden(target_user_name) => ErrorMessage {
+                r#type: "forbidden".into(),
+                message: format!("signalling user {} is not allowed", target_user_name),
+            },
+            Error::ServerSignal(signal_type) => ErrorMessage {
+                r#type: "server signal".into(),
+                message: format!("{} signals are only sent by the server", signal_type),
+            },
+            Error::TrickleNotSupported(target_user_name) => ErrorMessage {
+                r#type: "trickle not supported".into(),
+                message: format!(
+                    "user {} only takes the candidates of the answer",
+                    target_user_name
+                ),
+            },
+            Error::RequestPending(target_user_name) => ErrorMessage {
+                r#type: "request pending".into(),
+                message: format!("user {} is still negotiating the call", target_user_name),
+            },
+        }
+    }
+}
+
+impl ResponseError for Error {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::ParseError(_)
+            | Self::MessagePackDecodeError(_)
+            | Self::MessagePackEncodeError(_)
+            | Self::CborError(_)
+            | Self::UnsupportedVersion(_)
+            | Self::FeatureNotNegotiated(_)
+            | Self::ServerSignal(_)
+            | Self::TrickleNotSupported(_) => StatusCode::BAD_REQUEST,
+            Self::TargetNotFound(_) | Self::SessionNotFound(_) => StatusCode::NOT_FOUND,
+            Self::ConnectionClosed => StatusCode::BAD_GATEWAY,
+            Self::ConnectionTimeout | Self::NegotiationTimeout => StatusCode::GATEWAY_TIMEOUT,
+            Self::ServiceUnavailable | Self::ServiceTimeout => StatusCode::SERVICE_UNAVAILABLE,
+            Self::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            Self::KindNotAllowed(_) | Self::Forbidden(_) => StatusCode::FORBIDDEN,
+            Self::Unauthorized => StatusCode::UNAUTHORIZED,
+            Self::RequestPending(_) => StatusCode::CONFLICT,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut response = HttpResponse::build(self.status_code());
+        if let Self::Unauthorized = self {
+            response.header(header::WWW_AUTHENTICATE, "Bearer");
+        }
+        response.json(ErrorMessage::from(self))
+    }
+}
@@ -0,0 +1,87 @@
+use super::signal::Signal;
+
+/// Fields clients may never set through passthrough, either because they
+/// are part of the protocol or because the server sets them itself.
+const RESERVED_FIELDS: &[&str] = &[
+    "type",
+    "name",
+    "target",
+    "sdp",
+    "candidate",
+    "kind",
+    "payload",
+    "from",
+    "traceparent",
+    "peerjs",
+];
+
+/// Decides which unknown top-level fields of offers, answers and ICE
+/// candidates are forwarded to the target. Disabled by default, in which
+/// case every unknown field is dropped.
+#[derive(Clone, Debug, Default)]
+pub struct FieldPassthrough {
+    enabled: bool,
+    reserved_fields: Vec<String>,
+}
+
+impl FieldPassthrough {
+    pub fn enabled<I>(reserved_fields: I) -> Self
+    where
+        I: IntoIterator<Item = String>,
+    {
+        FieldPassthrough {
+            enabled: true,
+            reserved_fields: reserved_fields.into_iter().collect(),
+        }
+    }
+
+    fn is_reserved(&self, field: &str) -> bool {
+        RESERVED_FIELDS.contains(&field) || self.reserved_fields.iter().any(|name| name == field)
+    }
+
+    pub fn apply(&self, signal: &mut Signal) {
+        if let Some(extra) = signal.extra_mut() {
+            if !self.enabled {
+                extra.clear();
+                return;
+            }
+
+            let reserved_fields: Vec<String> = extra
+                .keys()
+                .filter(|field| self.is_reserved(field))
+                .cloned()
+                .collect();
+            for field in reserved_fields {
+                extra.remove(&field);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+fn offer_with_extra_fields() -> Signal {
+    serde_json::from_str(
+        r#"{"type":"offer","name":"caller","target":"callee","sdp":"sdp","callId":"call-1","from":"admin","tenant":"acme"}"#,
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_dropping_unknown_fields_when_disabled() {
+    let mut signal = offer_with_extra_fields();
+
+    FieldPassthrough::default().apply(&mut signal);
+
+    assert!(signal.extra_mut().unwrap().is_empty());
+}
+
+#[test]
+fn test_keeping_unreserved_fields_when_enabled() {
+    let mut signal = offer_with_extra_fields();
+
+    FieldPassthrough::enabled(vec!["tenant".to_owned()]).apply(&mut signal);
+
+    let extra = signal.extra_mut().unwrap();
+    assert_eq!(extra.len(), 1);
+    assert_eq!(extra["callId"], serde_json::json!("call-1"));
+}
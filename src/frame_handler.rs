@@ -0,0 +1,300 @@
+use serde_json::Value;
+use tracing::debug;
+
+use super::error::ErrorMessage;
+use super::signal::{jsonrpc, Encoding, Feature, Frame, Hello, Limits, PROTOCOL_VERSION};
+use super::{Error, Signal};
+
+/// What a socket has to do about a frame from its client.
+#[derive(Debug)]
+pub enum Action {
+    /// Send the frame back to the client.
+    Reply(Frame),
+    /// Send the frame back to the client, if any, then close the connection
+    /// as the client speaks another protocol version.
+    Close(Option<Frame>),
+    /// Hand the signal to the router, then report the outcome with
+    /// `FrameHandler::routed`.
+    Route(Option<Value>, Signal),
+    /// Add the client to the room, then report its members with
+    /// `FrameHandler::joined_room`.
+    JoinRoom(Option<Value>, String),
+}
+
+/// Client protocol of a `SignalSocket` without the socket: decodes frames,
+/// negotiates features and encodes replies, leaving routing to the caller.
+pub struct FrameHandler {
+    encoding: Encoding,
+    features: Vec<Feature>,
+}
+
+impl FrameHandler {
+    pub fn new(encoding: Encoding) -> Self {
+        FrameHandler {
+            encoding,
+            features: Vec::new(),
+        }
+    }
+
+    pub fn handle_frame(&mut self, frame: &[u8]) -> Result<Option<Action>, Error> {
+        if self.encoding == Encoding::JsonRpc {
+            return match jsonrpc::decode_request(frame) {
+                Ok(jsonrpc::Request {
+                    id,
+                    call: jsonrpc::Call::Signal(signal),
+                }) => self.handle_signal(id, signal),
+                Ok(jsonrpc::Request {
+                    id,
+                    call: jsonrpc::Call::JoinRoom(room),
+                }) => Ok(Some(Action::JoinRoom(id, room))),
+                Err(response) => self
+                    .encode(&response)
+                    .map(|frame| Some(Action::Reply(frame))),
+            };
+        }
+
+        match self.encoding.decode(frame) {
+            Ok(signal) => self.handle_signal(None, signal),
+            Err(err) => {
+                debug!(size = frame.len(), error = %err, "unparsable frame");
+                Ok(Some(Action::Reply(Frame::Text(
+                    "couldn't parse your message".to_owned(),
+                ))))
+            }
+        }
+    }
+
+    /// Frame reporting the outcome of routing a signal, if the client is
+    /// told about it.
+    pub fn routed(
+        &self,
+        id: Option<Value>,
+        result: Result<(), Error>,
+    ) -> Result<Option<Frame>, Error> {
+        self.reply(id, result.map(|()| None))
+    }
+
+    /// Frame answering the `room.join` request `id` with the members of
+    /// `room`, if the client asked for an answer.
+    pub fn joined_room(
+        &self,
+        id: Option<Value>,
+        room: &str,
+        result: Result<Vec<String>, Error>,
+    ) -> Result<Option<Frame>, Error> {
+        match id {
+            Some(id) => self
+                .encode(&jsonrpc::room_response(id, room, result))
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Frame delivering a signal routed to the client.
+    pub fn deliver(&self, signal: &Signal) -> Result<Frame, Error> {
+        self.check_feature(signal)?;
+        if self.encoding == Encoding::JsonRpc {
+            self.encode(&jsonrpc::notification(signal)?)
+        } else {
+            self.encode(signal)
+        }
+    }
+
+    fn handle_signal(
+        &mut self,
+        id: Option<Value>,
+        signal: Signal,
+    ) -> Result<Option<Action>, Error> {
+        let reply = match signal {
+            Signal::Hello(hello) => return self.handle_hello(id, hello),
+            signal if signal.is_server_signal() => {
+                self.reply(id, Err(Error::ServerSignal(signal.kind())))?
+            }
+            signal => match self.check_feature(&signal) {
+                Ok(()) => return Ok(Some(Action::Route(id, signal))),
+                Err(err) => self.reply(id, Err(err))?,
+            },
+        };
+        Ok(reply.map(Action::Reply))
+    }
+
+    fn handle_hello(&mut self, id: Option<Value>, hello: Hello) -> Result<Option<Action>, Error> {
+        if hello.version != PROTOCOL_VERSION {
+            let reply = self.reply(id, Err(Error::UnsupportedVersion(hello.version)))?;
+            return Ok(Some(Action::Close(reply)));
+        }
+
+        let server_hello = hello.negotiate(Limits::default());
+        self.features = server_hello.features.clone();
+        let reply = self.reply(id, Ok(Some(Signal::Hello(server_hello))))?;
+        Ok(reply.map(Action::Reply))
+    }
+
+    fn check_feature(&self, signal: &Signal) -> Result<(), Error> {
+        match signal.required_feature() {
+            Some(feature) if !self.features.contains(&feature) => {
+                Err(Error::FeatureNotNegotiated(feature))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Reports the outcome of a client message: JSON-RPC requests get a
+    /// response, other clients only hear about errors and server replies.
+    fn reply(
+        &self,
+        id: Option<Value>,
+        result: Result<Option<Signal>, Error>,
+    ) -> Result<Option<Frame>, Error> {
+        if self.encoding == Encoding::JsonRpc {
+            return match id {
+                Some(id) => self.encode(&jsonrpc::response(id, result)).map(Some),
+                None => Ok(None),
+            };
+        }
+
+        match result {
+            Ok(Some(signal)) => self.encode(&signal).map(Some),
+            Ok(None) => Ok(None),
+            Err(err) => self.encode(&ErrorMessage::from(&err)).map(Some),
+        }
+    }
+
+    fn encode<T: serde::Serialize>(&self, message: &T) -> Result<Frame, Error> {
+        self.encoding.encode(message)
+    }
+}
+
+#[test]
+fn test_routing_offer_and_reporting_its_failure() {
+    //given
+    let mut frame_handler = FrameHandler::new(Encoding::Json);
+    let offer = br#"{"type":"offer","name":"caller","target":"callee","sdp":"v=0"}"#;
+
+    //when
+    let action = frame_handler.handle_frame(offer).unwrap();
+    let reply = frame_handler
+        .routed(None, Err(Error::TargetNotFound("callee".to_owned())))
+        .unwrap();
+
+    //then
+    match action {
+        Some(Action::Route(None, Signal::Offer(offer))) => assert_eq!(offer.target, "callee"),
+        other => panic!("expected the offer to be routed, got {:?}", other),
+    }
+    match reply {
+        Some(Frame::Text(text)) => assert_eq!(
+            text,
+            r#"{"type":"target user not found","message":"user callee is not in connection"}"#
+        ),
+        other => panic!("expected an error message, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_replying_to_unparsable_frame() {
+    let mut frame_handler = FrameHandler::new(Encoding::Json);
+
+    match frame_handler.handle_frame(b"{ not a signal").unwrap() {
+        Some(Action::Reply(Frame::Text(text))) => assert_eq!(text, "couldn't parse your message"),
+        other => panic!("expected a reply, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_gating_signals_on_negotiated_features() {
+    //given
+    let mut frame_handler = FrameHandler::new(Encoding::Json);
+    let hangup = br#"{"type":"hangup","name":"caller","target":"callee"}"#;
+
+    //when
+    let before_hello = frame_handler.handle_frame(hangup).unwrap();
+    frame_handler
+        .handle_frame(br#"{"type":"hello","version":1,"features":["hangup"]}"#)
+        .unwrap();
+    let after_hello = frame_handler.handle_frame(hangup).unwrap();
+
+    //then
+    match before_hello {
+        Some(Action::Reply(Frame::Text(text))) => assert_eq!(
+            text,
+            r#"{"type":"feature not negotiated","message":"feature hangup was not enabled in hello"}"#
+        ),
+        other => panic!("expected an error message, got {:?}", other),
+    }
+    assert!(matches!(
+        after_hello,
+        Some(Action::Route(None, Signal::Hangup(_)))
+    ));
+    assert!(frame_handler
+        .deliver(&Signal::notice("restarting".to_owned()))
+        .is_err());
+}
+
+#[test]
+fn test_rejecting_notice_from_client() {
+    let mut frame_handler = FrameHandler::new(Encoding::Json);
+    frame_handler
+        .handle_frame(br#"{"type":"hello","version":1,"features":["notice"]}"#)
+        .unwrap();
+
+    match frame_handler
+        .handle_frame(br#"{"type":"notice","message":"restarting"}"#)
+        .unwrap()
+    {
+        Some(Action::Reply(Frame::Text(text))) => assert_eq!(
+            text,
+            r#"{"type":"server signal","message":"notice signals are only sent by the server"}"#
+        ),
+        other => panic!("expected an error message, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_closing_on_unsupported_version() {
+    let mut frame_handler = FrameHandler::new(Encoding::Json);
+
+    match frame_handler
+        .handle_frame(br#"{"type":"hello","version":2,"features":[]}"#)
+        .unwrap()
+    {
+        Some(Action::Close(Some(Frame::Text(text)))) => assert_eq!(
+            text,
+            r#"{"type":"unsupported version","message":"protocol version 2 is not supported, server speaks version 1"}"#
+        ),
+        other => panic!("expected to close, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_joining_room_requested_over_json_rpc() {
+    //given
+    let mut frame_handler = FrameHandler::new(Encoding::JsonRpc);
+    let request = br#"{"jsonrpc":"2.0","id":3,"method":"room.join","params":{"room":"lobby"}}"#;
+
+    //when
+    let action = frame_handler.handle_frame(request).unwrap();
+    let reply = frame_handler
+        .joined_room(
+            Some(serde_json::json!(3)),
+            "lobby",
+            Ok(vec!["caller".to_owned()]),
+        )
+        .unwrap();
+
+    //then
+    match action {
+        Some(Action::JoinRoom(Some(id), room)) => {
+            assert_eq!(id, serde_json::json!(3));
+            assert_eq!(room, "lobby");
+        }
+        other => panic!("expected to join the room, got {:?}", other),
+    }
+    match reply {
+        Some(Frame::Text(text)) => assert_eq!(
+            serde_json::from_str::<Value>(&text).unwrap(),
+            serde_json::json!({ "jsonrpc": "2.0", "id": 3, "result": { "room": "lobby", "members": ["caller"] } })
+        ),
+        other => panic!("expected a response, got {:?}", other),
+    }
+}
@@ -0,0 +1,409 @@
+use actix::prelude::{Actor, ActorContext, Addr, AsyncContext, Context, Handler};
+use actix_web::http::header;
+use actix_web::{HttpRequest, HttpResponse};
+use futures::channel::oneshot;
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use super::admin::{bearer_token, constant_time_eq};
+use super::signal::{Feature, HangupMessage, IceCandidate, SessionDescriptionMessage};
+use super::signal_router::CloseMessage;
+use super::signal_socket::into_service_releated_error;
+use super::{
+    admit, Error, ExitMessage, JoinMessage, Signal, SignalMessage, SignalRouter,
+    SignalServerStateData,
+};
+
+/// How long the target has to answer an offer before the request fails.
+const ANSWER_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long a session lives without a trickled candidate or a signal from
+/// its target, for clients that never `DELETE` it.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Characters escaped in a path segment: all but the unreserved ones.
+const PATH_SEGMENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+const SDP_CONTENT_TYPE: &str = "application/sdp";
+const TRICKLE_ICE_CONTENT_TYPE: &str = "application/trickle-ice-sdpfrag";
+
+/// Signalling peer standing in for an HTTP client during an offer/answer
+/// exchange. It joins the router under its own name, so the target answers
+/// it like any other connected user.
+pub struct HttpSession {
+    session_name: String,
+    target: String,
+    signal_router: Addr<SignalRouter>,
+    http_sessions: Arc<HttpSessions>,
+    answer_sender: Option<oneshot::Sender<SessionDescriptionMessage>>,
+    joined: bool,
+}
+
+impl Actor for HttpSession {
+    type Context = Context<Self>;
+
+    fn started(&mut self, context: &mut Self::Context) {
+        context.run_interval(IDLE_CHECK_INTERVAL, |session, context| {
+            if session.http_sessions.idle_for(&session.session_name) >= Some(IDLE_TIMEOUT) {
+                context.stop();
+            }
+        });
+    }
+
+    fn stopped(&mut self, _: &mut Self::Context) {
+        self.http_sessions.remove(&self.session_name);
+        if self.joined {
+            self.signal_router
+                .do_send(ExitMessage::from(self.session_name.clone()));
+        }
+    }
+}
+
+impl Handler<Signal> for HttpSession {
+    type Result = Result<(), Error>;
+
+    fn handle(&mut self, message: Signal, _: &mut Self::Context) -> Self::Result {
+        self.http_sessions.touch(&self.session_name);
+        match message {
+            Signal::Answer(answer) => {
+                // only the target the offer went to may answer it, which the
+                // router vouches for by naming the member that sent it
+                if answer.name != self.target {
+                    return Err(Error::Forbidden(self.session_name.clone()));
+                }
+                if let Some(answer_sender) = self.answer_sender.take() {
+                    let _ = answer_sender.send(answer);
+                }
+            }
+            // HTTP clients only learn the candidates gathered into the answer
+            Signal::NewIceCandidate(_) => {
+                return Err(Error::TrickleNotSupported(self.session_name.clone()))
+            }
+            // HTTP clients have nobody to show notices to
+            Signal::Notice(_) => return Err(Error::FeatureNotNegotiated(Feature::Notice)),
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// Marks a session as joined to the router, so it exits when stopped.
+pub(crate) struct JoinedMessage;
+
+impl actix::Message for JoinedMessage {
+    type Result = ();
+}
+
+impl Handler<JoinedMessage> for HttpSession {
+    type Result = ();
+
+    fn handle(&mut self, _: JoinedMessage, _: &mut Self::Context) -> Self::Result {
+        self.joined = true;
+    }
+}
+
+impl Handler<CloseMessage> for HttpSession {
+    type Result = ();
+
+    fn handle(&mut self, _: CloseMessage, context: &mut Self::Context) -> Self::Result {
+        context.stop();
+    }
+}
+
+struct SessionEntry {
+    target: String,
+    session_addr: Addr<HttpSession>,
+    last_activity: Instant,
+}
+
+/// Live HTTP sessions, addressed by their session name.
+#[derive(Default)]
+pub struct HttpSessions {
+    sessions: Mutex<HashMap<String, SessionEntry>>,
+}
+
+impl HttpSessions {
+    /// Starts a session joined to the router. The returned receiver resolves
+    /// with the first answer addressed to the session.
+    pub async fn open(
+        self: &Arc<Self>,
+        signal_router: &Addr<SignalRouter>,
+        target: &str,
+        metadata: Option<Value>,
+    ) -> Result<(String, oneshot::Receiver<SessionDescriptionMessage>), Error> {
+        let session_name = Uuid::new_v4().to_hyphenated().to_string();
+        let (answer_sender, answer_receiver) = oneshot::channel();
+        let session_addr = HttpSession {
+            session_name: session_name.clone(),
+            target: target.to_owned(),
+            signal_router: signal_router.clone(),
+            http_sessions: self.clone(),
+            answer_sender: Some(answer_sender),
+            joined: false,
+        }
+        .start();
+
+        let join_result = signal_router
+            .send(
+                JoinMessage::new(session_name.clone(), session_addr.clone().recipient())
+                    .protocol("http")
+                    .metadata(metadata)
+                    .close_recipient(session_addr.clone().recipient()),
+            )
+            .await;
+        if !matches!(join_result, Ok(Ok(()))) {
+            session_addr.do_send(CloseMessage::new("join failed".to_owned()));
+            return Err(Error::ServiceUnavailable);
+        }
+        session_addr.do_send(JoinedMessage);
+
+        self.sessions.lock().unwrap().insert(
+            session_name.clone(),
+            SessionEntry {
+                target: target.to_owned(),
+                session_addr,
+                last_activity: Instant::now(),
+            },
+        );
+        Ok((session_name, answer_receiver))
+    }
+
+    /// Whether `session_name` is a live session negotiated with `target`.
+    pub fn contains(&self, target: &str, session_name: &str) -> bool {
+        self.sessions
+            .lock()
+            .unwrap()
+            .get(session_name)
+            .map(|entry| entry.target == target)
+            .unwrap_or(false)
+    }
+
+    /// Keeps `session_name` from expiring for another `IDLE_TIMEOUT`.
+    fn touch(&self, session_name: &str) {
+        if let Some(entry) = self.sessions.lock().unwrap().get_mut(session_name) {
+            entry.last_activity = Instant::now();
+        }
+    }
+
+    fn idle_for(&self, session_name: &str) -> Option<Duration> {
+        self.sessions
+            .lock()
+            .unwrap()
+            .get(session_name)
+            .map(|entry| entry.last_activity.elapsed())
+    }
+
+    fn remove(&self, session_name: &str) {
+        self.sessions.lock().unwrap().remove(session_name);
+    }
+
+    pub fn close(&self, target: &str, session_name: &str) -> Result<(), Error> {
+        let mut sessions = self.sessions.lock().unwrap();
+        match sessions.get(session_name) {
+            Some(entry) if entry.target == target => {
+                entry
+                    .session_addr
+                    .do_send(CloseMessage::new("session torn down".to_owned()));
+                sessions.remove(session_name);
+                Ok(())
+            }
+            _ => Err(Error::SessionNotFound(session_name.to_owned())),
+        }
+    }
+}
+
+/// Relays the SDP offer in `body` to `target` and responds with its answer,
+/// locating the new session under `resource`.
+pub async fn negotiate(
+    state: &SignalServerStateData,
+    resource: &str,
+    target: &str,
+    request: &HttpRequest,
+    body: String,
+) -> Result<HttpResponse, Error> {
+    authorize(state, request)?;
+    if !has_content_type(request, SDP_CONTENT_TYPE) {
+        return Ok(HttpResponse::UnsupportedMediaType().finish());
+    }
+    // sessions are named by the server, as their name is part of the
+    // resource URL, so only the metadata of the admission is kept
+    let admission = match admit(state, request, None).await {
+        Ok(admission) => admission,
+        Err(response) => return Ok(response),
+    };
+
+    let (session_name, answer_receiver) = state
+        .http_sessions
+        .open(&state.signal_router, target, admission.metadata)
+        .await?;
+    let offer = Signal::Offer(SessionDescriptionMessage::new(
+        target.to_owned(),
+        session_name.clone(),
+        body,
+    ));
+    let answer = async {
+        route(state, &session_name, offer).await?;
+        actix_rt::time::timeout(ANSWER_TIMEOUT, answer_receiver)
+            .await
+            .map_err(|_| Error::NegotiationTimeout)?
+            .map_err(|_| Error::ConnectionClosed)
+    }
+    .await;
+
+    match answer {
+        Ok(answer) => Ok(HttpResponse::Created()
+            .content_type(SDP_CONTENT_TYPE)
+            .header(header::LOCATION, location(resource, target, &session_name))
+            .body(answer.sdp().to_owned())),
+        Err(err) => {
+            let _ = state.http_sessions.close(target, &session_name);
+            Err(err)
+        }
+    }
+}
+
+/// Path of a session, e.g. `/whip/{target}/{session}`.
+fn location(resource: &str, target: &str, session_name: &str) -> String {
+    format!(
+        "{}/{}/{}",
+        resource,
+        utf8_percent_encode(target, PATH_SEGMENT),
+        utf8_percent_encode(session_name, PATH_SEGMENT)
+    )
+}
+
+/// Forwards the ICE candidates trickled by a session to its target.
+pub async fn trickle(
+    state: &SignalServerStateData,
+    target: &str,
+    session_name: &str,
+    request: &HttpRequest,
+    body: &str,
+) -> Result<HttpResponse, Error> {
+    authorize(state, request)?;
+    if !state.http_sessions.contains(target, session_name) {
+        return Err(Error::SessionNotFound(session_name.to_owned()));
+    }
+    state.http_sessions.touch(session_name);
+    if !has_content_type(request, TRICKLE_ICE_CONTENT_TYPE) {
+        return Ok(HttpResponse::UnsupportedMediaType().finish());
+    }
+
+    for ice_candidate in parse_sdp_fragment(target, body) {
+        route(state, session_name, Signal::NewIceCandidate(ice_candidate)).await?;
+    }
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Ends a session, hanging up on its target.
+pub async fn teardown(
+    state: &SignalServerStateData,
+    target: &str,
+    session_name: &str,
+    request: &HttpRequest,
+) -> Result<HttpResponse, Error> {
+    authorize(state, request)?;
+    if !state.http_sessions.contains(target, session_name) {
+        return Err(Error::SessionNotFound(session_name.to_owned()));
+    }
+
+    let hangup = HangupMessage::new(target.to_owned(), session_name.to_owned());
+    // the target may be gone already, which ends the session just as well
+    let _ = route(state, session_name, Signal::Hangup(hangup)).await;
+    state.http_sessions.close(target, session_name)?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Routes a signal on behalf of the session `session_name`.
+async fn route(
+    state: &SignalServerStateData,
+    session_name: &str,
+    signal: Signal,
+) -> Result<(), Error> {
+    state
+        .signal_router
+        .send(SignalMessage::translated(signal).sender(session_name.to_owned()))
+        .await
+        .unwrap_or_else(into_service_releated_error)
+}
+
+/// Checks the `Authorization: Bearer` token if the server has one.
+fn authorize(state: &SignalServerStateData, request: &HttpRequest) -> Result<(), Error> {
+    match (&state.whip_token, bearer_token(request)) {
+        (None, _) => Ok(()),
+        (Some(whip_token), Some(token))
+            if constant_time_eq(token.as_bytes(), whip_token.as_bytes()) =>
+        {
+            Ok(())
+        }
+        _ => Err(Error::Unauthorized),
+    }
+}
+
+fn has_content_type(request: &HttpRequest, content_type: &str) -> bool {
+    request
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.split(';').next().unwrap_or("").trim() == content_type)
+        .unwrap_or(false)
+}
+
+/// Extracts the `a=candidate` lines of a trickle ICE SDP fragment
+/// (RFC 8840), keeping the `a=mid` they belong to as `sdpMid`.
+pub(crate) fn parse_sdp_fragment(target: &str, fragment: &str) -> Vec<IceCandidate> {
+    let mut mid: Option<&str> = None;
+    let mut ice_candidates = Vec::new();
+
+    for line in fragment.lines().map(str::trim) {
+        if let Some(value) = line.strip_prefix("a=mid:") {
+            mid = Some(value);
+        } else if let Some(candidate) = line.strip_prefix("a=") {
+            if candidate.starts_with("candidate:") {
+                let mut ice_candidate = IceCandidate::new(target.to_owned(), candidate.to_owned());
+                if let Some(mid) = mid {
+                    ice_candidate
+                        .extra
+                        .insert("sdpMid".to_owned(), serde_json::json!(mid));
+                }
+                ice_candidates.push(ice_candidate);
+            }
+        }
+    }
+
+    ice_candidates
+}
+
+#[test]
+fn test_parsing_sdp_fragment() {
+    let fragment = "a=ice-ufrag:EsAw\r\na=ice-pwd:P2uYro0UCOQ4zxjKXaWCBui1\r\nm=audio 9 RTP/AVP 0\r\na=mid:0\r\na=candidate:1387637174 1 udp 2122260223 192.0.2.1 61764 typ host generation 0\r\na=end-of-candidates\r\n";
+
+    let mut expected_ice_candidate = IceCandidate::new(
+        "publisher".to_owned(),
+        "candidate:1387637174 1 udp 2122260223 192.0.2.1 61764 typ host generation 0".to_owned(),
+    );
+    expected_ice_candidate
+        .extra
+        .insert("sdpMid".to_owned(), serde_json::json!("0"));
+
+    assert_eq!(
+        parse_sdp_fragment("publisher", fragment),
+        vec![expected_ice_candidate]
+    );
+}
+
+#[test]
+fn test_encoding_location() {
+    assert_eq!(
+        location("/whip", "studio a/b?c", "0f1e"),
+        "/whip/studio%20a%2Fb%3Fc/0f1e"
+    );
+}
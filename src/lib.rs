@@ -0,0 +1,220 @@
+//! WebRTC signalling server built on actix-web.
+//!
+//! Peers connect to `/signal` over WebSocket, get a name assigned and
+//! exchange offers, answers and ICE candidates through the `SignalRouter`.
+//! Embed it into an existing application with `SignalServer`:
+//!
+//! ```no_run
+//! use actix_web::{App, HttpServer};
+//! use signalling_server::SignalServer;
+//!
+//! #[actix_rt::main]
+//! async fn main() -> std::io::Result<()> {
+//!     let signal_server = SignalServer::builder().build();
+//!     HttpServer::new(move || App::new().configure(|config| signal_server.configure(config)))
+//!         .bind("0.0.0.0:8080")?
+//!         .run()
+//!         .await
+//! }
+//! ```
+
+use actix::prelude::{Actor, Addr};
+use actix_web::{web, HttpRequest, HttpResponse, Responder, ResponseError};
+use actix_web_actors::ws;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use admission::{Admission, AdmissionRequest};
+use dashboard::Dashboard;
+use http_session::HttpSessions;
+use metrics::Metrics;
+use peerjs::PeerJsTokens;
+use signal::{Encoding, Signal};
+use sse::SseSessions;
+use webhook::{Webhook, WebhookPublisher};
+
+pub use admission::AdmissionHook;
+pub use error::{CodecError, Error, ErrorMessage};
+pub use field_passthrough::FieldPassthrough;
+pub use origin::OriginAllowlist;
+pub use relay_policy::RelayPolicy;
+pub use server::{SignalServer, SignalServerBuilder};
+pub use signal_policy::SignalPolicy;
+pub use signal_router::{
+    ExitMessage, JoinMessage, JoinRoomMessage, MembersMessage, SignalMessage, SignalRouter,
+};
+pub use signal_socket::SignalSocket;
+pub use telemetry::{OtlpLayer, OtlpLayerBuilder};
+
+mod admin;
+mod admission;
+mod dashboard;
+mod error;
+mod field_passthrough;
+/// Public only for the fuzz targets, not covered by semver.
+#[cfg(feature = "fuzzing")]
+#[doc(hidden)]
+pub mod frame_handler;
+#[cfg(not(feature = "fuzzing"))]
+mod frame_handler;
+mod http_session;
+mod metrics;
+mod origin;
+mod peerjs;
+mod relay_policy;
+mod server;
+/// Signals exchanged with clients and how they are encoded.
+pub mod signal;
+mod signal_policy;
+mod signal_router;
+mod signal_socket;
+mod sip;
+mod sse;
+mod telemetry;
+/// Events delivered to a webhook and how receivers verify them.
+pub mod webhook;
+mod whep;
+mod whip;
+
+type SignalServerStateData = web::Data<Arc<SignalServerState>>;
+
+struct SignalServerState {
+    signal_router: Addr<SignalRouter>,
+    origin_allowlist: OriginAllowlist,
+    metrics: Metrics,
+    http_sessions: Arc<HttpSessions>,
+    sse_sessions: Arc<SseSessions>,
+    peerjs_tokens: Arc<PeerJsTokens>,
+    adapters: Adapters,
+    log_signal_bodies: bool,
+    admin_token: Option<String>,
+    whip_token: Option<String>,
+    admission_hook: Option<AdmissionHook>,
+    /// Running along with the admin API.
+    dashboard: Option<Addr<Dashboard>>,
+    /// Kept here as the router only holds a recipient of it.
+    _webhook_publisher: Option<Addr<WebhookPublisher>>,
+}
+
+impl SignalServerState {
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        signal_router: Addr<SignalRouter>,
+        origin_allowlist: OriginAllowlist,
+        adapters: Adapters,
+        log_signal_bodies: bool,
+        admin_token: Option<String>,
+        whip_token: Option<String>,
+        admission_hook: Option<AdmissionHook>,
+        webhook: Option<Webhook>,
+    ) -> Self {
+        let dashboard = admin_token
+            .as_ref()
+            .map(|_| Dashboard::new(signal_router.clone()).start());
+        let webhook_publisher = webhook.map(|webhook| webhook.start(&signal_router));
+        SignalServerState {
+            signal_router,
+            origin_allowlist,
+            metrics: Metrics::default(),
+            http_sessions: Arc::default(),
+            sse_sessions: Arc::default(),
+            peerjs_tokens: Arc::default(),
+            adapters,
+            log_signal_bodies,
+            admin_token,
+            whip_token,
+            admission_hook,
+            dashboard,
+            _webhook_publisher: webhook_publisher,
+        }
+    }
+}
+
+/// Endpoints mounted next to `/signal`.
+struct Adapters {
+    sse: bool,
+    /// API key of the PeerJS server, which is mounted if there is one.
+    peerjs_key: Option<String>,
+    /// Domain of the SIP gateway, which is mounted if there is one.
+    sip_domain: Option<String>,
+    metrics: bool,
+    whip: bool,
+    whep: bool,
+}
+
+#[derive(serde::Deserialize)]
+struct SignalQuery {
+    /// Name asked of the admission hook, ignored without one.
+    name: Option<String>,
+}
+
+async fn signal(
+    state: SignalServerStateData,
+    request: HttpRequest,
+    query: web::Query<SignalQuery>,
+    stream: web::Payload,
+) -> Result<HttpResponse, actix_web::Error> {
+    if !state.origin_allowlist.is_allowed(&request) {
+        state.metrics.reject_origin();
+        return Ok(HttpResponse::Forbidden().body("origin is not allowed"));
+    }
+
+    let admission = match admit(&state, &request, query.name.as_deref()).await {
+        Ok(admission) => admission,
+        Err(response) => return Ok(response),
+    };
+    // a taken name fails the join, which closes the socket
+    let user_name = admission_name(&admission);
+
+    let encoding = Encoding::negotiate(&request);
+    let signal_socket = SignalSocket::new(user_name, &state.signal_router, encoding)
+        .remote_addr(request.peer_addr())
+        .metadata(admission.metadata)
+        .log_signal_bodies(state.log_signal_bodies);
+    ws::start_with_protocols(signal_socket, Encoding::PROTOCOLS, &request, stream)
+}
+
+/// Asks the admission hook, if there is one, whether the client of
+/// `request` may join the router, admitting everyone without a hook.
+/// Resolves to the response refusing the client otherwise.
+async fn admit(
+    state: &SignalServerState,
+    request: &HttpRequest,
+    requested_name: Option<&str>,
+) -> Result<Admission, HttpResponse> {
+    let admission_hook = match &state.admission_hook {
+        Some(admission_hook) => admission_hook,
+        None => {
+            return Ok(Admission {
+                allow: true,
+                ..Admission::default()
+            })
+        }
+    };
+    let admission = admission_hook
+        .admit(&AdmissionRequest::new(request), requested_name)
+        .await
+        .map_err(|err| err.error_response())?;
+    if !admission.allow {
+        state.metrics.deny_admission();
+        let reason = admission
+            .reason
+            .unwrap_or_else(|| "admission denied".to_owned());
+        return Err(HttpResponse::Forbidden().body(reason));
+    }
+    Ok(admission)
+}
+
+/// Name granted by the admission hook, or a random one.
+fn admission_name(admission: &Admission) -> String {
+    match admission.name.as_deref() {
+        Some(name) if !name.is_empty() => name.to_owned(),
+        _ => Uuid::new_v4().to_hyphenated().to_string(),
+    }
+}
+
+async fn metrics(state: SignalServerStateData) -> impl Responder {
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(state.metrics.render())
+}
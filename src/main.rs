@@ -1,77 +1,246 @@
-use actix::prelude::{Actor, Addr};
-use actix_web::{middleware, web, App, HttpRequest, HttpServer, Responder};
-use actix_web_actors::ws;
-use futures::future;
-use futures::FutureExt;
+use actix_web::{middleware, App, HttpServer};
 use std::str::FromStr;
-use std::sync::Arc;
-use uuid::Uuid;
+use tracing::Level;
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::{fmt, EnvFilter};
 
-use error::Error;
-use signal::Signal;
-use signal_router::{ExitMessage, JoinMessage, SignalMessage, SignalRouter};
-use signal_socket::SignalSocket;
-
-mod error;
-mod signal;
-mod signal_router;
-mod signal_socket;
-
-type SignalServerStateData = web::Data<Arc<SignalServerState>>;
-
-struct SignalServerState {
-    signal_router: Addr<SignalRouter>,
-}
-
-impl SignalServerState {
-    fn new(signal_router: Addr<SignalRouter>) -> Self {
-        SignalServerState { signal_router }
-    }
-}
-
-async fn signal(
-    state: SignalServerStateData,
-    request: HttpRequest,
-    stream: web::Payload,
-) -> impl Responder {
-    let user_name = Uuid::new_v4();
-    ws::start(
-        SignalSocket::new(user_name.to_hyphenated(), &state.signal_router),
-        &request,
-        stream,
-    )
-}
+use signalling_server::webhook::Webhook;
+use signalling_server::{
+    AdmissionHook, FieldPassthrough, OriginAllowlist, OtlpLayer, RelayPolicy, SignalPolicy,
+    SignalServer,
+};
 
 #[actix_rt::main]
 async fn main() -> std::io::Result<()> {
-    let server_fut = async {
-        let signal_router = SignalRouter::default();
-        let signal_router_addr = signal_router.start();
-        let state = Arc::new(SignalServerState::new(signal_router_addr));
-        HttpServer::new(move || {
-            App::new()
-                .data(state.clone())
-                .wrap(middleware::Logger::default())
-                .service(web::resource("/signal").to(signal))
-        })
-    };
-    let app_matches_fut = app().map(clap::App::get_matches);
+    let matches = app().get_matches();
+    init_logging(
+        matches.value_of("log-format").unwrap_or("text"),
+        matches.value_of("otlp-endpoint"),
+    );
 
-    let (server, matches) = future::join(server_fut, app_matches_fut).await;
     let port = matches
         .value_of("port")
         .map(i32::from_str)
         .unwrap_or(Ok(80))
         .expect("couldn't parse port number");
+    let origin_allowlist =
+        OriginAllowlist::new(matches.values_of("allowed-origin").unwrap_or_default());
+
+    let max_relay_payload_size = matches
+        .value_of("max-relay-payload-size")
+        .map(usize::from_str)
+        .unwrap_or(Ok(16 * 1024))
+        .expect("couldn't parse max relay payload size");
+    let relay_kinds = matches
+        .values_of("relay-kind")
+        .map(|kinds| kinds.map(str::to_owned).collect());
+    let relay_policy = RelayPolicy::new(max_relay_payload_size, relay_kinds);
+
+    let signal_policy = matches
+        .value_of("signal-policy-file")
+        .map(|path| {
+            let rules = std::fs::read_to_string(path).expect("couldn't read signal policy file");
+            serde_json::from_str::<SignalPolicy>(&rules).expect("couldn't parse signal policy file")
+        })
+        .unwrap_or_default();
+
+    let field_passthrough = if matches.is_present("passthrough-fields") {
+        FieldPassthrough::enabled(
+            matches
+                .values_of("reserved-field")
+                .unwrap_or_default()
+                .map(str::to_owned),
+        )
+    } else {
+        FieldPassthrough::default()
+    };
+
+    let mut signal_server_builder = SignalServer::builder()
+        .origin_allowlist(origin_allowlist)
+        .relay_policy(relay_policy)
+        .signal_policy(signal_policy)
+        .field_passthrough(field_passthrough)
+        .sse(true)
+        .peerjs_key(matches.value_of("peerjs-key").unwrap_or("peerjs"))
+        .metrics(true)
+        .whip(true)
+        .whep(true)
+        .log_signal_bodies(matches.is_present("log-signal-bodies"));
+    if let Some(sip_domain) = matches.value_of("sip-domain") {
+        signal_server_builder = signal_server_builder.sip_domain(sip_domain);
+    }
+    if let Some(admin_token) = matches.value_of("admin-token") {
+        signal_server_builder = signal_server_builder.admin_token(admin_token);
+    }
+    if let Some(whip_token) = matches.value_of("whip-token") {
+        signal_server_builder = signal_server_builder.whip_token(whip_token);
+    }
+    if let Some(admission_hook_url) = matches.value_of("admission-hook-url") {
+        signal_server_builder =
+            signal_server_builder.admission_hook(AdmissionHook::new(admission_hook_url));
+    }
+    if let Some(webhook_url) = matches.value_of("webhook-url") {
+        let mut webhook = Webhook::new(
+            webhook_url,
+            matches
+                .value_of("webhook-secret")
+                .expect("--webhook-url requires --webhook-secret"),
+        );
+        if let Some(dead_letter_file) = matches.value_of("webhook-dead-letter-file") {
+            webhook = webhook.dead_letter_file(dead_letter_file);
+        }
+        signal_server_builder = signal_server_builder.webhook(webhook);
+    }
+    let signal_server = signal_server_builder.build();
+
+    HttpServer::new(move || {
+        App::new()
+            .wrap(middleware::Logger::default())
+            .configure(|config| signal_server.configure(config))
+    })
+    .bind(format!("0.0.0.0:{}", port))?
+    .run()
+    .await
+}
+
+/// Logs to stdout, filtered by `RUST_LOG` and at info level otherwise, and
+/// exports the spans of signals to an OTLP collector if there is one.
+fn init_logging(format: &str, otlp_endpoint: Option<&str>) {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let log_layer = match format {
+        "json" => fmt::layer().json().with_filter(filter).boxed(),
+        _ => fmt::layer().with_filter(filter).boxed(),
+    };
+    let otlp_layer = otlp_endpoint.map(|endpoint| {
+        OtlpLayer::builder(endpoint)
+            .build()
+            .with_filter(Targets::new().with_target("signalling_server", Level::INFO))
+    });
 
-    server.bind(format!("0.0.0.0:{}", port))?.run().await
+    tracing_subscriber::registry()
+        .with(log_layer)
+        .with(otlp_layer)
+        .init();
 }
 
-async fn app() -> clap::App<'static, 'static> {
-    clap::App::new("asdf").arg(
-        clap::Arg::with_name("port")
-            .short("p")
-            .long("port")
-            .takes_value(true),
-    )
+fn app() -> clap::App<'static, 'static> {
+    clap::App::new("asdf")
+        .arg(
+            clap::Arg::with_name("port")
+                .short("p")
+                .long("port")
+                .takes_value(true),
+        )
+        .arg(
+            clap::Arg::with_name("allowed-origin")
+                .long("allowed-origin")
+                .help("origin allowed to open /signal, e.g. app.example.com or *.example.com")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            clap::Arg::with_name("max-relay-payload-size")
+                .long("max-relay-payload-size")
+                .help("maximum size in bytes of a relay payload serialized as JSON")
+                .takes_value(true),
+        )
+        .arg(
+            clap::Arg::with_name("relay-kind")
+                .long("relay-kind")
+                .help("relay kind allowed to be forwarded, any kind is allowed if omitted")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            clap::Arg::with_name("signal-policy-file")
+                .long("signal-policy-file")
+                .help("JSON rules deciding which users may signal which, any signal is allowed if omitted")
+                .takes_value(true),
+        )
+        .arg(
+            clap::Arg::with_name("peerjs-key")
+                .long("peerjs-key")
+                .help("API key PeerJS clients must connect with, defaults to peerjs")
+                .takes_value(true),
+        )
+        .arg(
+            clap::Arg::with_name("sip-domain")
+                .long("sip-domain")
+                .help("enables the SIP over WebSocket gateway on /sip for the given domain, registering users the admission hook admits")
+                .takes_value(true),
+        )
+        .arg(
+            clap::Arg::with_name("admin-token")
+                .long("admin-token")
+                .env("SIGNALLING_ADMIN_TOKEN")
+                .help("enables the /admin API for requests bearing this token")
+                .takes_value(true),
+        )
+        .arg(
+            clap::Arg::with_name("whip-token")
+                .long("whip-token")
+                .env("SIGNALLING_WHIP_TOKEN")
+                .help("requires WHIP and WHEP requests to bear this token")
+                .takes_value(true),
+        )
+        .arg(
+            clap::Arg::with_name("admission-hook-url")
+                .long("admission-hook-url")
+                .help("endpoint asked to admit, deny or name every client joining the router")
+                .takes_value(true),
+        )
+        .arg(
+            clap::Arg::with_name("webhook-url")
+                .long("webhook-url")
+                .help("endpoint joins, exits, offers, answers and hangups are POSTed to")
+                .requires("webhook-secret")
+                .takes_value(true),
+        )
+        .arg(
+            clap::Arg::with_name("webhook-secret")
+                .long("webhook-secret")
+                .env("SIGNALLING_WEBHOOK_SECRET")
+                .help("key the bodies of webhook events are signed with using HMAC-SHA256")
+                .takes_value(true),
+        )
+        .arg(
+            clap::Arg::with_name("webhook-dead-letter-file")
+                .long("webhook-dead-letter-file")
+                .help("file webhook events that couldn't be delivered are appended to")
+                .takes_value(true),
+        )
+        .arg(
+            clap::Arg::with_name("passthrough-fields")
+                .long("passthrough-fields")
+                .help("forward unknown top-level fields of offers, answers and candidates"),
+        )
+        .arg(
+            clap::Arg::with_name("reserved-field")
+                .long("reserved-field")
+                .help("additional field clients may not pass through")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            clap::Arg::with_name("log-format")
+                .long("log-format")
+                .help("format of the log lines on stdout")
+                .possible_values(&["text", "json"])
+                .takes_value(true),
+        )
+        .arg(
+            clap::Arg::with_name("log-signal-bodies")
+                .long("log-signal-bodies")
+                .help("log session descriptions and candidates instead of redacting them"),
+        )
+        .arg(
+            clap::Arg::with_name("otlp-endpoint")
+                .long("otlp-endpoint")
+                .help("OTLP/HTTP collector to export signal traces to, e.g. http://localhost:4318")
+                .takes_value(true),
+        )
 }
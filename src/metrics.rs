@@ -0,0 +1,53 @@
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Process-wide counters exposed on `/metrics` in the Prometheus text format.
+#[derive(Default)]
+pub struct Metrics {
+    rejected_origins: AtomicU64,
+    denied_admissions: AtomicU64,
+}
+
+impl Metrics {
+    pub fn reject_origin(&self) {
+        self.rejected_origins.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn deny_admission(&self) {
+        self.denied_admissions.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn render(&self) -> String {
+        let mut text = String::new();
+        write_counter(
+            &mut text,
+            "signalling_rejected_origins_total",
+            "WebSocket upgrades rejected because of a disallowed Origin header",
+            self.rejected_origins.load(Ordering::Relaxed),
+        );
+        write_counter(
+            &mut text,
+            "signalling_denied_admissions_total",
+            "WebSocket upgrades denied by the admission hook",
+            self.denied_admissions.load(Ordering::Relaxed),
+        );
+        text
+    }
+}
+
+fn write_counter(text: &mut String, name: &str, help: &str, value: u64) {
+    writeln!(text, "# HELP {} {}", name, help).unwrap();
+    writeln!(text, "# TYPE {} counter", name).unwrap();
+    writeln!(text, "{} {}", name, value).unwrap();
+}
+
+#[test]
+fn test_rendering_rejected_origins() {
+    let metrics = Metrics::default();
+    metrics.reject_origin();
+    metrics.reject_origin();
+
+    assert!(metrics
+        .render()
+        .contains("\nsignalling_rejected_origins_total 2\n"));
+}
@@ -0,0 +1,158 @@
+use actix_web::http::header;
+use actix_web::HttpRequest;
+
+#[derive(Clone, Debug, PartialEq)]
+enum HostPattern {
+    Exact(String),
+    Subdomain(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct OriginPattern {
+    /// Scheme the origin must have, any if the entry has none.
+    scheme: Option<String>,
+    host: HostPattern,
+}
+
+impl OriginPattern {
+    fn parse(pattern: &str) -> Self {
+        let (scheme, host) = split_scheme(pattern.trim());
+        let host = host.to_ascii_lowercase();
+        let host = if host.starts_with("*.") {
+            HostPattern::Subdomain(host[1..].to_owned())
+        } else {
+            HostPattern::Exact(host)
+        };
+        OriginPattern {
+            scheme: scheme.map(str::to_ascii_lowercase),
+            host,
+        }
+    }
+
+    fn matches(&self, scheme: Option<&str>, host: &str) -> bool {
+        if self.scheme.is_some() && self.scheme.as_deref() != scheme {
+            return false;
+        }
+        match &self.host {
+            HostPattern::Exact(allowed_host) => allowed_host == host,
+            // subdomains are allowed on any port
+            HostPattern::Subdomain(suffix) => strip_port(host).ends_with(suffix.as_str()),
+        }
+    }
+}
+
+/// Web origins allowed to open the signalling WebSocket.
+///
+/// Entries are hosts, optionally with a port, e.g. `localhost:8080`, or
+/// wildcard subdomains, e.g. `*.example.com`, which match on any port. An
+/// entry with a scheme, e.g. `https://app.example.com`, only accepts origins
+/// of that scheme.
+///
+/// An empty allowlist accepts every origin. Requests without an `Origin`
+/// header come from non-browser clients and are always accepted.
+#[derive(Clone, Debug, Default)]
+pub struct OriginAllowlist {
+    patterns: Vec<OriginPattern>,
+}
+
+impl OriginAllowlist {
+    pub fn new<I, S>(patterns: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        OriginAllowlist {
+            patterns: patterns
+                .into_iter()
+                .map(|pattern| OriginPattern::parse(pattern.as_ref()))
+                .collect(),
+        }
+    }
+
+    pub fn is_allowed(&self, request: &HttpRequest) -> bool {
+        match request.headers().get(header::ORIGIN) {
+            Some(origin) => origin
+                .to_str()
+                .map(|origin| self.is_origin_allowed(origin))
+                .unwrap_or(false),
+            None => true,
+        }
+    }
+
+    fn is_origin_allowed(&self, origin: &str) -> bool {
+        if self.patterns.is_empty() {
+            return true;
+        }
+
+        let (scheme, host) = split_scheme(origin);
+        let scheme = scheme.map(str::to_ascii_lowercase);
+        let host = host.to_ascii_lowercase();
+        self.patterns
+            .iter()
+            .any(|pattern| pattern.matches(scheme.as_deref(), &host))
+    }
+}
+
+fn split_scheme(origin: &str) -> (Option<&str>, &str) {
+    let (scheme, host) = match origin.find("://") {
+        Some(index) => (Some(&origin[..index]), &origin[index + 3..]),
+        None => (None, origin),
+    };
+    (scheme, host.trim_end_matches('/'))
+}
+
+fn strip_port(host: &str) -> &str {
+    match host.rfind(':') {
+        Some(index) if host[index + 1..].bytes().all(|byte| byte.is_ascii_digit()) => {
+            &host[..index]
+        }
+        _ => host,
+    }
+}
+
+#[test]
+fn test_empty_allowlist_accepts_any_origin() {
+    let allowlist = OriginAllowlist::default();
+
+    assert!(allowlist.is_origin_allowed("https://evil.example"));
+}
+
+#[test]
+fn test_exact_origin_matching() {
+    let allowlist = OriginAllowlist::new(["app.example.com", "https://localhost:8080"]);
+
+    assert!(allowlist.is_origin_allowed("https://app.example.com"));
+    assert!(allowlist.is_origin_allowed("http://app.example.com"));
+    assert!(allowlist.is_origin_allowed("https://localhost:8080"));
+    assert!(!allowlist.is_origin_allowed("https://other.example.com"));
+    assert!(!allowlist.is_origin_allowed("https://localhost:8081"));
+}
+
+#[test]
+fn test_wildcard_subdomain_matching() {
+    let allowlist = OriginAllowlist::new(["*.example.com"]);
+
+    assert!(allowlist.is_origin_allowed("https://app.example.com"));
+    assert!(allowlist.is_origin_allowed("https://a.b.example.com"));
+    assert!(!allowlist.is_origin_allowed("https://example.com"));
+    assert!(!allowlist.is_origin_allowed("https://evilexample.com"));
+}
+
+#[test]
+fn test_matching_scheme_of_entry() {
+    let allowlist = OriginAllowlist::new(["https://app.example.com", "https://*.example.org"]);
+
+    assert!(allowlist.is_origin_allowed("https://app.example.com"));
+    assert!(!allowlist.is_origin_allowed("http://app.example.com"));
+    assert!(allowlist.is_origin_allowed("https://a.example.org"));
+    assert!(!allowlist.is_origin_allowed("http://a.example.org"));
+}
+
+#[test]
+fn test_wildcard_subdomain_matching_any_port() {
+    let allowlist = OriginAllowlist::new(["*.example.com", "app.example.org"]);
+
+    assert!(allowlist.is_origin_allowed("https://a.example.com:8443"));
+    assert!(!allowlist.is_origin_allowed("https://a.evil.com:8443"));
+    assert!(!allowlist.is_origin_allowed("https://app.example.org:8443"));
+}
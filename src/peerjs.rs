@@ -0,0 +1,512 @@
+use actix::fut::{wrap_future, ActorFuture};
+use actix::prelude::{Actor, ActorContext, Addr, AsyncContext, Handler, StreamHandler};
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use actix_web_actors::ws;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use uuid::Uuid;
+
+use super::signal::{Extra, Feature, HangupMessage, IceCandidate, SessionDescriptionMessage};
+use super::signal_router::{CloseMessage, DisconnectMessage};
+use super::signal_socket::into_service_releated_error;
+use super::{
+    admit, Error, ExitMessage, JoinMessage, Signal, SignalMessage, SignalRouter,
+    SignalServerStateData,
+};
+
+/// Key of the unknown field carrying PeerJS connection metadata
+/// (`connectionId`, connection type, ...) of translated signals.
+const PEERJS_FIELD: &str = "peerjs";
+
+/// How often a reconnecting client tries to take over its id, waiting for
+/// the connection it replaces to exit in between.
+const REJOIN_ATTEMPTS: u32 = 10;
+const REJOIN_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(serde::Deserialize)]
+pub struct PeerJsQuery {
+    key: String,
+    id: String,
+    token: String,
+}
+
+/// Tokens peer ids were connected with. A client reconnecting with the token
+/// of its id replaces its previous connection, others can't take the id.
+#[derive(Default)]
+pub struct PeerJsTokens {
+    tokens: Mutex<HashMap<String, String>>,
+}
+
+impl PeerJsTokens {
+    fn token(&self, id: &str) -> Option<String> {
+        self.tokens.lock().unwrap().get(id).cloned()
+    }
+
+    fn insert(&self, id: String, token: String) {
+        self.tokens.lock().unwrap().insert(id, token);
+    }
+
+    fn remove(&self, id: &str) {
+        self.tokens.lock().unwrap().remove(id);
+    }
+}
+
+/// `GET /{peerjs_key}/id`: hands out a fresh peer id.
+pub async fn id() -> impl Responder {
+    HttpResponse::Ok()
+        .content_type("text/html")
+        .body(Uuid::new_v4().to_hyphenated().to_string())
+}
+
+/// `GET /peerjs?key=...&id=...&token=...`: WebSocket speaking the PeerJS
+/// server protocol.
+pub async fn socket(
+    state: SignalServerStateData,
+    query: web::Query<PeerJsQuery>,
+    request: HttpRequest,
+    stream: web::Payload,
+) -> Result<HttpResponse, actix_web::Error> {
+    if !state.origin_allowlist.is_allowed(&request) {
+        state.metrics.reject_origin();
+        return Ok(HttpResponse::Forbidden().body("origin is not allowed"));
+    }
+    let query = query.into_inner();
+    if query.id.is_empty() || query.token.is_empty() {
+        return Ok(HttpResponse::BadRequest().body("id and token are required"));
+    }
+    let admission = match admit(&state, &request, Some(&query.id)).await {
+        Ok(admission) => admission,
+        Err(response) => return Ok(response),
+    };
+    // PeerJS clients pick their id, so the hook can't name them otherwise
+    if admission
+        .name
+        .as_deref()
+        .is_some_and(|name| name != query.id)
+    {
+        return Ok(HttpResponse::Forbidden().body("id is not admitted"));
+    }
+
+    let mut peerjs_socket = PeerJsSocket::new(
+        query.id,
+        query.token,
+        &state.signal_router,
+        request.peer_addr(),
+    );
+    peerjs_socket.valid_key = state.adapters.peerjs_key.as_ref() == Some(&query.key);
+    peerjs_socket.peerjs_tokens = state.peerjs_tokens.clone();
+    peerjs_socket.metadata = admission.metadata;
+    ws::start(peerjs_socket, &request, stream)
+}
+
+/// Adapter translating between the PeerJS server protocol and `Signal`, so
+/// PeerJS clients can call native clients and vice versa.
+pub struct PeerJsSocket {
+    id: String,
+    token: String,
+    valid_key: bool,
+    joined: bool,
+    /// Whether the id is connected with the same token, so the client is
+    /// reconnecting.
+    reconnecting: bool,
+    join_attempts: u32,
+    peerjs_tokens: Arc<PeerJsTokens>,
+    signal_router: Addr<SignalRouter>,
+    remote_addr: Option<SocketAddr>,
+    /// Granted by the admission hook.
+    metadata: Option<Value>,
+    /// PeerJS metadata last exchanged with each remote peer, reused for
+    /// native signals that don't carry any.
+    connections: HashMap<String, Value>,
+}
+
+impl PeerJsSocket {
+    fn new(
+        id: String,
+        token: String,
+        signal_router: &Addr<SignalRouter>,
+        remote_addr: Option<SocketAddr>,
+    ) -> Self {
+        PeerJsSocket {
+            id,
+            token,
+            valid_key: true,
+            joined: false,
+            reconnecting: false,
+            join_attempts: 0,
+            peerjs_tokens: Arc::default(),
+            signal_router: signal_router.clone(),
+            remote_addr,
+            metadata: None,
+            connections: HashMap::new(),
+        }
+    }
+
+    fn send(&self, message: Value, context: &mut ws::WebsocketContext<Self>) {
+        context.text(message.to_string())
+    }
+
+    fn reject_id(&self, context: &mut ws::WebsocketContext<Self>) {
+        self.send(
+            json!({ "type": "ID-TAKEN", "payload": { "msg": "ID is taken" } }),
+            context,
+        );
+        context.stop();
+    }
+
+    fn join(&mut self, context: &mut ws::WebsocketContext<Self>) {
+        self.join_attempts += 1;
+        let joining_router_future = self.signal_router.send(
+            JoinMessage::new(self.id.clone(), context.address().recipient())
+                .protocol("peerjs")
+                .remote_addr(self.remote_addr)
+                .metadata(self.metadata.clone())
+                .close_recipient(context.address().recipient()),
+        );
+        context.wait(wrap_future(joining_router_future).map(
+            |join_result, socket: &mut Self, context| match join_result {
+                Ok(Ok(())) => {
+                    socket.joined = true;
+                    socket
+                        .peerjs_tokens
+                        .insert(socket.id.clone(), socket.token.clone());
+                    socket.send(json!({ "type": "OPEN" }), context);
+                }
+                // the client reconnected before its previous connection went
+                // away, which is closed for this one to take over
+                Ok(Err(())) if socket.reconnecting && socket.join_attempts < REJOIN_ATTEMPTS => {
+                    if socket.join_attempts == 1 {
+                        socket.signal_router.do_send(DisconnectMessage::new(
+                            socket.id.clone(),
+                            "connected again".to_owned(),
+                        ));
+                    }
+                    context.run_later(REJOIN_INTERVAL, |socket, context| socket.join(context));
+                }
+                Ok(Err(())) => socket.reject_id(context),
+                Err(_) => context.stop(),
+            },
+        ));
+    }
+
+    fn handle_message(&mut self, message: Value, context: &mut ws::WebsocketContext<Self>) {
+        let dst = message["dst"].as_str().unwrap_or_default().to_owned();
+        let signal = match message["type"].as_str() {
+            Some("OFFER") | Some("ANSWER") | Some("CANDIDATE") | Some("LEAVE") => {
+                self.remember(&dst, &message["payload"]);
+                to_signal(&self.id, &dst, &message)
+            }
+            _ => None, // HEARTBEAT and unknown types
+        };
+
+        if let Some(signal) = signal {
+            let signal_routing_future = self
+                .signal_router
+                .send(SignalMessage::translated(signal).sender(self.id.clone()));
+            context.spawn(wrap_future(signal_routing_future).map(
+                move |signal_routing_result, socket: &mut Self, context| {
+                    match signal_routing_result.unwrap_or_else(into_service_releated_error) {
+                        Ok(()) => {}
+                        Err(Error::TargetNotFound(_)) | Err(Error::ConnectionClosed) => socket
+                            .send(
+                                json!({ "type": "EXPIRE", "src": dst, "dst": socket.id }),
+                                context,
+                            ),
+                        Err(err) => socket.send(
+                            json!({ "type": "ERROR", "payload": { "msg": err.to_string() } }),
+                            context,
+                        ),
+                    }
+                },
+            ));
+        }
+    }
+
+    fn remember(&mut self, peer: &str, payload: &Value) {
+        if let Some(connection_id) = payload.get("connectionId") {
+            self.connections.insert(
+                peer.to_owned(),
+                json!({
+                    "connectionId": connection_id,
+                    "type": payload.get("type").cloned().unwrap_or_else(|| json!("media")),
+                }),
+            );
+        }
+    }
+
+    fn connection(&mut self, peer: &str) -> Value {
+        self.connections
+            .entry(peer.to_owned())
+            .or_insert_with(
+                || json!({ "connectionId": format!("native_{}", peer), "type": "media" }),
+            )
+            .clone()
+    }
+
+    fn translate_signal(&mut self, signal: Signal) -> Option<Value> {
+        let (message_type, src, payload) = match signal {
+            Signal::Offer(sdp_signal) => {
+                let payload = self.payload(&sdp_signal.name, &sdp_signal.extra);
+                let sdp = json!({ "type": "offer", "sdp": sdp_signal.sdp() });
+                ("OFFER", sdp_signal.name, with_field(payload, "sdp", sdp))
+            }
+            Signal::Answer(sdp_signal) => {
+                let payload = self.payload(&sdp_signal.name, &sdp_signal.extra);
+                let sdp = json!({ "type": "answer", "sdp": sdp_signal.sdp() });
+                ("ANSWER", sdp_signal.name, with_field(payload, "sdp", sdp))
+            }
+            Signal::NewIceCandidate(ice_candidate) => {
+                // the router names every candidate after the peer sending it
+                let src = ice_candidate.name.clone()?;
+                let payload = self.payload(&src, &ice_candidate.extra);
+                let candidate = json!({
+                    "candidate": ice_candidate.candidate(),
+                    "sdpMid": ice_candidate.extra.get("sdpMid"),
+                    "sdpMLineIndex": ice_candidate.extra.get("sdpMLineIndex"),
+                });
+                (
+                    "CANDIDATE",
+                    src,
+                    with_field(payload, "candidate", candidate),
+                )
+            }
+            Signal::Hangup(hangup_message) => ("LEAVE", hangup_message.name, Value::Null),
+            _ => return None,
+        };
+
+        Some(json!({ "type": message_type, "src": src, "dst": self.id, "payload": payload }))
+    }
+
+    fn payload(&mut self, peer: &str, extra: &Extra) -> Value {
+        match extra.get(PEERJS_FIELD) {
+            Some(payload) if payload.is_object() => {
+                self.remember(peer, payload);
+                payload.clone()
+            }
+            _ => self.connection(peer),
+        }
+    }
+}
+
+fn with_field(mut payload: Value, key: &str, value: Value) -> Value {
+    if let Some(payload) = payload.as_object_mut() {
+        payload.insert(key.to_owned(), value);
+    }
+    payload
+}
+
+/// Translates a PeerJS client message sent by `src` into a `Signal`.
+fn to_signal(src: &str, dst: &str, message: &Value) -> Option<Signal> {
+    let payload = &message["payload"];
+    let mut metadata = payload.as_object().cloned().unwrap_or_default();
+
+    match message["type"].as_str()? {
+        message_type @ "OFFER" | message_type @ "ANSWER" => {
+            metadata.remove("sdp");
+            let mut sdp_signal = SessionDescriptionMessage::new(
+                dst.to_owned(),
+                src.to_owned(),
+                payload["sdp"]["sdp"].as_str()?.to_owned(),
+            );
+            sdp_signal
+                .extra
+                .insert(PEERJS_FIELD.to_owned(), Value::Object(metadata));
+            if message_type == "OFFER" {
+                Some(Signal::Offer(sdp_signal))
+            } else {
+                Some(Signal::Answer(sdp_signal))
+            }
+        }
+        "CANDIDATE" => {
+            let candidate = &payload["candidate"];
+            metadata.remove("candidate");
+            let mut ice_candidate =
+                IceCandidate::new(dst.to_owned(), candidate["candidate"].as_str()?.to_owned());
+            ice_candidate.name = Some(src.to_owned());
+            for key in &["sdpMid", "sdpMLineIndex"] {
+                if let Some(value) = candidate.get(*key) {
+                    ice_candidate.extra.insert((*key).to_owned(), value.clone());
+                }
+            }
+            ice_candidate
+                .extra
+                .insert(PEERJS_FIELD.to_owned(), Value::Object(metadata));
+            Some(Signal::NewIceCandidate(ice_candidate))
+        }
+        "LEAVE" => Some(Signal::Hangup(HangupMessage::new(
+            dst.to_owned(),
+            src.to_owned(),
+        ))),
+        _ => None,
+    }
+}
+
+impl Actor for PeerJsSocket {
+    type Context = ws::WebsocketContext<Self>;
+
+    fn started(&mut self, context: &mut Self::Context) {
+        if !self.valid_key {
+            self.send(
+                json!({ "type": "ERROR", "payload": { "msg": "Invalid key provided" } }),
+                context,
+            );
+            context.stop();
+            return;
+        }
+        match self.peerjs_tokens.token(&self.id) {
+            Some(token) if token != self.token => {
+                self.reject_id(context);
+                return;
+            }
+            Some(_) => self.reconnecting = true,
+            None => {}
+        }
+        self.join(context);
+    }
+
+    fn stopped(&mut self, _: &mut Self::Context) {
+        if self.joined {
+            self.peerjs_tokens.remove(&self.id);
+            self.signal_router
+                .do_send(ExitMessage::from(self.id.clone()));
+        }
+    }
+}
+
+impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for PeerJsSocket {
+    fn handle(
+        &mut self,
+        message: Result<ws::Message, ws::ProtocolError>,
+        context: &mut Self::Context,
+    ) {
+        match message {
+            Ok(ws::Message::Text(text_message)) => match serde_json::from_str(&text_message) {
+                Ok(message) => self.handle_message(message, context),
+                Err(_) => self.send(
+                    json!({ "type": "ERROR", "payload": { "msg": "Invalid message" } }),
+                    context,
+                ),
+            },
+            Ok(ws::Message::Ping(message)) => context.pong(&message),
+            Ok(ws::Message::Close(_)) | Err(_) => context.stop(),
+            Ok(_) => {}
+        }
+    }
+}
+
+impl Handler<Signal> for PeerJsSocket {
+    type Result = Result<(), Error>;
+
+    fn handle(&mut self, message: Signal, context: &mut Self::Context) -> Self::Result {
+        // the PeerJS protocol has no message carrying a notice
+        if let Signal::Notice(_) = message {
+            return Err(Error::FeatureNotNegotiated(Feature::Notice));
+        }
+        if let Some(message) = self.translate_signal(message) {
+            self.send(message, context);
+        }
+        Ok(())
+    }
+}
+
+impl Handler<CloseMessage> for PeerJsSocket {
+    type Result = ();
+
+    fn handle(&mut self, message: CloseMessage, context: &mut Self::Context) -> Self::Result {
+        context.close(Some((ws::CloseCode::Normal, message.reason).into()));
+        context.stop();
+    }
+}
+
+#[test]
+fn test_translating_peerjs_offer() {
+    let message = json!({
+        "type": "OFFER",
+        "dst": "callee",
+        "payload": {
+            "sdp": { "type": "offer", "sdp": "v=0" },
+            "type": "media",
+            "connectionId": "mc_1"
+        }
+    });
+
+    match to_signal("caller", "callee", &message) {
+        Some(Signal::Offer(offer)) => {
+            assert_eq!(offer.target, "callee");
+            assert_eq!(offer.name, "caller");
+            assert_eq!(offer.sdp(), "v=0");
+            assert_eq!(
+                offer.extra[PEERJS_FIELD],
+                json!({ "type": "media", "connectionId": "mc_1" })
+            );
+        }
+        others => panic!("unexpected signal {:?}", others),
+    }
+}
+
+#[actix_rt::test]
+async fn test_round_trip_of_peerjs_candidate() {
+    let message = json!({
+        "type": "CANDIDATE",
+        "dst": "callee",
+        "payload": {
+            "candidate": { "candidate": "candidate:1", "sdpMid": "0", "sdpMLineIndex": 0 },
+            "type": "media",
+            "connectionId": "mc_1"
+        }
+    });
+    let signal = to_signal("caller", "callee", &message).unwrap();
+    let mut callee = PeerJsSocket::new(
+        "callee".to_owned(),
+        "token".to_owned(),
+        &SignalRouter::default().start(),
+        None,
+    );
+
+    assert_eq!(
+        callee.translate_signal(signal).unwrap(),
+        json!({
+            "type": "CANDIDATE",
+            "src": "caller",
+            "dst": "callee",
+            "payload": {
+                "candidate": { "candidate": "candidate:1", "sdpMid": "0", "sdpMLineIndex": 0 },
+                "type": "media",
+                "connectionId": "mc_1"
+            }
+        })
+    );
+}
+
+#[actix_rt::test]
+async fn test_crediting_candidates_to_their_sender() {
+    //given
+    let mut callee = PeerJsSocket::new(
+        "callee".to_owned(),
+        "token".to_owned(),
+        &SignalRouter::default().start(),
+        None,
+    );
+    for name in &["alice", "bob"] {
+        let offer = SessionDescriptionMessage::new(
+            "callee".to_owned(),
+            (*name).to_owned(),
+            "v=0".to_owned(),
+        );
+        callee.translate_signal(Signal::Offer(offer));
+    }
+    let mut ice_candidate = IceCandidate::new("callee".to_owned(), "candidate:1".to_owned());
+    ice_candidate.name = Some("alice".to_owned());
+
+    //when
+    let message = callee
+        .translate_signal(Signal::NewIceCandidate(ice_candidate))
+        .unwrap();
+
+    //then
+    assert_eq!(message["src"], "alice");
+    assert_eq!(message["payload"]["connectionId"], "native_alice");
+}
@@ -0,0 +1,83 @@
+use super::signal::RelayMessage;
+use super::Error;
+
+/// Deployment limits applied to `relay` signals before they are forwarded.
+#[derive(Clone, Debug)]
+pub struct RelayPolicy {
+    max_payload_size: usize,
+    allowed_kinds: Option<Vec<String>>,
+}
+
+impl Default for RelayPolicy {
+    fn default() -> Self {
+        RelayPolicy {
+            max_payload_size: 16 * 1024,
+            allowed_kinds: None,
+        }
+    }
+}
+
+impl RelayPolicy {
+    /// `allowed_kinds` of `None` accepts relays of any kind, including ones
+    /// without a `kind`.
+    pub fn new(max_payload_size: usize, allowed_kinds: Option<Vec<String>>) -> Self {
+        RelayPolicy {
+            max_payload_size,
+            allowed_kinds,
+        }
+    }
+
+    pub fn check(&self, relay_message: &RelayMessage) -> Result<(), Error> {
+        let payload_size = serde_json::to_vec(&relay_message.payload)?.len();
+        if payload_size > self.max_payload_size {
+            return Err(Error::PayloadTooLarge(self.max_payload_size));
+        }
+
+        match (&self.allowed_kinds, &relay_message.kind) {
+            (None, _) => Ok(()),
+            (Some(allowed_kinds), Some(kind)) if allowed_kinds.contains(kind) => Ok(()),
+            (Some(_), kind) => Err(Error::KindNotAllowed(kind.clone().unwrap_or_default())),
+        }
+    }
+}
+
+#[cfg(test)]
+fn relay_message(kind: Option<&str>, payload: serde_json::Value) -> RelayMessage {
+    RelayMessage {
+        target: "callee".to_owned(),
+        name: "caller".to_owned(),
+        kind: kind.map(str::to_owned),
+        payload,
+        traceparent: None,
+    }
+}
+
+#[test]
+fn test_rejecting_large_payload() {
+    let policy = RelayPolicy::new(8, None);
+
+    assert!(policy
+        .check(&relay_message(None, serde_json::json!("short")))
+        .is_ok());
+    assert!(matches!(
+        policy.check(&relay_message(None, serde_json::json!("too long payload"))),
+        Err(Error::PayloadTooLarge(8))
+    ));
+}
+
+#[test]
+fn test_rejecting_kind_not_in_allowlist() {
+    let policy = RelayPolicy::new(1024, Some(vec!["mute".to_owned()]));
+
+    assert!(policy
+        .check(&relay_message(Some("mute"), serde_json::json!(true)))
+        .is_ok());
+    assert!(matches!(
+        policy.check(&relay_message(Some("chat"), serde_json::json!("hi"))),
+        Err(Error::KindNotAllowed(_))
+    ));
+    assert!(matches!(
+        policy.check(&relay_message(None, serde_json::json!(true))),
+        Err(Error::KindNotAllowed(_))
+    ));
+}
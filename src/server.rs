@@ -0,0 +1,235 @@
+use actix::prelude::{Actor, Addr};
+use actix_web::web;
+use std::sync::Arc;
+
+use super::admission::AdmissionHook;
+use super::field_passthrough::FieldPassthrough;
+use super::origin::OriginAllowlist;
+use super::relay_policy::RelayPolicy;
+use super::signal_policy::SignalPolicy;
+use super::webhook::Webhook;
+use super::{admin, dashboard, peerjs, sip, sse, whep, whip};
+use super::{Adapters, SignalRouter, SignalServerState};
+
+/// Signalling service ready to be mounted into an actix-web `App`.
+#[derive(Clone)]
+pub struct SignalServer {
+    state: Arc<SignalServerState>,
+}
+
+impl SignalServer {
+    pub fn builder() -> SignalServerBuilder {
+        SignalServerBuilder::default()
+    }
+
+    /// Router the sockets of this server join, for applications routing
+    /// signals of their own.
+    pub fn signal_router(&self) -> &Addr<SignalRouter> {
+        &self.state.signal_router
+    }
+
+    /// Mounts `/signal` along with the adapters enabled on the builder,
+    /// which share its router, and `/admin` if it has a token.
+    pub fn configure(&self, config: &mut web::ServiceConfig) {
+        let adapters = &self.state.adapters;
+        if self.state.admin_token.is_some() {
+            config
+                .service(web::resource("/admin/users").route(web::get().to(admin::users)))
+                .service(web::resource("/admin/users/{name}").route(web::get().to(admin::user)))
+                .service(
+                    web::resource("/admin/users/{name}/disconnect")
+                        .route(web::post().to(admin::disconnect)),
+                )
+                .service(web::resource("/admin/notices").route(web::post().to(admin::notices)))
+                .service(web::resource("/admin/dashboard").route(web::get().to(dashboard::page)))
+                .service(web::resource("/admin/stream").route(web::get().to(dashboard::stream)));
+        }
+        if adapters.sse {
+            config
+                .service(web::resource("/signal/events").route(web::get().to(sse::events)))
+                .service(web::resource("/signal/send").route(web::post().to(sse::send)));
+        }
+        if let Some(peerjs_key) = &adapters.peerjs_key {
+            config
+                .service(
+                    web::resource(format!("/{}/id", peerjs_key)).route(web::get().to(peerjs::id)),
+                )
+                .service(web::resource("/peerjs").route(web::get().to(peerjs::socket)));
+        }
+        if adapters.sip_domain.is_some() {
+            config.service(web::resource("/sip").route(web::get().to(sip::socket)));
+        }
+        if adapters.metrics {
+            config.service(web::resource("/metrics").to(super::metrics));
+        }
+        if adapters.whip {
+            config
+                .service(web::resource("/whip/{target}").route(web::post().to(whip::offer)))
+                .service(
+                    web::resource("/whip/{target}/{session}")
+                        .route(web::patch().to(whip::trickle))
+                        .route(web::delete().to(whip::teardown)),
+                );
+        }
+        if adapters.whep {
+            config
+                .service(web::resource("/whep/{publisher}").route(web::post().to(whep::offer)))
+                .service(
+                    web::resource("/whep/{publisher}/{session}")
+                        .route(web::patch().to(whep::trickle))
+                        .route(web::delete().to(whep::teardown)),
+                );
+        }
+
+        config
+            .data(self.state.clone())
+            .service(web::resource("/signal").to(super::signal));
+    }
+}
+
+/// Configuration of a `SignalServer`. Only `/signal` is mounted by default:
+/// SSE, PeerJS, SIP, WHIP, WHEP and `/metrics` are enabled one by one,
+/// while the `signalling-server` binary enables all of them but SIP.
+#[derive(Default)]
+pub struct SignalServerBuilder {
+    origin_allowlist: OriginAllowlist,
+    relay_policy: RelayPolicy,
+    signal_policy: SignalPolicy,
+    field_passthrough: FieldPassthrough,
+    sse: bool,
+    peerjs_key: Option<String>,
+    sip_domain: Option<String>,
+    metrics: bool,
+    whip: bool,
+    whep: bool,
+    log_signal_bodies: bool,
+    admin_token: Option<String>,
+    whip_token: Option<String>,
+    admission_hook: Option<AdmissionHook>,
+    webhook: Option<Webhook>,
+}
+
+impl SignalServerBuilder {
+    pub fn origin_allowlist(mut self, origin_allowlist: OriginAllowlist) -> Self {
+        self.origin_allowlist = origin_allowlist;
+        self
+    }
+
+    pub fn relay_policy(mut self, relay_policy: RelayPolicy) -> Self {
+        self.relay_policy = relay_policy;
+        self
+    }
+
+    /// Rules deciding which members may signal which.
+    pub fn signal_policy(mut self, signal_policy: SignalPolicy) -> Self {
+        self.signal_policy = signal_policy;
+        self
+    }
+
+    pub fn field_passthrough(mut self, field_passthrough: FieldPassthrough) -> Self {
+        self.field_passthrough = field_passthrough;
+        self
+    }
+
+    /// Mounts `/signal/events` and `/signal/send` for clients signalling
+    /// over Server-Sent Events.
+    pub fn sse(mut self, sse: bool) -> Self {
+        self.sse = sse;
+        self
+    }
+
+    /// Enables the PeerJS server on `/peerjs` and `/{peerjs_key}/id` for
+    /// clients connecting with `peerjs_key`.
+    pub fn peerjs_key<T: Into<String>>(mut self, peerjs_key: T) -> Self {
+        self.peerjs_key = Some(peerjs_key.into());
+        self
+    }
+
+    /// Enables the SIP over WebSocket gateway for `sip_domain`. Endpoints
+    /// may only register users the admission hook admits.
+    pub fn sip_domain<T: Into<String>>(mut self, sip_domain: T) -> Self {
+        self.sip_domain = Some(sip_domain.into());
+        self
+    }
+
+    /// Mounts `/metrics` in the Prometheus text format.
+    pub fn metrics(mut self, metrics: bool) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Mounts `/whip` for WHIP clients publishing to connected users.
+    pub fn whip(mut self, whip: bool) -> Self {
+        self.whip = whip;
+        self
+    }
+
+    /// Mounts `/whep` for WHEP clients playing what connected users publish.
+    pub fn whep(mut self, whep: bool) -> Self {
+        self.whep = whep;
+        self
+    }
+
+    /// Logs session descriptions and candidates of routed signals, which
+    /// are redacted by default.
+    pub fn log_signal_bodies(mut self, log_signal_bodies: bool) -> Self {
+        self.log_signal_bodies = log_signal_bodies;
+        self
+    }
+
+    /// Enables the `/admin` API for requests bearing `admin_token`, e.g.
+    /// `Authorization: Bearer <admin_token>`, and its dashboard at
+    /// `/admin/dashboard`.
+    pub fn admin_token<T: Into<String>>(mut self, admin_token: T) -> Self {
+        self.admin_token = Some(admin_token.into());
+        self
+    }
+
+    /// Requires WHIP and WHEP requests to bear `whip_token`, e.g.
+    /// `Authorization: Bearer <whip_token>`.
+    pub fn whip_token<T: Into<String>>(mut self, whip_token: T) -> Self {
+        self.whip_token = Some(whip_token.into());
+        self
+    }
+
+    /// Asks `admission_hook` whether to admit every client joining the router.
+    pub fn admission_hook(mut self, admission_hook: AdmissionHook) -> Self {
+        self.admission_hook = Some(admission_hook);
+        self
+    }
+
+    /// Delivers joins, exits, offers, answers and hangups to `webhook`.
+    pub fn webhook(mut self, webhook: Webhook) -> Self {
+        self.webhook = Some(webhook);
+        self
+    }
+
+    /// Starts the router. Must be called from within a running actix system.
+    pub fn build(self) -> SignalServer {
+        let signal_router = SignalRouter::new(
+            self.relay_policy,
+            self.signal_policy,
+            self.field_passthrough,
+        )
+        .start();
+        SignalServer {
+            state: Arc::new(SignalServerState::new(
+                signal_router,
+                self.origin_allowlist,
+                Adapters {
+                    sse: self.sse,
+                    peerjs_key: self.peerjs_key,
+                    sip_domain: self.sip_domain,
+                    metrics: self.metrics,
+                    whip: self.whip,
+                    whep: self.whep,
+                },
+                self.log_signal_bodies,
+                self.admin_token,
+                self.whip_token,
+                self.admission_hook,
+                self.webhook,
+            )),
+        }
+    }
+}
@@ -1,7 +1,10 @@
 use serde::de::{Error, MapAccess, Unexpected, Visitor};
 use serde::{Deserialize, Deserializer};
 
-use super::{IceCandidate, SessionDescriptionMessage, Signal};
+use super::{
+    Extra, Feature, HangupMessage, Hello, IceCandidate, Limits, RelayMessage,
+    SessionDescriptionMessage, Signal, TraceParent,
+};
 
 impl<'de> Deserialize<'de> for Signal {
     fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
@@ -29,9 +32,13 @@ impl<'de> Visitor<'de> for SignalVisitor {
                     "assign" => Ok(Signal::assign(
                         AssignedNameVisitor.visit_map(map)?.to_owned(),
                     )),
+                    "hello" => Ok(Signal::Hello(HelloVisitor.visit_map(map)?)),
+                    "relay" => Ok(Signal::Relay(RelayVisitor.visit_map(map)?)),
+                    "hangup" => Ok(Signal::Hangup(HangupVisitor.visit_map(map)?)),
+                    "notice" => Ok(Signal::notice(NoticeVisitor.visit_map(map)?)),
                     others => Err(M::Error::invalid_value(
                         Unexpected::Str(others),
-                        &"offer, answer, new_ice_candidate, assign",
+                        &"offer, answer, new_ice_candidate, assign, hello, relay, hangup, notice",
                     )),
                 };
             }
@@ -50,22 +57,29 @@ impl<'de> Visitor<'de> for SessionDescriptionVisitor {
 
     fn visit_map<M: MapAccess<'de>>(self, mut map: M) -> Result<Self::Value, M::Error> {
         let mut target = Err(M::Error::missing_field("target"));
-        let mut name: Result<&'de str, M::Error> = Err(M::Error::missing_field("name"));
+        let mut name = Err(M::Error::missing_field("name"));
         let mut sdp = Err(M::Error::missing_field("sdp"));
+        let mut traceparent = None;
+        let mut extra = Extra::new();
 
-        while let Some((key, value)) = map.next_entry()? {
+        while let Some(key) = map.next_key()? as Option<&'de str> {
             match key {
-                "target" => target = Ok(value),
-                "name" => name = Ok(value),
-                "sdp" => sdp = Ok(value),
-                _ => continue,
+                "target" => target = Ok(map.next_value()?),
+                "name" => name = Ok(map.next_value()?),
+                "sdp" => sdp = Ok(map.next_value()?),
+                "traceparent" => traceparent = next_traceparent(&mut map)?,
+                _ => {
+                    extra.insert(key.to_owned(), map.next_value()?);
+                }
             }
         }
 
         Ok(SessionDescriptionMessage {
-            name: name?.to_owned(),
-            target: target?.to_owned(),
-            sdp: sdp?.to_owned(),
+            name: name?,
+            target: target?,
+            sdp: sdp?,
+            traceparent,
+            extra,
         })
     }
 }
@@ -78,20 +92,30 @@ impl<'de> Visitor<'de> for IceCandidateVisitor {
     }
 
     fn visit_map<M: MapAccess<'de>>(self, mut map: M) -> Result<Self::Value, M::Error> {
-        let mut target: Result<&'de str, M::Error> = Err(M::Error::missing_field("target"));
-        let mut candidate: Result<&'de str, M::Error> = Err(M::Error::missing_field("candidate"));
+        let mut target = Err(M::Error::missing_field("target"));
+        let mut name = None;
+        let mut candidate = Err(M::Error::missing_field("candidate"));
+        let mut traceparent = None;
+        let mut extra = Extra::new();
 
-        while let Some((key, value)) = map.next_entry()? {
+        while let Some(key) = map.next_key()? as Option<&'de str> {
             match key {
-                "target" => target = Ok(value),
-                "candidate" => candidate = Ok(value),
-                _ => continue,
+                "target" => target = Ok(map.next_value()?),
+                "name" => name = Some(map.next_value()?),
+                "candidate" => candidate = Ok(map.next_value()?),
+                "traceparent" => traceparent = next_traceparent(&mut map)?,
+                _ => {
+                    extra.insert(key.to_owned(), map.next_value()?);
+                }
             }
         }
 
         Ok(IceCandidate {
-            target: target?.to_owned(),
-            candidate: candidate?.to_owned(),
+            target: target?,
+            name,
+            candidate: candidate?,
+            traceparent,
+            extra,
         })
     }
 }
@@ -114,6 +138,141 @@ impl<'de> Visitor<'de> for AssignedNameVisitor {
     }
 }
 
+pub struct HelloVisitor;
+impl<'de> Visitor<'de> for HelloVisitor {
+    type Value = Hello;
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(formatter, "couldn't parse Signal type")
+    }
+
+    fn visit_map<M: MapAccess<'de>>(self, mut map: M) -> Result<Self::Value, M::Error> {
+        let mut version = Err(M::Error::missing_field("version"));
+        let mut features: Vec<Feature> = Vec::new();
+        let mut limits: Option<Limits> = None;
+
+        while let Some(key) = map.next_key()? as Option<&'de str> {
+            match key {
+                "version" => version = Ok(map.next_value()?),
+                "features" => {
+                    // features unknown to this server are simply not enabled
+                    features = map
+                        .next_value::<Vec<String>>()?
+                        .iter()
+                        .filter_map(|name| Feature::from_name(name))
+                        .collect()
+                }
+                "limits" => limits = Some(map.next_value()?),
+                _ => {
+                    map.next_value::<serde::de::IgnoredAny>()?;
+                }
+            }
+        }
+
+        Ok(Hello {
+            version: version?,
+            features,
+            limits,
+        })
+    }
+}
+
+pub struct RelayVisitor;
+impl<'de> Visitor<'de> for RelayVisitor {
+    type Value = RelayMessage;
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(formatter, "couldn't parse Signal type")
+    }
+
+    fn visit_map<M: MapAccess<'de>>(self, mut map: M) -> Result<Self::Value, M::Error> {
+        let mut target = Err(M::Error::missing_field("target"));
+        let mut name = Err(M::Error::missing_field("name"));
+        let mut kind = None;
+        let mut payload = Err(M::Error::missing_field("payload"));
+        let mut traceparent = None;
+
+        while let Some(key) = map.next_key()? as Option<&'de str> {
+            match key {
+                "target" => target = Ok(map.next_value()?),
+                "name" => name = Ok(map.next_value()?),
+                "kind" => kind = Some(map.next_value()?),
+                "payload" => payload = Ok(map.next_value()?),
+                "traceparent" => traceparent = next_traceparent(&mut map)?,
+                _ => {
+                    map.next_value::<serde::de::IgnoredAny>()?;
+                }
+            }
+        }
+
+        Ok(RelayMessage {
+            target: target?,
+            name: name?,
+            kind,
+            payload: payload?,
+            traceparent,
+        })
+    }
+}
+
+pub struct HangupVisitor;
+impl<'de> Visitor<'de> for HangupVisitor {
+    type Value = HangupMessage;
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(formatter, "couldn't parse Signal type")
+    }
+
+    fn visit_map<M: MapAccess<'de>>(self, mut map: M) -> Result<Self::Value, M::Error> {
+        let mut target = Err(M::Error::missing_field("target"));
+        let mut name = Err(M::Error::missing_field("name"));
+        let mut traceparent = None;
+
+        while let Some(key) = map.next_key()? as Option<&'de str> {
+            match key {
+                "target" => target = Ok(map.next_value()?),
+                "name" => name = Ok(map.next_value()?),
+                "traceparent" => traceparent = next_traceparent(&mut map)?,
+                _ => {
+                    map.next_value::<serde::de::IgnoredAny>()?;
+                }
+            }
+        }
+
+        Ok(HangupMessage {
+            target: target?,
+            name: name?,
+            traceparent,
+        })
+    }
+}
+
+pub struct NoticeVisitor;
+impl<'de> Visitor<'de> for NoticeVisitor {
+    type Value = String;
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(formatter, "couldn't parse Signal type")
+    }
+
+    fn visit_map<M: MapAccess<'de>>(self, mut map: M) -> Result<Self::Value, M::Error> {
+        let mut message = Err(M::Error::missing_field("message"));
+
+        while let Some(key) = map.next_key()? as Option<&'de str> {
+            match key {
+                "message" => message = Ok(map.next_value()?),
+                _ => {
+                    map.next_value::<serde::de::IgnoredAny>()?;
+                }
+            }
+        }
+
+        message
+    }
+}
+
+/// Trace context of a signal. An invalid one is dropped rather than
+/// failing the signal, as W3C trace context requires.
+fn next_traceparent<'de, M: MapAccess<'de>>(map: &mut M) -> Result<Option<TraceParent>, M::Error> {
+    Ok(map.next_value::<String>()?.parse().ok())
+}
+
 #[test]
 fn test_deserealizing_offer_signal() {
     use super::SessionDescriptionMessage;
@@ -124,6 +283,8 @@ fn test_deserealizing_offer_signal() {
         target: "4fe681ad-aba1-4732-89df-ee784b7d4abf".to_owned(),
         name: "3872379c-4743-4a7d-b2ee-79cf7368cf58".to_owned(),
         sdp: "sdp".to_owned(),
+        traceparent: None,
+        extra: Default::default(),
     });
 
     assert_eq!(
@@ -142,10 +303,12 @@ fn test_deserializing_answer_signal() {
         target: "4fe681ad-aba1-4732-89df-ee784b7d4abf".to_owned(),
         name: "3872379c-4743-4a7d-b2ee-79cf7368cf58".to_owned(),
         sdp: "sdp".to_owned(),
+        traceparent: None,
+        extra: Default::default(),
     });
 
     assert_eq!(
-        serde_json::from_str::<Signal>(&answer_signal_text).unwrap(),
+        serde_json::from_str::<Signal>(answer_signal_text).unwrap(),
         answer_signal_struct
     );
 }
@@ -158,11 +321,14 @@ fn test_deserializing_new_ice_candidate_signal() {
 
     let new_ice_candidate_struct = Signal::NewIceCandidate(IceCandidate {
         target: "4fe681ad-aba1-4732-89df-ee784b7d4abf".to_owned(),
+        name: None,
         candidate: "candidate".to_owned(),
+        traceparent: None,
+        extra: Default::default(),
     });
 
     assert_eq!(
-        serde_json::from_str::<Signal>(&new_ice_candidate_text).unwrap(),
+        serde_json::from_str::<Signal>(new_ice_candidate_text).unwrap(),
         new_ice_candidate_struct
     );
 }
@@ -174,7 +340,80 @@ fn test_serializing_assign_message() {
     let assign_message_struct = Signal::Assign("4fe681ad-aba1-4732-89df-ee784b7d4abf".to_owned());
 
     assert_eq!(
-        serde_json::from_str::<Signal>(&assign_message_text).unwrap(),
+        serde_json::from_str::<Signal>(assign_message_text).unwrap(),
         assign_message_struct
     );
 }
+
+#[test]
+fn test_deserializing_hello_message() {
+    let hello_message_text = r#"{"type":"hello","version":1,"features":["acks","binary"]}"#;
+
+    let hello_message_struct = Signal::Hello(Hello {
+        version: 1,
+        features: vec![Feature::Acks, Feature::Binary],
+        limits: None,
+    });
+
+    assert_eq!(
+        serde_json::from_str::<Signal>(hello_message_text).unwrap(),
+        hello_message_struct
+    );
+}
+
+#[test]
+fn test_deserializing_relay_signal() {
+    let relay_signal_text = r#"{"type":"relay","name":"3872379c-4743-4a7d-b2ee-79cf7368cf58","target":"4fe681ad-aba1-4732-89df-ee784b7d4abf","kind":"chat","payload":{"text":"hi\nthere"}}"#;
+
+    let relay_signal_struct = Signal::Relay(RelayMessage {
+        target: "4fe681ad-aba1-4732-89df-ee784b7d4abf".to_owned(),
+        name: "3872379c-4743-4a7d-b2ee-79cf7368cf58".to_owned(),
+        kind: Some("chat".to_owned()),
+        payload: serde_json::json!({ "text": "hi\nthere" }),
+        traceparent: None,
+    });
+
+    assert_eq!(
+        serde_json::from_str::<Signal>(relay_signal_text).unwrap(),
+        relay_signal_struct
+    );
+}
+
+#[test]
+fn test_deserializing_offer_signal_with_unknown_fields() {
+    let offer_signal_text = r#"{"type":"offer","name":"caller","target":"callee","sdp":"v=0\r\n","callId":"call-1","simulcast":{"layers":3}}"#;
+
+    match serde_json::from_str::<Signal>(offer_signal_text).unwrap() {
+        Signal::Offer(offer) => {
+            assert_eq!(offer.sdp, "v=0\r\n");
+            assert_eq!(offer.extra["callId"], serde_json::json!("call-1"));
+            assert_eq!(offer.extra["simulcast"], serde_json::json!({ "layers": 3 }));
+        }
+        others => panic!("unexpected signal {:?}", others),
+    }
+}
+
+#[test]
+fn test_deserializing_traceparent_of_signal() {
+    let offer_signal_text = r#"{"type":"offer","name":"caller","target":"callee","sdp":"v=0","traceparent":"00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"}"#;
+    let answer_signal_text = r#"{"type":"answer","name":"callee","target":"caller","sdp":"v=0","traceparent":"not a trace context"}"#;
+
+    let offer = serde_json::from_str::<Signal>(offer_signal_text).unwrap();
+    let answer = serde_json::from_str::<Signal>(answer_signal_text).unwrap();
+
+    assert_eq!(
+        offer.traceparent().map(ToString::to_string).as_deref(),
+        Some("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01")
+    );
+    assert_eq!(answer.traceparent(), None);
+}
+
+#[test]
+fn test_deserializing_notice_signal() {
+    let notice_signal_text = r#"{"type":"notice","message":"maintenance in 5 minutes","sentAt":1}"#;
+
+    assert_eq!(
+        serde_json::from_str::<Signal>(notice_signal_text).unwrap(),
+        Signal::notice("maintenance in 5 minutes".to_owned())
+    );
+}
@@ -0,0 +1,143 @@
+use actix_web::http::header;
+use actix_web::HttpRequest;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{CodecError, Error};
+
+/// Wire format of a signalling connection, negotiated through the
+/// `Sec-WebSocket-Protocol` header. Clients that don't ask for a
+/// subprotocol get JSON.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Encoding {
+    Json,
+    MessagePack,
+    Cbor,
+    /// JSON wrapped in JSON-RPC 2.0 envelopes, see `jsonrpc`.
+    JsonRpc,
+}
+
+/// Encoded form of a value, sent as a text or binary WebSocket frame.
+#[derive(Debug, PartialEq)]
+pub enum Frame {
+    Text(String),
+    Binary(Vec<u8>),
+}
+
+impl Encoding {
+    pub const PROTOCOLS: &'static [&'static str] = &[
+        "signal.json.v1",
+        "signal.msgpack.v1",
+        "signal.cbor.v1",
+        "jsonrpc",
+    ];
+
+    pub fn from_protocol(protocol: &str) -> Option<Self> {
+        match protocol {
+            "signal.json.v1" => Some(Encoding::Json),
+            "signal.msgpack.v1" => Some(Encoding::MessagePack),
+            "signal.cbor.v1" => Some(Encoding::Cbor),
+            "jsonrpc" => Some(Encoding::JsonRpc),
+            _ => None,
+        }
+    }
+
+    /// Picks the first protocol requested by the client that the server
+    /// supports, the same way `ws::handshake_with_protocols` answers it.
+    pub fn negotiate(request: &HttpRequest) -> Self {
+        request
+            .headers()
+            .get(header::SEC_WEBSOCKET_PROTOCOL)
+            .and_then(|protocols| protocols.to_str().ok())
+            .and_then(|protocols| {
+                protocols
+                    .split(',')
+                    .find_map(|protocol| Self::from_protocol(protocol.trim()))
+            })
+            .unwrap_or(Encoding::Json)
+    }
+
+    pub fn encode<T: Serialize>(self, value: &T) -> Result<Frame, Error> {
+        Ok(match self {
+            Encoding::Json | Encoding::JsonRpc => Frame::Text(serde_json::to_string(value)?),
+            Encoding::MessagePack => Frame::Binary(
+                rmp_serde::to_vec(value)
+                    .map_err(|err| Error::MessagePackEncodeError(CodecError::new(err)))?,
+            ),
+            Encoding::Cbor => Frame::Binary(
+                serde_cbor::to_vec(value).map_err(|err| Error::CborError(CodecError::new(err)))?,
+            ),
+        })
+    }
+
+    pub fn decode<'de, T: Deserialize<'de>>(self, bytes: &'de [u8]) -> Result<T, Error> {
+        Ok(match self {
+            Encoding::Json | Encoding::JsonRpc => serde_json::from_slice(bytes)?,
+            Encoding::MessagePack => rmp_serde::from_slice(bytes)
+                .map_err(|err| Error::MessagePackDecodeError(CodecError::new(err)))?,
+            Encoding::Cbor => serde_cbor::from_slice(bytes)
+                .map_err(|err| Error::CborError(CodecError::new(err)))?,
+        })
+    }
+}
+
+#[cfg(test)]
+fn offer_signal() -> super::Signal {
+    super::Signal::Offer(super::SessionDescriptionMessage {
+        target: "4fe681ad-aba1-4732-89df-ee784b7d4abf".to_owned(),
+        name: "3872379c-4743-4a7d-b2ee-79cf7368cf58".to_owned(),
+        sdp: "sdp".to_owned(),
+        traceparent: None,
+        extra: Default::default(),
+    })
+}
+
+#[test]
+fn test_message_pack_round_trip() {
+    let signal = offer_signal();
+
+    let frame = Encoding::MessagePack.encode(&signal).unwrap();
+
+    match frame {
+        Frame::Binary(bytes) => assert_eq!(
+            Encoding::MessagePack
+                .decode::<super::Signal>(&bytes)
+                .unwrap(),
+            signal
+        ),
+        Frame::Text(_) => panic!("MessagePack must be sent as a binary frame"),
+    }
+}
+
+#[test]
+fn test_cbor_round_trip() {
+    let signal = offer_signal();
+
+    let frame = Encoding::Cbor.encode(&signal).unwrap();
+
+    match frame {
+        Frame::Binary(bytes) => assert_eq!(
+            Encoding::Cbor.decode::<super::Signal>(&bytes).unwrap(),
+            signal
+        ),
+        Frame::Text(_) => panic!("CBOR must be sent as a binary frame"),
+    }
+}
+
+#[test]
+fn test_negotiating_first_supported_protocol() {
+    let request = actix_web::test::TestRequest::default()
+        .header(
+            header::SEC_WEBSOCKET_PROTOCOL,
+            "chat, signal.cbor.v1, signal.msgpack.v1",
+        )
+        .to_http_request();
+
+    assert_eq!(Encoding::negotiate(&request), Encoding::Cbor);
+}
+
+#[test]
+fn test_negotiating_without_protocol_header() {
+    let request = actix_web::test::TestRequest::default().to_http_request();
+
+    assert_eq!(Encoding::negotiate(&request), Encoding::Json);
+}
@@ -0,0 +1,119 @@
+use serde::{Deserialize, Serialize};
+
+/// Version of the signalling protocol spoken by this server.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Optional protocol features a client may ask for in its `hello`.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Feature {
+    Acks,
+    Rooms,
+    Resume,
+    Binary,
+    /// `relay` signals between peers.
+    Relay,
+    /// `hangup` signals ending calls.
+    Hangup,
+    /// `notice` signals of the server operator.
+    Notice,
+}
+
+impl Feature {
+    /// Features this server is able to enable for a connection.
+    pub const SUPPORTED: &'static [Feature] = &[Feature::Relay, Feature::Hangup, Feature::Notice];
+
+    pub fn from_name(name: &str) -> Option<Feature> {
+        match name {
+            "acks" => Some(Feature::Acks),
+            "rooms" => Some(Feature::Rooms),
+            "resume" => Some(Feature::Resume),
+            "binary" => Some(Feature::Binary),
+            "relay" => Some(Feature::Relay),
+            "hangup" => Some(Feature::Hangup),
+            "notice" => Some(Feature::Notice),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Feature {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Feature::Acks => write!(formatter, "acks"),
+            Feature::Rooms => write!(formatter, "rooms"),
+            Feature::Resume => write!(formatter, "resume"),
+            Feature::Binary => write!(formatter, "binary"),
+            Feature::Relay => write!(formatter, "relay"),
+            Feature::Hangup => write!(formatter, "hangup"),
+            Feature::Notice => write!(formatter, "notice"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct Limits {
+    pub max_message_size: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        // default frame size limit of `actix_http::ws::Codec`
+        Limits {
+            max_message_size: 65_536,
+        }
+    }
+}
+
+/// Capability handshake. Clients announce the version they speak and the
+/// features they want, the server answers with the features it enabled and
+/// its limits.
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct Hello {
+    pub version: u32,
+    pub features: Vec<Feature>,
+    pub limits: Option<Limits>,
+}
+
+impl Hello {
+    /// Hello of a client speaking this version and asking for `features`.
+    pub fn new(features: Vec<Feature>) -> Hello {
+        Hello {
+            version: PROTOCOL_VERSION,
+            features,
+            limits: None,
+        }
+    }
+
+    /// Answer of the server to a client `hello`, enabling the requested
+    /// features the server supports.
+    pub fn negotiate(&self, limits: Limits) -> Hello {
+        Hello {
+            version: PROTOCOL_VERSION,
+            features: self
+                .features
+                .iter()
+                .filter(|feature| Feature::SUPPORTED.contains(feature))
+                .copied()
+                .collect(),
+            limits: Some(limits),
+        }
+    }
+}
+
+#[test]
+fn test_negotiating_supported_features() {
+    let client_hello = Hello {
+        version: PROTOCOL_VERSION,
+        features: vec![Feature::Acks, Feature::Relay],
+        limits: None,
+    };
+
+    let server_hello = client_hello.negotiate(Limits::default());
+
+    assert_eq!(server_hello.version, PROTOCOL_VERSION);
+    assert_eq!(server_hello.features, vec![Feature::Relay]);
+    assert_eq!(server_hello.limits, Some(Limits::default()));
+}
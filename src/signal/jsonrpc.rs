@@ -0,0 +1,323 @@
+use serde::de::value::{BorrowedStrDeserializer, MapDeserializer};
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+
+use super::{Error, Signal};
+use crate::error::ErrorMessage;
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+
+/// JSON-RPC methods and the signal types they carry. `session.assign` and
+/// `session.notice` are only ever sent by the server.
+const METHODS: [(&str, &str); 8] = [
+    ("signal.offer", "offer"),
+    ("signal.answer", "answer"),
+    ("signal.candidate", "new_ice_candidate"),
+    ("signal.relay", "relay"),
+    ("signal.hangup", "hangup"),
+    ("session.hello", "hello"),
+    ("session.assign", "assign"),
+    ("session.notice", "notice"),
+];
+const SERVER_SIGNAL_TYPES: [&str; 2] = ["assign", "notice"];
+/// Method joining the room named by the `room` param, which carries no
+/// signal.
+const ROOM_JOIN: &str = "room.join";
+
+/// JSON-RPC request of a client. Requests without an id are notifications
+/// and get no response.
+#[derive(Debug, PartialEq)]
+pub struct Request {
+    pub id: Option<Value>,
+    pub call: Call,
+}
+
+/// What a client asks for with a request.
+#[derive(Debug, PartialEq)]
+pub enum Call {
+    /// Routing a signal, e.g. with `signal.offer`.
+    Signal(Signal),
+    /// Joining a room with `room.join`, which resolves to its members.
+    JoinRoom(String),
+}
+
+/// Decodes a JSON-RPC request whose params are the fields of a signal, or
+/// the room of `room.join`. On failure, the error response to send back is
+/// returned instead.
+pub fn decode_request(frame: &[u8]) -> Result<Request, Value> {
+    let request: Value = serde_json::from_slice(frame)
+        .map_err(|_| error_response(Value::Null, PARSE_ERROR, "Parse error", None))?;
+    let id = request.get("id").cloned();
+    let invalid_request = || {
+        error_response(
+            id.clone().unwrap_or(Value::Null),
+            INVALID_REQUEST,
+            "Invalid Request",
+            None,
+        )
+    };
+
+    if request["jsonrpc"] != "2.0" {
+        return Err(invalid_request());
+    }
+    let method = request["method"].as_str().ok_or_else(invalid_request)?;
+    let no_params = Map::new();
+    let params = match request.get("params") {
+        Some(Value::Object(params)) => params,
+        None => &no_params,
+        Some(_) => return Err(invalid_request()),
+    };
+    let invalid_params = |data: String| {
+        error_response(
+            id.clone().unwrap_or(Value::Null),
+            INVALID_PARAMS,
+            "Invalid params",
+            Some(json!(data)),
+        )
+    };
+
+    if method == ROOM_JOIN {
+        let room = match params.get("room") {
+            Some(Value::String(room)) if !room.is_empty() => room.clone(),
+            _ => return Err(invalid_params("room must be a non-empty string".to_owned())),
+        };
+        return Ok(Request {
+            id,
+            call: Call::JoinRoom(room),
+        });
+    }
+    let signal_type = METHODS
+        .iter()
+        .find(|(name, signal_type)| *name == method && !SERVER_SIGNAL_TYPES.contains(signal_type))
+        .map(|(_, signal_type)| *signal_type)
+        .ok_or_else(|| {
+            error_response(
+                id.clone().unwrap_or(Value::Null),
+                METHOD_NOT_FOUND,
+                "Method not found",
+                Some(json!(method)),
+            )
+        })?;
+
+    // signals are deserialized from borrowed strings, starting with their type
+    let kind = Value::from(signal_type);
+    let fields = std::iter::once(("type", &kind))
+        .chain(
+            params
+                .iter()
+                .map(|(key, value)| (key.as_str(), value))
+                .filter(|(key, _)| *key != "type"),
+        )
+        .map(|(key, value)| (BorrowedStrDeserializer::new(key), value));
+    let signal = Signal::deserialize(MapDeserializer::<_, serde_json::Error>::new(fields))
+        .map_err(|err| invalid_params(err.to_string()))?;
+
+    Ok(Request {
+        id,
+        call: Call::Signal(signal),
+    })
+}
+
+/// Notification delivering a signal routed to the client.
+pub fn notification(signal: &Signal) -> Result<Value, Error> {
+    let (method, params) = method_and_params(signal)?;
+    Ok(json!({ "jsonrpc": "2.0", "method": method, "params": params }))
+}
+
+/// Response to the request `id`, carrying the signal the server answered
+/// with, if any.
+pub fn response(id: Value, result: Result<Option<Signal>, Error>) -> Value {
+    let result = match result {
+        Ok(Some(signal)) => method_and_params(&signal).map(|(_, params)| params),
+        Ok(None) => Ok(Value::Null),
+        Err(err) => Err(err),
+    };
+    result_response(id, result)
+}
+
+/// Response to the `room.join` request `id`, carrying the names of the
+/// members of `room`, sorted.
+pub fn room_response(id: Value, room: &str, result: Result<Vec<String>, Error>) -> Value {
+    result_response(
+        id,
+        result.map(|members| json!({ "room": room, "members": members })),
+    )
+}
+
+fn result_response(id: Value, result: Result<Value, Error>) -> Value {
+    match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(err) => {
+            let error_message = json!(ErrorMessage::from(&err));
+            let message = error_message["type"]
+                .as_str()
+                .unwrap_or_default()
+                .to_owned();
+            error_response(id, error_code(&err), &message, Some(error_message))
+        }
+    }
+}
+
+fn method_and_params(signal: &Signal) -> Result<(&'static str, Value), Error> {
+    let mut params = serde_json::to_value(signal)?;
+    let signal_type = params
+        .as_object_mut()
+        .and_then(|params| params.remove("type"))
+        .unwrap_or_default();
+    let method = METHODS
+        .iter()
+        .find(|(_, name)| signal_type == *name)
+        .map(|(method, _)| *method)
+        .unwrap_or_default();
+    Ok((method, params))
+}
+
+/// Server error codes of the JSON-RPC reserved range.
+fn error_code(err: &Error) -> i64 {
+    match err {
+        Error::ParseError(_)
+        | Error::MessagePackDecodeError(_)
+        | Error::MessagePackEncodeError(_)
+        | Error::CborError(_) => INTERNAL_ERROR,
+        Error::TargetNotFound(_) | Error::SessionNotFound(_) => -32001,
+        Error::ConnectionClosed => -32002,
+        Error::ConnectionTimeout | Error::NegotiationTimeout => -32003,
+        Error::ServiceUnavailable | Error::ServiceTimeout => -32004,
+        Error::PayloadTooLarge(_) | Error::KindNotAllowed(_) | Error::TrickleNotSupported(_) => {
+            -32005
+        }
+        Error::UnsupportedVersion(_) | Error::FeatureNotNegotiated(_) => -32006,
+        Error::Unauthorized => -32007,
+        Error::Forbidden(_) => -32008,
+        Error::RequestPending(_) => -32009,
+        Error::ServerSignal(_) => METHOD_NOT_FOUND,
+    }
+}
+
+fn error_response(id: Value, code: i64, message: &str, data: Option<Value>) -> Value {
+    let mut error = json!({ "code": code, "message": message });
+    if let Some(data) = data {
+        error["data"] = data;
+    }
+    json!({ "jsonrpc": "2.0", "id": id, "error": error })
+}
+
+#[test]
+fn test_decoding_offer_request() {
+    let frame = br#"{"jsonrpc":"2.0","id":1,"method":"signal.offer","params":{"name":"caller","target":"callee","sdp":"v=0"}}"#;
+
+    let request = decode_request(frame).unwrap();
+
+    assert_eq!(request.id, Some(json!(1)));
+    assert_eq!(
+        request.call,
+        Call::Signal(Signal::Offer(super::SessionDescriptionMessage::new(
+            "callee".to_owned(),
+            "caller".to_owned(),
+            "v=0".to_owned()
+        )))
+    );
+}
+
+#[test]
+fn test_rejecting_unknown_method() {
+    let frame =
+        br#"{"jsonrpc":"2.0","id":"a","method":"signal.ring","params":{"target":"callee"}}"#;
+
+    assert_eq!(
+        decode_request(frame),
+        Err(json!({
+            "jsonrpc": "2.0",
+            "id": "a",
+            "error": { "code": -32601, "message": "Method not found", "data": "signal.ring" }
+        }))
+    );
+}
+
+#[test]
+fn test_decoding_candidate_with_extra_fields() {
+    let frame = br#"{"jsonrpc":"2.0","method":"signal.candidate","params":{"type":"offer","target":"callee","candidate":"candidate:1","sdpMLineIndex":0}}"#;
+
+    let request = decode_request(frame).unwrap();
+
+    let mut ice_candidate = super::IceCandidate::new("callee".to_owned(), "candidate:1".to_owned());
+    ice_candidate
+        .extra
+        .insert("sdpMLineIndex".to_owned(), json!(0));
+    assert_eq!(request.id, None);
+    assert_eq!(
+        request.call,
+        Call::Signal(Signal::NewIceCandidate(ice_candidate))
+    );
+}
+
+#[test]
+fn test_encoding_signal_notification() {
+    let signal = Signal::assign("callee".to_owned());
+
+    assert_eq!(
+        notification(&signal).unwrap(),
+        json!({ "jsonrpc": "2.0", "method": "session.assign", "params": { "name": "callee" } })
+    );
+}
+
+#[test]
+fn test_mapping_error_response() {
+    let err = Error::TargetNotFound("callee".to_owned());
+
+    assert_eq!(
+        response(json!(7), Err(err)),
+        json!({
+            "jsonrpc": "2.0",
+            "id": 7,
+            "error": {
+                "code": -32001,
+                "message": "target user not found",
+                "data": {
+                    "type": "target user not found",
+                    "message": "user callee is not in connection"
+                }
+            }
+        })
+    );
+}
+
+#[test]
+fn test_decoding_room_join_and_encoding_its_members() {
+    let frame = br#"{"jsonrpc":"2.0","id":3,"method":"room.join","params":{"room":"lobby"}}"#;
+
+    let request = decode_request(frame).unwrap();
+    let members = vec!["callee".to_owned(), "caller".to_owned()];
+
+    assert_eq!(request.call, Call::JoinRoom("lobby".to_owned()));
+    assert_eq!(
+        room_response(json!(3), "lobby", Ok(members)),
+        json!({
+            "jsonrpc": "2.0",
+            "id": 3,
+            "result": { "room": "lobby", "members": ["callee", "caller"] }
+        })
+    );
+}
+
+#[test]
+fn test_rejecting_room_join_without_room() {
+    let frame = br#"{"jsonrpc":"2.0","id":4,"method":"room.join","params":{}}"#;
+
+    assert_eq!(
+        decode_request(frame),
+        Err(json!({
+            "jsonrpc": "2.0",
+            "id": 4,
+            "error": {
+                "code": -32602,
+                "message": "Invalid params",
+                "data": "room must be a non-empty string"
+            }
+        }))
+    );
+}
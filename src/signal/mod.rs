@@ -2,33 +2,263 @@ use super::Error;
 use actix::Message;
 
 mod deserialize;
+mod encoding;
+mod hello;
+pub(crate) mod jsonrpc;
 mod serialize;
+pub(crate) mod trace_parent;
+
+pub use encoding::{Encoding, Frame};
+pub use hello::{Feature, Hello, Limits, PROTOCOL_VERSION};
+pub use trace_parent::TraceParent;
 
 #[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
 pub enum Signal {
     Offer(SessionDescriptionMessage),
     Answer(SessionDescriptionMessage),
     NewIceCandidate(IceCandidate),
     Assign(String),
+    Hello(Hello),
+    Relay(RelayMessage),
+    Hangup(HangupMessage),
+    /// Announcement of the server operator to every connected user.
+    Notice(String),
 }
 
+/// Top-level fields of a signal that the server doesn't interpret.
+pub type Extra = serde_json::Map<String, serde_json::Value>;
+
 #[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
 pub struct SessionDescriptionMessage {
     pub target: String,
     pub name: String,
     sdp: String,
+    pub traceparent: Option<TraceParent>,
+    pub extra: Extra,
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
 pub struct IceCandidate {
     pub target: String,
+    /// Name of the user who gathered the candidate, told by the server to
+    /// receivers that take candidates of several peers.
+    pub name: Option<String>,
     candidate: String,
+    pub traceparent: Option<TraceParent>,
+    pub extra: Extra,
+}
+
+/// Application-defined message relayed to `target` without interpretation,
+/// e.g. mute state, chat or cursor positions.
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct RelayMessage {
+    pub target: String,
+    pub name: String,
+    pub kind: Option<String>,
+    pub payload: serde_json::Value,
+    pub traceparent: Option<TraceParent>,
+}
+
+/// Call-control event telling `target` that `name` ended their call.
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct HangupMessage {
+    pub target: String,
+    pub name: String,
+    pub traceparent: Option<TraceParent>,
+}
+
+impl SessionDescriptionMessage {
+    pub fn new(target: String, name: String, sdp: String) -> Self {
+        SessionDescriptionMessage {
+            target,
+            name,
+            sdp,
+            traceparent: None,
+            extra: Extra::new(),
+        }
+    }
+
+    pub fn sdp(&self) -> &str {
+        &self.sdp
+    }
+}
+
+impl IceCandidate {
+    pub fn new(target: String, candidate: String) -> Self {
+        IceCandidate {
+            target,
+            name: None,
+            candidate,
+            traceparent: None,
+            extra: Extra::new(),
+        }
+    }
+
+    pub fn candidate(&self) -> &str {
+        &self.candidate
+    }
+}
+
+impl RelayMessage {
+    pub fn new(target: String, name: String, payload: serde_json::Value) -> Self {
+        RelayMessage {
+            target,
+            name,
+            kind: None,
+            payload,
+            traceparent: None,
+        }
+    }
+
+    /// Application-defined kind of the payload, e.g. `mute`.
+    pub fn kind<T: Into<String>>(mut self, kind: T) -> Self {
+        self.kind = Some(kind.into());
+        self
+    }
+}
+
+impl HangupMessage {
+    pub fn new(target: String, name: String) -> Self {
+        HangupMessage {
+            target,
+            name,
+            traceparent: None,
+        }
+    }
 }
 
 impl Signal {
     pub fn assign(user_name: String) -> Signal {
         Signal::Assign(user_name)
     }
+
+    pub fn notice(message: String) -> Signal {
+        Signal::Notice(message)
+    }
+
+    /// Feature a connection must have negotiated in its `hello` to send or
+    /// receive this signal. Signals older than the handshake need none.
+    pub fn required_feature(&self) -> Option<Feature> {
+        match self {
+            Signal::Relay(_) => Some(Feature::Relay),
+            Signal::Hangup(_) => Some(Feature::Hangup),
+            Signal::Notice(_) => Some(Feature::Notice),
+            Signal::Offer(_)
+            | Signal::Answer(_)
+            | Signal::NewIceCandidate(_)
+            | Signal::Assign(_)
+            | Signal::Hello(_) => None,
+        }
+    }
+
+    /// Whether only the server sends the signal, so clients may not.
+    pub fn is_server_signal(&self) -> bool {
+        matches!(self, Signal::Assign(_) | Signal::Notice(_))
+    }
+
+    /// Value of the `type` field the signal is sent with.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Signal::Offer(_) => "offer",
+            Signal::Answer(_) => "answer",
+            Signal::NewIceCandidate(_) => "new_ice_candidate",
+            Signal::Assign(_) => "assign",
+            Signal::Hello(_) => "hello",
+            Signal::Relay(_) => "relay",
+            Signal::Hangup(_) => "hangup",
+            Signal::Notice(_) => "notice",
+        }
+    }
+
+    /// Name of the user the signal is routed to, if it is routed at all.
+    pub fn target(&self) -> Option<&str> {
+        match self {
+            Signal::Offer(sdp_signal) | Signal::Answer(sdp_signal) => Some(&sdp_signal.target),
+            Signal::NewIceCandidate(ice_candidate) => Some(&ice_candidate.target),
+            Signal::Relay(relay_message) => Some(&relay_message.target),
+            Signal::Hangup(hangup_message) => Some(&hangup_message.target),
+            Signal::Assign(_) | Signal::Hello(_) | Signal::Notice(_) => None,
+        }
+    }
+
+    /// Name of the user sending the signal, if it tells.
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            Signal::Offer(sdp_signal) | Signal::Answer(sdp_signal) => Some(&sdp_signal.name),
+            Signal::Relay(relay_message) => Some(&relay_message.name),
+            Signal::Hangup(hangup_message) => Some(&hangup_message.name),
+            Signal::NewIceCandidate(ice_candidate) => ice_candidate.name.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Replaces the name the signal tells with `name`, if it tells one.
+    pub(crate) fn set_name(&mut self, name: String) {
+        match self {
+            Signal::Offer(sdp_signal) | Signal::Answer(sdp_signal) => sdp_signal.name = name,
+            Signal::Relay(relay_message) => relay_message.name = name,
+            Signal::Hangup(hangup_message) => hangup_message.name = name,
+            Signal::NewIceCandidate(ice_candidate) => ice_candidate.name = Some(name),
+            _ => {}
+        }
+    }
+
+    /// Session description or candidate of the signal: what reveals the
+    /// addresses and media setup of a peer.
+    pub fn body(&self) -> Option<&str> {
+        match self {
+            Signal::Offer(sdp_signal) | Signal::Answer(sdp_signal) => Some(&sdp_signal.sdp),
+            Signal::NewIceCandidate(ice_candidate) => Some(&ice_candidate.candidate),
+            _ => None,
+        }
+    }
+
+    /// Trace context the signal was sent with, if it is routed at all.
+    pub fn traceparent(&self) -> Option<&TraceParent> {
+        self.traceparent_slot()?.as_ref()
+    }
+
+    pub fn set_traceparent(&mut self, traceparent: TraceParent) {
+        if let Some(slot) = self.traceparent_mut() {
+            *slot = Some(traceparent);
+        }
+    }
+
+    fn traceparent_slot(&self) -> Option<&Option<TraceParent>> {
+        match self {
+            Signal::Offer(sdp_signal) | Signal::Answer(sdp_signal) => Some(&sdp_signal.traceparent),
+            Signal::NewIceCandidate(ice_candidate) => Some(&ice_candidate.traceparent),
+            Signal::Relay(relay_message) => Some(&relay_message.traceparent),
+            Signal::Hangup(hangup_message) => Some(&hangup_message.traceparent),
+            Signal::Assign(_) | Signal::Hello(_) | Signal::Notice(_) => None,
+        }
+    }
+
+    fn traceparent_mut(&mut self) -> Option<&mut Option<TraceParent>> {
+        match self {
+            Signal::Offer(sdp_signal) | Signal::Answer(sdp_signal) => {
+                Some(&mut sdp_signal.traceparent)
+            }
+            Signal::NewIceCandidate(ice_candidate) => Some(&mut ice_candidate.traceparent),
+            Signal::Relay(relay_message) => Some(&mut relay_message.traceparent),
+            Signal::Hangup(hangup_message) => Some(&mut hangup_message.traceparent),
+            Signal::Assign(_) | Signal::Hello(_) | Signal::Notice(_) => None,
+        }
+    }
+
+    /// Unknown fields carried by the signal, if it can carry any.
+    pub fn extra_mut(&mut self) -> Option<&mut Extra> {
+        match self {
+            Signal::Offer(sdp_signal) | Signal::Answer(sdp_signal) => Some(&mut sdp_signal.extra),
+            Signal::NewIceCandidate(ice_candidate) => Some(&mut ice_candidate.extra),
+            _ => None,
+        }
+    }
 }
 
 impl Message for Signal {
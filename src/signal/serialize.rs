@@ -1,4 +1,4 @@
-use super::Signal;
+use super::{Extra, Signal, TraceParent};
 use serde::ser::SerializeMap;
 use serde::{Serialize, Serializer};
 
@@ -6,26 +6,35 @@ impl Serialize for Signal {
     fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
         match self {
             Signal::Offer(sdp_signal) => {
-                let mut map = serializer.serialize_map(Some(4))?;
+                let mut map = serializer.serialize_map(None)?;
                 map.serialize_entry("type", "offer")?;
                 map.serialize_entry("name", &sdp_signal.name)?;
                 map.serialize_entry("target", &sdp_signal.target)?;
                 map.serialize_entry("sdp", &sdp_signal.sdp)?;
+                serialize_traceparent(&mut map, &sdp_signal.traceparent)?;
+                serialize_extra(&mut map, &sdp_signal.extra)?;
                 map.end()
             }
             Signal::Answer(sdp_signal) => {
-                let mut map = serializer.serialize_map(Some(4))?;
+                let mut map = serializer.serialize_map(None)?;
                 map.serialize_entry("type", "answer")?;
                 map.serialize_entry("name", &sdp_signal.name)?;
                 map.serialize_entry("target", &sdp_signal.target)?;
                 map.serialize_entry("sdp", &sdp_signal.sdp)?;
+                serialize_traceparent(&mut map, &sdp_signal.traceparent)?;
+                serialize_extra(&mut map, &sdp_signal.extra)?;
                 map.end()
             }
             Signal::NewIceCandidate(ice_candidate) => {
-                let mut map = serializer.serialize_map(Some(3))?;
+                let mut map = serializer.serialize_map(None)?;
                 map.serialize_entry("type", "new_ice_candidate")?;
+                if let Some(name) = &ice_candidate.name {
+                    map.serialize_entry("name", name)?;
+                }
                 map.serialize_entry("target", &ice_candidate.target)?;
                 map.serialize_entry("candidate", &ice_candidate.candidate)?;
+                serialize_traceparent(&mut map, &ice_candidate.traceparent)?;
+                serialize_extra(&mut map, &ice_candidate.extra)?;
                 map.end()
             }
             Signal::Assign(user_name) => {
@@ -34,10 +43,63 @@ impl Serialize for Signal {
                 map.serialize_entry("name", &user_name)?;
                 map.end()
             }
+            Signal::Hello(hello) => {
+                let mut map = serializer.serialize_map(None)?;
+                map.serialize_entry("type", "hello")?;
+                map.serialize_entry("version", &hello.version)?;
+                map.serialize_entry("features", &hello.features)?;
+                if let Some(limits) = &hello.limits {
+                    map.serialize_entry("limits", limits)?;
+                }
+                map.end()
+            }
+            Signal::Relay(relay_message) => {
+                let mut map = serializer.serialize_map(None)?;
+                map.serialize_entry("type", "relay")?;
+                map.serialize_entry("name", &relay_message.name)?;
+                map.serialize_entry("target", &relay_message.target)?;
+                if let Some(kind) = &relay_message.kind {
+                    map.serialize_entry("kind", kind)?;
+                }
+                map.serialize_entry("payload", &relay_message.payload)?;
+                serialize_traceparent(&mut map, &relay_message.traceparent)?;
+                map.end()
+            }
+            Signal::Hangup(hangup_message) => {
+                let mut map = serializer.serialize_map(None)?;
+                map.serialize_entry("type", "hangup")?;
+                map.serialize_entry("name", &hangup_message.name)?;
+                map.serialize_entry("target", &hangup_message.target)?;
+                serialize_traceparent(&mut map, &hangup_message.traceparent)?;
+                map.end()
+            }
+            Signal::Notice(message) => {
+                let mut map = serializer.serialize_map(Some(2))?;
+                map.serialize_entry("type", "notice")?;
+                map.serialize_entry("message", message)?;
+                map.end()
+            }
         }
     }
 }
 
+fn serialize_traceparent<M: SerializeMap>(
+    map: &mut M,
+    traceparent: &Option<TraceParent>,
+) -> Result<(), M::Error> {
+    match traceparent {
+        Some(traceparent) => map.serialize_entry("traceparent", &traceparent.to_string()),
+        None => Ok(()),
+    }
+}
+
+fn serialize_extra<M: SerializeMap>(map: &mut M, extra: &Extra) -> Result<(), M::Error> {
+    for (key, value) in extra {
+        map.serialize_entry(key, value)?;
+    }
+    Ok(())
+}
+
 #[test]
 fn test_serealizing_offer_signal() {
     use super::SessionDescriptionMessage;
@@ -46,6 +108,8 @@ fn test_serealizing_offer_signal() {
         target: "4fe681ad-aba1-4732-89df-ee784b7d4abf".to_owned(),
         name: "3872379c-4743-4a7d-b2ee-79cf7368cf58".to_owned(),
         sdp: "sdp".to_owned(),
+        traceparent: None,
+        extra: Default::default(),
     });
 
     let offer_signal_text = r#"{"type":"offer","name":"3872379c-4743-4a7d-b2ee-79cf7368cf58","target":"4fe681ad-aba1-4732-89df-ee784b7d4abf","sdp":"sdp"}"#;
@@ -64,6 +128,8 @@ fn test_serializing_answer_signal() {
         target: "4fe681ad-aba1-4732-89df-ee784b7d4abf".to_owned(),
         name: "3872379c-4743-4a7d-b2ee-79cf7368cf58".to_owned(),
         sdp: "sdp".to_owned(),
+        traceparent: None,
+        extra: Default::default(),
     });
 
     let answer_signal_text = r#"{"type":"answer","name":"3872379c-4743-4a7d-b2ee-79cf7368cf58","target":"4fe681ad-aba1-4732-89df-ee784b7d4abf","sdp":"sdp"}"#;
@@ -80,7 +146,10 @@ fn test_serializing_new_ice_candidate_signal() {
 
     let new_ice_candidate_struct = Signal::NewIceCandidate(IceCandidate {
         target: "4fe681ad-aba1-4732-89df-ee784b7d4abf".to_owned(),
+        name: None,
         candidate: "candidate".to_owned(),
+        traceparent: None,
+        extra: Default::default(),
     });
 
     let ice_candidate_text = r#"{"type":"new_ice_candidate","target":"4fe681ad-aba1-4732-89df-ee784b7d4abf","candidate":"candidate"}"#;
@@ -102,3 +171,134 @@ fn test_serializing_assign_message() {
         assign_message_text
     );
 }
+
+#[test]
+fn test_serializing_hello_message() {
+    use super::{Feature, Hello, Limits};
+
+    let hello_message_struct = Signal::Hello(Hello {
+        version: 1,
+        features: vec![Feature::Binary],
+        limits: Some(Limits {
+            max_message_size: 1024,
+        }),
+    });
+
+    let hello_message_text =
+        r#"{"type":"hello","version":1,"features":["binary"],"limits":{"max_message_size":1024}}"#;
+
+    assert_eq!(
+        &serde_json::to_string(&hello_message_struct).unwrap(),
+        hello_message_text
+    );
+}
+
+#[test]
+fn test_serializing_relay_signal() {
+    use super::RelayMessage;
+
+    let relay_signal_struct = Signal::Relay(RelayMessage {
+        target: "4fe681ad-aba1-4732-89df-ee784b7d4abf".to_owned(),
+        name: "3872379c-4743-4a7d-b2ee-79cf7368cf58".to_owned(),
+        kind: Some("mute".to_owned()),
+        payload: serde_json::json!({ "audio": true }),
+        traceparent: None,
+    });
+
+    let relay_signal_text = r#"{"type":"relay","name":"3872379c-4743-4a7d-b2ee-79cf7368cf58","target":"4fe681ad-aba1-4732-89df-ee784b7d4abf","kind":"mute","payload":{"audio":true}}"#;
+
+    assert_eq!(
+        &serde_json::to_string(&relay_signal_struct).unwrap(),
+        relay_signal_text
+    );
+}
+
+#[test]
+fn test_serializing_new_ice_candidate_signal_with_name() {
+    use super::IceCandidate;
+
+    let mut ice_candidate = IceCandidate::new(
+        "4fe681ad-aba1-4732-89df-ee784b7d4abf".to_owned(),
+        "candidate".to_owned(),
+    );
+    ice_candidate.name = Some("caller".to_owned());
+
+    let ice_candidate_text = r#"{"type":"new_ice_candidate","name":"caller","target":"4fe681ad-aba1-4732-89df-ee784b7d4abf","candidate":"candidate"}"#;
+
+    assert_eq!(
+        &serde_json::to_string(&Signal::NewIceCandidate(ice_candidate)).unwrap(),
+        ice_candidate_text
+    );
+}
+
+#[test]
+fn test_serializing_new_ice_candidate_signal_with_extra_fields() {
+    use super::IceCandidate;
+
+    let mut extra = Extra::new();
+    extra.insert("sdpMid".to_owned(), serde_json::json!("0"));
+    let new_ice_candidate_struct = Signal::NewIceCandidate(IceCandidate {
+        target: "4fe681ad-aba1-4732-89df-ee784b7d4abf".to_owned(),
+        name: None,
+        candidate: "candidate".to_owned(),
+        traceparent: None,
+        extra,
+    });
+
+    let ice_candidate_text = r#"{"type":"new_ice_candidate","target":"4fe681ad-aba1-4732-89df-ee784b7d4abf","candidate":"candidate","sdpMid":"0"}"#;
+
+    assert_eq!(
+        &serde_json::to_string(&new_ice_candidate_struct).unwrap(),
+        ice_candidate_text
+    );
+}
+
+#[test]
+fn test_serializing_hangup_signal() {
+    use super::HangupMessage;
+
+    let hangup_signal_struct = Signal::Hangup(HangupMessage {
+        target: "4fe681ad-aba1-4732-89df-ee784b7d4abf".to_owned(),
+        name: "3872379c-4743-4a7d-b2ee-79cf7368cf58".to_owned(),
+        traceparent: None,
+    });
+
+    let hangup_signal_text = r#"{"type":"hangup","name":"3872379c-4743-4a7d-b2ee-79cf7368cf58","target":"4fe681ad-aba1-4732-89df-ee784b7d4abf"}"#;
+
+    assert_eq!(
+        &serde_json::to_string(&hangup_signal_struct).unwrap(),
+        hangup_signal_text
+    );
+}
+
+#[test]
+fn test_serializing_hangup_signal_with_traceparent() {
+    use super::HangupMessage;
+
+    let hangup_signal_struct = Signal::Hangup(HangupMessage {
+        target: "4fe681ad-aba1-4732-89df-ee784b7d4abf".to_owned(),
+        name: "3872379c-4743-4a7d-b2ee-79cf7368cf58".to_owned(),
+        traceparent: "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"
+            .parse()
+            .ok(),
+    });
+
+    let hangup_signal_text = r#"{"type":"hangup","name":"3872379c-4743-4a7d-b2ee-79cf7368cf58","target":"4fe681ad-aba1-4732-89df-ee784b7d4abf","traceparent":"00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"}"#;
+
+    assert_eq!(
+        &serde_json::to_string(&hangup_signal_struct).unwrap(),
+        hangup_signal_text
+    );
+}
+
+#[test]
+fn test_serializing_notice_signal() {
+    let notice_signal_struct = Signal::notice("maintenance in 5 minutes".to_owned());
+
+    let notice_signal_text = r#"{"type":"notice","message":"maintenance in 5 minutes"}"#;
+
+    assert_eq!(
+        &serde_json::to_string(&notice_signal_struct).unwrap(),
+        notice_signal_text
+    );
+}
@@ -0,0 +1,114 @@
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+/// W3C trace context of a signal, sent as its `traceparent` field, e.g.
+/// `00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct TraceParent {
+    pub trace_id: [u8; 16],
+    pub span_id: [u8; 8],
+    pub flags: u8,
+}
+
+impl TraceParent {
+    pub const SAMPLED: u8 = 0x01;
+
+    pub fn new(trace_id: [u8; 16], span_id: [u8; 8], flags: u8) -> Self {
+        TraceParent {
+            trace_id,
+            span_id,
+            flags,
+        }
+    }
+
+    /// Whether the caller records the trace, so its spans are exported.
+    pub fn is_sampled(&self) -> bool {
+        self.flags & Self::SAMPLED != 0
+    }
+}
+
+impl FromStr for TraceParent {
+    type Err = ();
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut parts = text.split('-');
+        let version = parts.next().ok_or(())?;
+        let trace_id = parts.next().ok_or(())?;
+        let span_id = parts.next().ok_or(())?;
+        let flags = parts.next().ok_or(())?;
+
+        // later versions may append fields, version 00 has exactly four
+        let version = decode_hex::<1>(version)?[0];
+        if version == 0xff || (version == 0 && parts.next().is_some()) {
+            return Err(());
+        }
+
+        let trace_parent = TraceParent {
+            trace_id: decode_hex(trace_id)?,
+            span_id: decode_hex(span_id)?,
+            flags: decode_hex::<1>(flags)?[0],
+        };
+        if trace_parent.trace_id == [0; 16] || trace_parent.span_id == [0; 8] {
+            return Err(());
+        }
+        Ok(trace_parent)
+    }
+}
+
+impl Display for TraceParent {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            formatter,
+            "00-{}-{}-{:02x}",
+            encode_hex(&self.trace_id),
+            encode_hex(&self.span_id),
+            self.flags
+        )
+    }
+}
+
+pub fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn decode_hex<const N: usize>(text: &str) -> Result<[u8; N], ()> {
+    if text.len() != N * 2 || !text.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return Err(());
+    }
+
+    let mut bytes = [0; N];
+    for (index, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&text[index * 2..index * 2 + 2], 16).map_err(|_| ())?;
+    }
+    Ok(bytes)
+}
+
+#[test]
+fn test_parsing_trace_parent() {
+    let text = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    let trace_parent: TraceParent = text.parse().unwrap();
+
+    assert_eq!(
+        trace_parent.span_id,
+        [0, 0xf0, 0x67, 0xaa, 0x0b, 0xa9, 0x02, 0xb7]
+    );
+    assert_eq!(trace_parent.flags, TraceParent::SAMPLED);
+    assert_eq!(trace_parent.to_string(), text);
+}
+
+#[test]
+fn test_rejecting_invalid_trace_parent() {
+    for text in &[
+        "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+        "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+        "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+        "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+        "00-4bf92f3577b34da6a3ce929d0e0e473-00f067aa0ba902b7-01",
+        "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902bz-01",
+        "+0-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+    ] {
+        assert_eq!(text.parse::<TraceParent>(), Err(()), "{}", text);
+    }
+}
@@ -0,0 +1,191 @@
+//! Rules deciding which members may signal which, checked by the router
+//! before forwarding a signal to its target.
+//!
+//! Rules are tried in order and the first one matching a signal decides
+//! whether it is forwarded, `default` deciding if none does:
+//!
+//! ```json
+//! {
+//!   "default": "deny",
+//!   "rules": [
+//!     { "effect": "deny", "sender": { "name": "bob" }, "target": { "name": "alice" } },
+//!     { "effect": "allow", "sender": { "role": "guest" }, "target": { "role": "agent" } },
+//!     { "effect": "deny", "sender": { "role": "guest" } },
+//!     { "effect": "allow", "same": ["tenant"] }
+//!   ]
+//! }
+//! ```
+//!
+//! `sender` and `target` match the `name` of a member and the top-level
+//! fields of the metadata it was admitted with, a list matching any of its
+//! values. `same` matches members having equal values of the given metadata
+//! fields, and `signals` restricts a rule to signal types, e.g. `["offer"]`.
+
+use serde_json::{Map, Value};
+
+use super::Error;
+
+/// Rules signals are forwarded by. The default one forwards every signal.
+#[derive(Clone, Debug, Default, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SignalPolicy {
+    #[serde(default)]
+    default: Effect,
+    #[serde(default)]
+    rules: Vec<Rule>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Effect {
+    #[default]
+    Allow,
+    Deny,
+}
+
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Rule {
+    effect: Effect,
+    #[serde(default)]
+    signals: Option<Vec<String>>,
+    #[serde(default)]
+    sender: Map<String, Value>,
+    #[serde(default)]
+    target: Map<String, Value>,
+    #[serde(default)]
+    same: Vec<String>,
+}
+
+/// Member sending or receiving a signal.
+pub(crate) struct Party<'a> {
+    pub name: &'a str,
+    pub metadata: Option<&'a Value>,
+}
+
+impl Party<'_> {
+    fn field(&self, field: &str) -> Option<&Value> {
+        self.metadata.and_then(|metadata| metadata.get(field))
+    }
+
+    fn matches(&self, pattern: &Map<String, Value>) -> bool {
+        pattern.iter().all(|(field, expected)| {
+            let name = Value::from(self.name);
+            let actual = match field.as_str() {
+                "name" => Some(&name),
+                field => self.field(field),
+            };
+            match (actual, expected) {
+                (Some(actual), Value::Array(values)) => values.contains(actual),
+                (actual, expected) => actual == Some(expected),
+            }
+        })
+    }
+}
+
+impl Rule {
+    fn matches(&self, kind: &str, sender: &Party, target: &Party) -> bool {
+        let signals_match = match &self.signals {
+            Some(signals) => signals.iter().any(|signal| signal == kind),
+            None => true,
+        };
+        signals_match
+            && sender.matches(&self.sender)
+            && target.matches(&self.target)
+            && self.same.iter().all(|field| match sender.field(field) {
+                Some(value) => target.field(field) == Some(value),
+                None => false,
+            })
+    }
+}
+
+impl SignalPolicy {
+    /// Whether every signal is forwarded, whoever sends it.
+    pub(crate) fn is_permissive(&self) -> bool {
+        self.rules.is_empty() && self.default == Effect::Allow
+    }
+
+    /// Fails with `Forbidden` if a signal of type `kind` may not be
+    /// forwarded from `sender` to `target`.
+    pub(crate) fn check(&self, kind: &str, sender: &Party, target: &Party) -> Result<(), Error> {
+        let effect = self
+            .rules
+            .iter()
+            .find(|rule| rule.matches(kind, sender, target))
+            .map_or(self.default, |rule| rule.effect);
+        match effect {
+            Effect::Allow => Ok(()),
+            Effect::Deny => Err(Error::Forbidden(target.name.to_owned())),
+        }
+    }
+}
+
+#[cfg(test)]
+fn party<'a>(name: &'a str, metadata: &'a Value) -> Party<'a> {
+    Party {
+        name,
+        metadata: Some(metadata),
+    }
+}
+
+#[test]
+fn test_allowing_calls_within_tenant_only() {
+    use serde_json::json;
+
+    let policy: SignalPolicy = serde_json::from_value(json!({
+        "default": "deny",
+        "rules": [{ "effect": "allow", "same": ["tenant"] }],
+    }))
+    .unwrap();
+    let (acme, initech, none) = (
+        json!({ "tenant": "acme" }),
+        json!({ "tenant": "initech" }),
+        json!({}),
+    );
+
+    assert!(policy
+        .check("offer", &party("a", &acme), &party("b", &acme))
+        .is_ok());
+    assert!(policy
+        .check("offer", &party("a", &acme), &party("b", &initech))
+        .is_err());
+    assert!(policy
+        .check("offer", &party("a", &none), &party("b", &none))
+        .is_err());
+}
+
+#[test]
+fn test_applying_first_matching_rule() {
+    use serde_json::json;
+
+    let policy: SignalPolicy = serde_json::from_value(json!({
+        "rules": [
+            { "effect": "deny", "signals": ["offer"], "sender": { "name": "bob" }, "target": { "name": "alice" } },
+            { "effect": "allow", "sender": { "role": "guest" }, "target": { "role": ["agent", "supervisor"] } },
+            { "effect": "deny", "sender": { "role": "guest" } },
+        ],
+    }))
+    .unwrap();
+    let (guest, supervisor, none) = (
+        json!({ "role": "guest" }),
+        json!({ "role": "supervisor" }),
+        json!({}),
+    );
+
+    assert!(matches!(
+        policy.check("offer", &party("bob", &none), &party("alice", &none)),
+        Err(Error::Forbidden(target)) if target == "alice"
+    ));
+    assert!(policy
+        .check("hangup", &party("bob", &none), &party("alice", &none))
+        .is_ok());
+    assert!(policy
+        .check("offer", &party("alice", &none), &party("bob", &none))
+        .is_ok());
+    assert!(policy
+        .check("offer", &party("g", &guest), &party("s", &supervisor))
+        .is_ok());
+    assert!(policy
+        .check("offer", &party("g", &guest), &party("h", &guest))
+        .is_err());
+}
@@ -1,15 +1,53 @@
 use super::signal::Signal;
-use actix::fut::wrap_future;
-use actix::prelude::{Actor, Context, Handler, Message, Recipient, ResponseActFuture};
-use futures::TryFutureExt;
-use std::collections::HashMap;
+use actix::fut::{wrap_future, ActorFuture};
+use actix::prelude::{
+    Actor, Context, Handler, Message, MessageResult, Recipient, ResponseActFuture,
+};
+use futures::{FutureExt, TryFutureExt};
+use serde_json::Value;
+use std::collections::{BTreeSet, HashMap};
 use std::future::Future;
-
+use std::net::SocketAddr;
+use std::time::SystemTime;
+use tracing::{field, info_span, Span};
+
+use super::field_passthrough::FieldPassthrough;
+use super::relay_policy::RelayPolicy;
+use super::signal_policy::{Party, SignalPolicy};
+use super::telemetry;
 use super::Error;
 
 #[derive(Default)]
 pub struct SignalRouter {
-    sockets: HashMap<String, Recipient<Signal>>,
+    members: HashMap<String, Member>,
+    relay_policy: RelayPolicy,
+    signal_policy: SignalPolicy,
+    field_passthrough: FieldPassthrough,
+    subscribers: Vec<Recipient<RouterEvent>>,
+}
+
+struct Member {
+    signal_recipient: Recipient<Signal>,
+    close_recipient: Option<Recipient<CloseMessage>>,
+    info: MemberInfo,
+}
+
+/// Connection of a router member, as shown to operators.
+#[derive(Clone, Debug)]
+pub struct MemberInfo {
+    pub name: String,
+    /// How the member is connected, e.g. `websocket` or `sip`.
+    pub protocol: &'static str,
+    pub remote_addr: Option<SocketAddr>,
+    pub connected_at: SystemTime,
+    /// Signals the member handed to the router.
+    pub signals_sent: u64,
+    /// Signals the router forwarded to the member.
+    pub signals_received: u64,
+    /// Attached to the session when it was admitted.
+    pub metadata: Option<Value>,
+    /// Rooms the member joined, which it leaves on exit.
+    pub rooms: BTreeSet<String>,
 }
 
 impl Actor for SignalRouter {
@@ -17,49 +55,157 @@ impl Actor for SignalRouter {
 }
 
 impl SignalRouter {
-    fn target(&self, target_name: &str) -> Option<&Recipient<Signal>> {
-        self.sockets.get(target_name)
+    pub fn new(
+        relay_policy: RelayPolicy,
+        signal_policy: SignalPolicy,
+        field_passthrough: FieldPassthrough,
+    ) -> Self {
+        SignalRouter {
+            members: HashMap::new(),
+            relay_policy,
+            signal_policy,
+            field_passthrough,
+            subscribers: Vec::new(),
+        }
     }
 
-    fn wrap_future<F>(future: F) -> ResponseActFuture<Self, Result<(), Error>>
+    /// Tells every subscriber about `event`, forgetting those that stopped.
+    fn publish(&mut self, event: RouterEvent) {
+        self.subscribers
+            .retain(|subscriber| subscriber.do_send(event.clone()).is_ok());
+    }
+
+    /// Checks the signal policy for a signal, whether its target is a
+    /// member or not, so `Forbidden` doesn't tell which names are taken.
+    /// Signals without a sender are only forwarded by a permissive policy.
+    fn authorize(
+        &self,
+        kind: &str,
+        sender_name: Option<&str>,
+        target_name: &str,
+    ) -> Result<(), Error> {
+        let sender_name = match sender_name {
+            Some(sender_name) => sender_name,
+            None if self.signal_policy.is_permissive() => return Ok(()),
+            None => return Err(Error::Forbidden(target_name.to_owned())),
+        };
+        let sender = Party {
+            name: sender_name,
+            metadata: self.metadata(sender_name),
+        };
+        let target = Party {
+            name: target_name,
+            metadata: self.metadata(target_name),
+        };
+        self.signal_policy.check(kind, &sender, &target)
+    }
+
+    fn metadata(&self, name: &str) -> Option<&Value> {
+        self.members
+            .get(name)
+            .and_then(|member| member.info.metadata.as_ref())
+    }
+
+    fn forward(
+        &mut self,
+        target_name: &str,
+        signal: Signal,
+    ) -> impl Future<Output = Result<(), Error>> + 'static {
+        match self.members.get_mut(target_name) {
+            Some(target) => {
+                target.info.signals_received += 1;
+                target
+                    .signal_recipient
+                    .send(signal)
+                    .unwrap_or_else(into_target_related_error)
+                    .left_future()
+            }
+            None => {
+                futures::future::err(Error::TargetNotFound(target_name.to_owned())).right_future()
+            }
+        }
+    }
+
+    /// Resolves to the outcome of routing, closing `span` with it and
+    /// publishing it as `routed`.
+    fn traced<F>(
+        future: F,
+        span: Span,
+        mut routed: Routed,
+    ) -> ResponseActFuture<Self, Result<(), Error>>
     where
         F: Future<Output = Result<(), Error>> + 'static,
     {
-        Box::new(wrap_future(future))
+        Box::new(
+            wrap_future(future).map(move |result, router: &mut Self, _| {
+                if let Err(err) = &result {
+                    span.record("error", field::display(err));
+                    routed.error = Some(err.name());
+                }
+                router.publish(RouterEvent::Routed(routed));
+                result
+            }),
+        )
     }
 }
 
 impl Handler<SignalMessage> for SignalRouter {
     type Result = ResponseActFuture<Self, Result<(), Error>>;
 
-    fn handle(&mut self, message: SignalMessage, _: &mut Self::Context) -> Self::Result {
-        match &message.0 {
-            Signal::Answer(signal) | Signal::Offer(signal) => {
-                if let Some(target_socket) = self.target(&signal.target) {
-                    let message_transfer_future = target_socket
-                        .send(message.0)
-                        .unwrap_or_else(into_target_related_error);
-                    Self::wrap_future(message_transfer_future)
-                } else {
-                    Self::wrap_future(futures::future::err(Error::TargetNotFound(
-                        signal.target.clone(),
-                    )))
-                }
-            }
-            Signal::NewIceCandidate(ice_candidate) => {
-                if let Some(target_socket) = self.target(&ice_candidate.target) {
-                    let message_transfer_future = target_socket
-                        .send(message.0)
-                        .unwrap_or_else(into_target_related_error);
-                    Self::wrap_future(message_transfer_future)
-                } else {
-                    Self::wrap_future(futures::future::err(Error::TargetNotFound(
-                        ice_candidate.target.clone(),
-                    )))
+    fn handle(&mut self, mut message: SignalMessage, _: &mut Self::Context) -> Self::Result {
+        if !message.translated {
+            self.field_passthrough.apply(&mut message.signal);
+        }
+        // receivers go by the name a signal tells, which only the router
+        // can vouch for
+        if let Some(sender) = &message.sender {
+            message.signal.set_name(sender.clone());
+        }
+        if let Some(sender) = message
+            .sender
+            .as_ref()
+            .and_then(|name| self.members.get_mut(name))
+        {
+            sender.info.signals_sent += 1;
+        }
+        let routed = Routed {
+            kind: message.signal.kind(),
+            sender: message
+                .sender
+                .clone()
+                .or_else(|| message.signal.name().map(str::to_owned)),
+            target: message.signal.target().map(str::to_owned),
+            error: None,
+        };
+
+        // routing is not part of whatever the router's thread is doing
+        let span = info_span!(
+            parent: None,
+            "route",
+            signal_type = message.signal.kind(),
+            target = message.signal.target().unwrap_or_default(),
+            traceparent = field::Empty,
+            error = field::Empty,
+        );
+        telemetry::trace_through(&span, &mut message.signal);
+
+        let target_name = match &message.signal {
+            Signal::Answer(signal) | Signal::Offer(signal) => signal.target.clone(),
+            Signal::NewIceCandidate(ice_candidate) => ice_candidate.target.clone(),
+            Signal::Hangup(hangup_message) => hangup_message.target.clone(),
+            Signal::Relay(relay_message) => {
+                if let Err(err) = self.relay_policy.check(relay_message) {
+                    return Self::traced(futures::future::err(err), span, routed);
                 }
+                relay_message.target.clone()
             }
-            _ => Self::wrap_future(futures::future::ok(())), //do nothing
+            _ => return Self::traced(futures::future::ok(()), span, routed), //do nothing
+        };
+
+        if let Err(err) = self.authorize(routed.kind, message.sender.as_deref(), &target_name) {
+            return Self::traced(futures::future::err(err), span, routed);
         }
+        Self::traced(self.forward(&target_name, message.signal), span, routed)
     }
 }
 
@@ -74,8 +220,29 @@ impl Handler<JoinMessage> for SignalRouter {
     type Result = <JoinMessage as Message>::Result;
 
     fn handle(&mut self, message: JoinMessage, _: &mut Self::Context) -> Self::Result {
-        self.sockets
-            .insert(message.user_name, message.signal_recipient);
+        if self.members.contains_key(&message.user_name) {
+            return Err(());
+        }
+
+        let info = MemberInfo {
+            name: message.user_name.clone(),
+            protocol: message.protocol,
+            remote_addr: message.remote_addr,
+            connected_at: SystemTime::now(),
+            signals_sent: 0,
+            signals_received: 0,
+            metadata: message.metadata,
+            rooms: BTreeSet::new(),
+        };
+        self.publish(RouterEvent::Joined(info.clone()));
+        self.members.insert(
+            message.user_name,
+            Member {
+                signal_recipient: message.signal_recipient,
+                close_recipient: message.close_recipient,
+                info,
+            },
+        );
         Ok(())
     }
 }
@@ -84,12 +251,273 @@ impl Handler<ExitMessage> for SignalRouter {
     type Result = <JoinMessage as Message>::Result;
 
     fn handle(&mut self, message: ExitMessage, _: &mut Self::Context) -> Self::Result {
-        self.sockets.remove(&message.0);
+        if self.members.remove(&message.0).is_some() {
+            self.publish(RouterEvent::Exited(message.0));
+        }
         Ok(())
     }
 }
 
-pub struct SignalMessage(Signal);
+/// Adds a member to a room, creating the room if it has no members yet.
+/// Resolves to the names of the members of the room, sorted.
+pub struct JoinRoomMessage {
+    user_name: String,
+    room: String,
+}
+
+impl JoinRoomMessage {
+    pub fn new(user_name: String, room: String) -> Self {
+        JoinRoomMessage { user_name, room }
+    }
+}
+
+impl Message for JoinRoomMessage {
+    type Result = Result<Vec<String>, Error>;
+}
+
+impl Handler<JoinRoomMessage> for SignalRouter {
+    type Result = Result<Vec<String>, Error>;
+
+    fn handle(&mut self, message: JoinRoomMessage, _: &mut Self::Context) -> Self::Result {
+        match self.members.get_mut(&message.user_name) {
+            Some(member) => member.info.rooms.insert(message.room.clone()),
+            None => return Err(Error::TargetNotFound(message.user_name)),
+        };
+        let mut room_members: Vec<String> = self
+            .members
+            .values()
+            .filter(|member| member.info.rooms.contains(&message.room))
+            .map(|member| member.info.name.clone())
+            .collect();
+        room_members.sort();
+        self.publish(RouterEvent::JoinedRoom {
+            user_name: message.user_name,
+            room: message.room,
+        });
+        Ok(room_members)
+    }
+}
+
+/// Asks the router for the names of its members, sorted.
+pub struct MembersMessage;
+
+impl Message for MembersMessage {
+    type Result = Vec<String>;
+}
+
+impl Handler<MembersMessage> for SignalRouter {
+    type Result = MessageResult<MembersMessage>;
+
+    fn handle(&mut self, _: MembersMessage, _: &mut Self::Context) -> Self::Result {
+        let mut members: Vec<String> = self.members.keys().cloned().collect();
+        members.sort();
+        MessageResult(members)
+    }
+}
+
+/// Asks the router for the connections of its members, sorted by name.
+pub struct MemberInfosMessage;
+
+impl Message for MemberInfosMessage {
+    type Result = Vec<MemberInfo>;
+}
+
+impl Handler<MemberInfosMessage> for SignalRouter {
+    type Result = MessageResult<MemberInfosMessage>;
+
+    fn handle(&mut self, _: MemberInfosMessage, _: &mut Self::Context) -> Self::Result {
+        let mut member_infos: Vec<MemberInfo> = self
+            .members
+            .values()
+            .map(|member| member.info.clone())
+            .collect();
+        member_infos.sort_by(|left, right| left.name.cmp(&right.name));
+        MessageResult(member_infos)
+    }
+}
+
+/// Asks the router for the connection of one member.
+pub struct MemberInfoMessage(pub String);
+
+impl Message for MemberInfoMessage {
+    type Result = Option<MemberInfo>;
+}
+
+impl Handler<MemberInfoMessage> for SignalRouter {
+    type Result = Option<MemberInfo>;
+
+    fn handle(&mut self, message: MemberInfoMessage, _: &mut Self::Context) -> Self::Result {
+        self.members
+            .get(&message.0)
+            .map(|member| member.info.clone())
+    }
+}
+
+/// Closes the connection of a member, telling it why. A member that can't
+/// be closed is only removed from the router.
+pub struct DisconnectMessage {
+    user_name: String,
+    reason: String,
+}
+
+impl DisconnectMessage {
+    pub fn new(user_name: String, reason: String) -> Self {
+        DisconnectMessage { user_name, reason }
+    }
+}
+
+impl Message for DisconnectMessage {
+    type Result = Result<(), Error>;
+}
+
+impl Handler<DisconnectMessage> for SignalRouter {
+    type Result = Result<(), Error>;
+
+    fn handle(&mut self, message: DisconnectMessage, _: &mut Self::Context) -> Self::Result {
+        let member = match self.members.get(&message.user_name) {
+            Some(member) => member,
+            None => return Err(Error::TargetNotFound(message.user_name)),
+        };
+
+        // the member exits the router once its connection is closed
+        match &member.close_recipient {
+            Some(close_recipient) => close_recipient
+                .do_send(CloseMessage::new(message.reason))
+                .map_err(|_| Error::ConnectionClosed),
+            None => {
+                self.members.remove(&message.user_name);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Sends a signal to every member. Resolves to the number of members that
+/// took it, leaving out those that can't receive it, e.g. as they didn't
+/// negotiate its feature.
+pub struct BroadcastMessage(pub Signal);
+
+impl Message for BroadcastMessage {
+    type Result = Result<usize, Error>;
+}
+
+impl Handler<BroadcastMessage> for SignalRouter {
+    type Result = ResponseActFuture<Self, Result<usize, Error>>;
+
+    fn handle(&mut self, message: BroadcastMessage, _: &mut Self::Context) -> Self::Result {
+        let deliveries: Vec<_> = self
+            .members
+            .iter()
+            .map(|(name, member)| {
+                let name = name.clone();
+                member
+                    .signal_recipient
+                    .send(message.0.clone())
+                    .map(move |result| (name, result))
+            })
+            .collect();
+        Box::new(wrap_future(futures::future::join_all(deliveries)).map(
+            |results, router: &mut Self, _| {
+                let mut recipients = 0;
+                for (name, result) in results {
+                    if let Ok(Ok(())) = result {
+                        if let Some(member) = router.members.get_mut(&name) {
+                            member.info.signals_received += 1;
+                        }
+                        recipients += 1;
+                    }
+                }
+                Ok(recipients)
+            },
+        ))
+    }
+}
+
+/// Something that happened in the router, as told to its subscribers.
+#[derive(Clone, Debug)]
+pub enum RouterEvent {
+    Joined(MemberInfo),
+    Exited(String),
+    JoinedRoom { user_name: String, room: String },
+    Routed(Routed),
+}
+
+impl Message for RouterEvent {
+    type Result = ();
+}
+
+/// Outcome of routing a signal.
+#[derive(Clone, Debug)]
+pub struct Routed {
+    /// `type` of the signal, e.g. `offer`.
+    pub kind: &'static str,
+    pub sender: Option<String>,
+    pub target: Option<String>,
+    /// Name of the `Error` variant the signal failed with.
+    pub error: Option<&'static str>,
+}
+
+/// Subscribes to the events of the router. Resolves to its members at the
+/// time of subscribing, which later `Joined` and `Exited` events update.
+pub struct SubscribeMessage(pub Recipient<RouterEvent>);
+
+impl Message for SubscribeMessage {
+    type Result = Vec<MemberInfo>;
+}
+
+impl Handler<SubscribeMessage> for SignalRouter {
+    type Result = MessageResult<SubscribeMessage>;
+
+    fn handle(&mut self, message: SubscribeMessage, _: &mut Self::Context) -> Self::Result {
+        self.subscribers.push(message.0);
+        MessageResult(
+            self.members
+                .values()
+                .map(|member| member.info.clone())
+                .collect(),
+        )
+    }
+}
+
+/// Tells a member that the server closes its connection.
+pub struct CloseMessage {
+    pub reason: String,
+}
+
+impl CloseMessage {
+    pub fn new(reason: String) -> Self {
+        CloseMessage { reason }
+    }
+}
+
+impl Message for CloseMessage {
+    type Result = ();
+}
+
+pub struct SignalMessage {
+    signal: Signal,
+    translated: bool,
+    sender: Option<String>,
+}
+
+impl SignalMessage {
+    /// Name of the member sending the signal. It replaces the name the
+    /// signal tells, and is counted in the statistics of the member.
+    pub fn sender(mut self, user_name: String) -> Self {
+        self.sender = Some(user_name);
+        self
+    }
+
+    /// Signal translated by the server from another protocol. Its unknown
+    /// fields were set by the server itself and are forwarded as they are.
+    pub fn translated(signal: Signal) -> Self {
+        SignalMessage {
+            signal,
+            translated: true,
+            sender: None,
+        }
+    }
+}
 
 impl Message for SignalMessage {
     type Result = Result<(), Error>;
@@ -97,13 +525,21 @@ impl Message for SignalMessage {
 
 impl From<Signal> for SignalMessage {
     fn from(signal: Signal) -> Self {
-        SignalMessage(signal)
+        SignalMessage {
+            signal,
+            translated: false,
+            sender: None,
+        }
     }
 }
 
 pub struct JoinMessage {
     user_name: String,
     signal_recipient: Recipient<Signal>,
+    close_recipient: Option<Recipient<CloseMessage>>,
+    protocol: &'static str,
+    remote_addr: Option<SocketAddr>,
+    metadata: Option<Value>,
 }
 
 impl JoinMessage {
@@ -111,8 +547,34 @@ impl JoinMessage {
         JoinMessage {
             user_name,
             signal_recipient,
+            close_recipient: None,
+            protocol: "other",
+            remote_addr: None,
+            metadata: None,
         }
     }
+
+    /// Where operators disconnecting the member are sent to.
+    pub(crate) fn close_recipient(mut self, close_recipient: Recipient<CloseMessage>) -> Self {
+        self.close_recipient = Some(close_recipient);
+        self
+    }
+
+    /// How the member is connected, e.g. `websocket` or `sip`.
+    pub fn protocol(mut self, protocol: &'static str) -> Self {
+        self.protocol = protocol;
+        self
+    }
+
+    pub fn remote_addr(mut self, remote_addr: Option<SocketAddr>) -> Self {
+        self.remote_addr = remote_addr;
+        self
+    }
+
+    pub fn metadata(mut self, metadata: Option<Value>) -> Self {
+        self.metadata = metadata;
+        self
+    }
 }
 
 impl Message for JoinMessage {
@@ -133,7 +595,9 @@ impl From<String> for ExitMessage {
 
 #[cfg(test)]
 mod test {
-    use super::{JoinMessage, Signal, SignalMessage, SignalRouter};
+    use super::{
+        Error, ExitMessage, JoinMessage, JoinRoomMessage, Signal, SignalMessage, SignalRouter,
+    };
     use actix::prelude::{Actor, Addr, Context, Handler, Message};
     use std::sync::{Arc, Mutex};
 
@@ -188,6 +652,218 @@ mod test {
         Ok(())
     }
 
+    #[actix_rt::test]
+    async fn test_naming_signal_after_sender() -> std::io::Result<()> {
+        //given
+        let testing_env = RouteTestingEnvironment::new().await;
+        let offer_signal: Signal = serde_json::from_str(
+            r#"{"type":"offer","name":"mallory","target":"callee","sdp":"dummy sdp"}"#,
+        )
+        .unwrap();
+
+        //when
+        testing_env
+            .router_addr
+            .send(SignalMessage::from(offer_signal).sender("caller".to_owned()))
+            .await
+            .unwrap()
+            .unwrap();
+
+        //then
+        assert_eq!(
+            testing_env
+                .last_received_message
+                .lock()
+                .unwrap()
+                .as_ref()
+                .and_then(Signal::name),
+            Some("caller")
+        );
+
+        Ok(())
+    }
+
+    #[actix_rt::test]
+    async fn test_rejecting_taken_user_name() -> std::io::Result<()> {
+        //given
+        let testing_env = RouteTestingEnvironment::new().await;
+        let impostor_addr = MockSignalHandler::new(Default::default()).start();
+
+        //when
+        let join_result = testing_env
+            .router_addr
+            .send(JoinMessage::new(
+                RouteTestingEnvironment::callee_name().to_owned(),
+                impostor_addr.recipient(),
+            ))
+            .await
+            .unwrap();
+
+        //then
+        assert!(join_result.is_err());
+
+        Ok(())
+    }
+
+    #[actix_rt::test]
+    async fn test_joining_room_and_leaving_it_on_exit() -> std::io::Result<()> {
+        //given
+        let testing_env = RouteTestingEnvironment::new().await;
+        let join_room = |user_name: &str| {
+            testing_env.router_addr.send(JoinRoomMessage::new(
+                user_name.to_owned(),
+                "lobby".to_owned(),
+            ))
+        };
+
+        //when
+        let first_members = join_room("caller").await.unwrap().unwrap();
+        let second_members = join_room("callee").await.unwrap().unwrap();
+        testing_env
+            .router_addr
+            .send(ExitMessage::from("caller".to_owned()))
+            .await
+            .unwrap()
+            .unwrap();
+        let members_after_exit = join_room("callee").await.unwrap().unwrap();
+
+        //then
+        assert_eq!(first_members, vec!["caller"]);
+        assert_eq!(second_members, vec!["callee", "caller"]);
+        assert_eq!(members_after_exit, vec!["callee"]);
+        assert!(matches!(
+            join_room("caller").await.unwrap(),
+            Err(Error::TargetNotFound(_))
+        ));
+
+        Ok(())
+    }
+
+    #[actix_rt::test]
+    async fn test_forbidding_signal_denied_by_policy() -> std::io::Result<()> {
+        //given
+        let signal_policy = serde_json::from_value(serde_json::json!({
+            "rules": [{ "effect": "deny", "sender": { "name": "caller" }, "signals": ["offer"] }],
+        }))
+        .unwrap();
+        let testing_env = RouteTestingEnvironment::with_router(SignalRouter::new(
+            Default::default(),
+            signal_policy,
+            Default::default(),
+        ))
+        .await;
+        let offer_signal: Signal = serde_json::from_str(
+            r#"{"type":"offer","name":"caller","target":"callee","sdp":"dummy sdp"}"#,
+        )
+        .unwrap();
+
+        //when
+        let signal_result = testing_env
+            .router_addr
+            .send(SignalMessage::from(offer_signal).sender("caller".to_owned()))
+            .await
+            .unwrap();
+
+        //then
+        assert!(matches!(signal_result, Err(Error::Forbidden(target)) if target == "callee"));
+        assert!(testing_env.last_received_message.lock().unwrap().is_none());
+
+        Ok(())
+    }
+
+    #[actix_rt::test]
+    async fn test_forbidding_signal_without_sender() -> std::io::Result<()> {
+        //given
+        let signal_policy = serde_json::from_value(serde_json::json!({
+            "rules": [{ "effect": "deny", "sender": { "name": "mallory" } }],
+        }))
+        .unwrap();
+        let testing_env = RouteTestingEnvironment::with_router(SignalRouter::new(
+            Default::default(),
+            signal_policy,
+            Default::default(),
+        ))
+        .await;
+        let offer_signal: Signal = serde_json::from_str(
+            r#"{"type":"offer","name":"caller","target":"callee","sdp":"dummy sdp"}"#,
+        )
+        .unwrap();
+
+        //when
+        let signal_result = testing_env
+            .router_addr
+            .send(SignalMessage::from(offer_signal))
+            .await
+            .unwrap();
+
+        //then
+        assert!(matches!(signal_result, Err(Error::Forbidden(target)) if target == "callee"));
+        assert!(testing_env.last_received_message.lock().unwrap().is_none());
+
+        Ok(())
+    }
+
+    #[actix_rt::test]
+    async fn test_forbidding_signal_to_unknown_target() -> std::io::Result<()> {
+        //given
+        let signal_policy = serde_json::from_value(serde_json::json!({
+            "rules": [{ "effect": "deny", "sender": { "name": "caller" } }],
+        }))
+        .unwrap();
+        let testing_env = RouteTestingEnvironment::with_router(SignalRouter::new(
+            Default::default(),
+            signal_policy,
+            Default::default(),
+        ))
+        .await;
+        let offer_signal: Signal = serde_json::from_str(
+            r#"{"type":"offer","name":"caller","target":"nobody","sdp":"dummy sdp"}"#,
+        )
+        .unwrap();
+
+        //when
+        let signal_result = testing_env
+            .router_addr
+            .send(SignalMessage::from(offer_signal).sender("caller".to_owned()))
+            .await
+            .unwrap();
+
+        //then
+        assert!(matches!(signal_result, Err(Error::Forbidden(target)) if target == "nobody"));
+
+        Ok(())
+    }
+
+    #[actix_rt::test]
+    async fn test_delivering_trickled_candidate_with_sdp_mid() -> std::io::Result<()> {
+        //given
+        let testing_env = RouteTestingEnvironment::new().await;
+        let fragment = "a=mid:0\r\na=candidate:1 1 udp 2122260223 192.0.2.1 61764 typ host\r\n";
+        let ice_candidate = crate::http_session::parse_sdp_fragment("callee", fragment)
+            .pop()
+            .unwrap();
+
+        //when
+        testing_env
+            .router_addr
+            .send(SignalMessage::translated(Signal::NewIceCandidate(
+                ice_candidate,
+            )))
+            .await
+            .unwrap()
+            .unwrap();
+
+        //then
+        match testing_env.last_received_message.lock().unwrap().as_ref() {
+            Some(Signal::NewIceCandidate(ice_candidate)) => {
+                assert_eq!(ice_candidate.extra["sdpMid"], "0")
+            }
+            other => panic!("expected a candidate, got {:?}", other),
+        }
+
+        Ok(())
+    }
+
     struct MockSignalHandler {
         last_received_message: Arc<Mutex<Option<Signal>>>,
     }
@@ -220,7 +896,11 @@ mod test {
 
     impl RouteTestingEnvironment {
         async fn new() -> Self {
-            let router_addr = SignalRouter::default().start();
+            Self::with_router(SignalRouter::default()).await
+        }
+
+        async fn with_router(router: SignalRouter) -> Self {
+            let router_addr = router.start();
             let message_placeholder: Arc<Mutex<Option<Signal>>> = Default::default();
             let caller_addr = MockSignalHandler::new(message_placeholder.clone()).start();
             let callee_addr = MockSignalHandler::new(message_placeholder.clone()).start();
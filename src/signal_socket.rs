@@ -1,39 +1,190 @@
+use actix::fut::{wrap_future, ActorFuture};
 use actix::prelude::{Actor, ActorContext, Addr, AsyncContext, Handler, StreamHandler};
 use actix_web_actors::ws;
 use futures::executor::block_on;
+use serde_json::Value;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tracing::{debug, field, info, info_span, trace, warn, Span};
 
+use super::frame_handler::{Action, FrameHandler};
+use super::signal::{Encoding, Frame};
+use super::signal_router::{CloseMessage, JoinRoomMessage};
+use super::telemetry;
 use super::{Error, ExitMessage, JoinMessage, Signal, SignalMessage, SignalRouter};
 
+static NEXT_CONNECTION_ID: AtomicU64 = AtomicU64::new(1);
+
 pub struct SignalSocket {
     user_name: String,
     signal_router: Addr<SignalRouter>,
+    frame_handler: FrameHandler,
+    remote_addr: Option<SocketAddr>,
+    metadata: Option<Value>,
+    log_signal_bodies: bool,
+    /// Whether the router took the name, so it has to be given back.
+    joined: bool,
+    span: Span,
 }
 
 impl SignalSocket {
-    pub fn new<T: ToString>(user_name: T, signal_router: &Addr<SignalRouter>) -> Self {
+    pub fn new<T: ToString>(
+        user_name: T,
+        signal_router: &Addr<SignalRouter>,
+        encoding: Encoding,
+    ) -> Self {
+        let user_name = user_name.to_string();
+        let span = info_span!(
+            "connection",
+            connection_id = NEXT_CONNECTION_ID.fetch_add(1, Ordering::Relaxed),
+            user_name = %user_name,
+            remote_addr = field::Empty,
+        );
         SignalSocket {
-            user_name: user_name.to_string(),
+            user_name,
             signal_router: signal_router.clone(),
+            frame_handler: FrameHandler::new(encoding),
+            remote_addr: None,
+            metadata: None,
+            log_signal_bodies: false,
+            joined: false,
+            span,
+        }
+    }
+
+    /// Address of the client, logged with every event of the connection.
+    pub fn remote_addr(mut self, remote_addr: Option<SocketAddr>) -> Self {
+        if let Some(remote_addr) = remote_addr {
+            self.span.record("remote_addr", field::display(remote_addr));
+        }
+        self.remote_addr = remote_addr;
+        self
+    }
+
+    /// Kept with the member in the router, e.g. as granted by the
+    /// admission hook.
+    pub fn metadata(mut self, metadata: Option<Value>) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// Logs session descriptions and candidates instead of redacting them.
+    pub fn log_signal_bodies(mut self, log_signal_bodies: bool) -> Self {
+        self.log_signal_bodies = log_signal_bodies;
+        self
+    }
+
+    fn send(frame: Frame, context: &mut ws::WebsocketContext<Self>) {
+        match frame {
+            Frame::Text(text) => context.text(text),
+            Frame::Binary(bytes) => context.binary(bytes),
+        }
+    }
+
+    /// Closes the connection after a reply couldn't be encoded, which
+    /// leaves the client waiting for an answer that never comes.
+    fn close_with_error(err: &Error, context: &mut ws::WebsocketContext<Self>) {
+        warn!(%err, "couldn't encode frame");
+        context.close(Some((ws::CloseCode::Error, "couldn't encode frame").into()));
+        context.stop();
+    }
+
+    fn handle_frame(&mut self, frame: &[u8], context: &mut ws::WebsocketContext<Self>) {
+        let action = match self.frame_handler.handle_frame(frame) {
+            Ok(action) => action,
+            Err(err) => return Self::close_with_error(&err, context),
+        };
+        match action {
+            Some(Action::Reply(frame)) => Self::send(frame, context),
+            Some(Action::Close(frame)) => {
+                if let Some(frame) = frame {
+                    Self::send(frame, context)
+                }
+                info!("closing connection of unsupported protocol version");
+                context.close(Some(
+                    (ws::CloseCode::Protocol, "unsupported protocol version").into(),
+                ));
+                context.stop();
+            }
+            Some(Action::Route(id, signal)) => self.handle_signal_message(id, signal, context),
+            Some(Action::JoinRoom(id, room)) => self.join_room(id, room, context),
+            None => {}
         }
     }
 
-    async fn handle_signal_message(
+    fn join_room(&self, id: Option<Value>, room: String, context: &mut ws::WebsocketContext<Self>) {
+        let joining_room_future = self
+            .signal_router
+            .send(JoinRoomMessage::new(self.user_name.clone(), room.clone()));
+        context.spawn(wrap_future(joining_room_future).map(
+            move |joining_room_result, socket: &mut Self, context| {
+                let result = joining_room_result.unwrap_or_else(into_service_releated_error);
+                match socket.frame_handler.joined_room(id, &room, result) {
+                    Ok(Some(frame)) => Self::send(frame, context),
+                    Ok(None) => {}
+                    Err(err) => Self::close_with_error(&err, context),
+                }
+            },
+        ));
+    }
+
+    fn handle_signal_message(
         &self,
-        signal_message: Signal,
+        id: Option<Value>,
+        mut signal_message: Signal,
         context: &mut ws::WebsocketContext<Self>,
     ) {
-        let signal_routing_result = self
-            .signal_router
-            .send(SignalMessage::from(signal_message))
-            .await
-            .unwrap_or_else(into_service_releated_error);
-        if let Err(err) = signal_routing_result {
-            context.text(&serde_json::to_string(&ErrorMessage::from(err)).unwrap())
+        let span = info_span!(
+            parent: &self.span,
+            "receive",
+            signal_type = signal_message.kind(),
+            target = signal_message.target().unwrap_or_default(),
+            body = field::Empty,
+            traceparent = field::Empty,
+            outcome = field::Empty,
+            error = field::Empty,
+        );
+        if let Some(body) = signal_message.body() {
+            span.record(
+                "body",
+                field::display(Body::new(body, self.log_signal_bodies)),
+            );
         }
+        telemetry::trace_through(&span, &mut signal_message);
+
+        // routing must not block this thread: the target may be an actor
+        // living on the same arbiter, e.g. an HTTP session
+        let signal_routing_future = self
+            .signal_router
+            .send(SignalMessage::from(signal_message).sender(self.user_name.clone()));
+        context.spawn(wrap_future(signal_routing_future).map(
+            move |signal_routing_result, socket: &mut Self, context| {
+                let result = signal_routing_result.unwrap_or_else(into_service_releated_error);
+                let _entered = span.enter();
+                match &result {
+                    Ok(()) => {
+                        span.record("outcome", "delivered");
+                        debug!("signal routed")
+                    }
+                    Err(err) => {
+                        span.record("outcome", field::display(err));
+                        span.record("error", field::display(err));
+                        info!("signal not routed")
+                    }
+                }
+                match socket.frame_handler.routed(id, result) {
+                    Ok(Some(frame)) => Self::send(frame, context),
+                    Ok(None) => {}
+                    Err(err) => Self::close_with_error(&err, context),
+                }
+            },
+        ));
     }
 }
 
-fn into_service_releated_error<T>(mailbox_error: actix::MailboxError) -> Result<T, Error> {
+pub(crate) fn into_service_releated_error<T>(
+    mailbox_error: actix::MailboxError,
+) -> Result<T, Error> {
     Err(match mailbox_error {
         actix::MailboxError::Closed => Error::ServiceUnavailable,
         actix::MailboxError::Timeout => Error::ServiceTimeout,
@@ -44,28 +195,51 @@ impl Actor for SignalSocket {
     type Context = ws::WebsocketContext<Self>;
 
     fn started(&mut self, context: &mut Self::Context) {
-        let joining_router_fut = self.signal_router.send(JoinMessage::new(
-            self.user_name.clone(),
-            context.address().recipient(),
-        ));
+        let _entered = self.span.enter();
+        let joining_router_fut = self.signal_router.send(
+            JoinMessage::new(self.user_name.clone(), context.address().recipient())
+                .protocol("websocket")
+                .remote_addr(self.remote_addr)
+                .metadata(self.metadata.take())
+                .close_recipient(context.address().recipient()),
+        );
 
-        if block_on(joining_router_fut).is_ok() {
-            context.text(&serde_json::to_string(&Signal::assign(self.user_name.clone())).unwrap());
-            println!("Signal Socket Opened")
-        } else {
-            context.stop();
+        match block_on(joining_router_fut) {
+            Ok(Ok(())) => self.joined = true,
+            Ok(Err(())) => {
+                warn!("user name is taken");
+                context.close(Some((ws::CloseCode::Policy, "user name is taken").into()));
+                context.stop();
+                return;
+            }
+            Err(_) => {
+                warn!("couldn't join router");
+                context.stop();
+                return;
+            }
         }
+
+        let assign = Signal::assign(self.user_name.clone());
+        match self.frame_handler.deliver(&assign) {
+            Ok(frame) => Self::send(frame, context),
+            Err(err) => return Self::close_with_error(&err, context),
+        }
+        info!("connection opened")
     }
 
     fn stopped(&mut self, _: &mut Self::Context) {
+        let _entered = self.span.enter();
+        if !self.joined {
+            return;
+        }
         let exiting_router_fut = self
             .signal_router
             .send(ExitMessage::from(self.user_name.clone()));
 
         if block_on(exiting_router_fut).is_ok() {
-            println!("Signal Socket Closed")
+            info!("connection closed")
         } else {
-            eprintln!("couldn't exit from router. user name: {}", self.user_name)
+            warn!("couldn't exit from router")
         }
     }
 }
@@ -76,22 +250,19 @@ impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for SignalSocket {
         message: Result<ws::Message, ws::ProtocolError>,
         context: &mut Self::Context,
     ) {
+        let span = self.span.clone();
+        let _entered = span.enter();
         match message {
-            Ok(ws::Message::Close(_)) => {
-                println!("close request received. closing.");
+            Ok(ws::Message::Close(reason)) => {
+                debug!(?reason, "close requested");
                 context.stop();
             }
             Ok(ws::Message::Text(text_message)) => {
-                if let Ok(signal) = serde_json::from_str(&text_message) {
-                    block_on(self.handle_signal_message(signal, context))
-                } else {
-                    context.text("couldn't parse your message")
-                }
+                self.handle_frame(text_message.as_bytes(), context)
             }
-            Ok(_) => {
-                println!("some message received.");
-            }
-            Err(error) => eprintln!("error occurred during receive message: {}", error),
+            Ok(ws::Message::Binary(binary_message)) => self.handle_frame(&binary_message, context),
+            Ok(message) => trace!(?message, "control frame"),
+            Err(error) => warn!(%error, "couldn't receive frame"),
         }
     }
 }
@@ -99,45 +270,67 @@ impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for SignalSocket {
 impl Handler<Signal> for SignalSocket {
     type Result = Result<(), Error>;
 
-    fn handle(&mut self, message: Signal, context: &mut Self::Context) -> Self::Result {
-        context.text(serde_json::to_string(&message)?);
+    fn handle(&mut self, mut message: Signal, context: &mut Self::Context) -> Self::Result {
+        let span = info_span!(
+            parent: &self.span,
+            "deliver",
+            signal_type = message.kind(),
+            traceparent = field::Empty,
+            error = field::Empty,
+        );
+        let _entered = span.enter();
+        telemetry::trace_through(&span, &mut message);
+        let frame = self.frame_handler.deliver(&message).inspect_err(|err| {
+            span.record("error", field::display(err));
+            info!("signal not delivered")
+        })?;
+        debug!("signal delivered");
+        Self::send(frame, context);
         Ok(())
     }
 }
 
-#[derive(serde::Serialize)]
-struct ErrorMessage {
-    r#type: &'static str,
-    message: String,
+impl Handler<CloseMessage> for SignalSocket {
+    type Result = ();
+
+    fn handle(&mut self, message: CloseMessage, context: &mut Self::Context) -> Self::Result {
+        let _entered = self.span.enter();
+        info!(reason = %message.reason, "connection closed by server");
+        context.close(Some((ws::CloseCode::Normal, message.reason).into()));
+        context.stop();
+    }
+}
+
+/// Session description or candidate as logged: replaced by its size unless
+/// bodies are logged explicitly, as they reveal peer addresses.
+struct Body<'a> {
+    text: &'a str,
+    revealed: bool,
 }
 
-impl From<Error> for ErrorMessage {
-    fn from(message_send_error: Error) -> Self {
-        match message_send_error {
-            Error::ParseError(parse_error) => ErrorMessage {
-                r#type: "parse error",
-                message: format!("{}", parse_error),
-            },
-            Error::ConnectionClosed => ErrorMessage {
-                r#type: "connection closed",
-                message: "target user's connection is closed".to_owned(),
-            },
-            Error::ConnectionTimeout => ErrorMessage {
-                r#type: "timeout",
-                message: "timeout occurres during send message to target user".to_owned(),
-            },
-            Error::TargetNotFound(target_user_name) => ErrorMessage {
-                r#type: "target user not found",
-                message: format!("user {} is not in connection", target_user_name),
-            },
-            Error::ServiceUnavailable => ErrorMessage {
-                r#type: "service unavailable",
-                message: "service is unavailable, please contact to service provider".to_owned(),
-            },
-            Error::ServiceTimeout => ErrorMessage {
-                r#type: "service timeout",
-                message: "service is busy. try after".to_owned(),
-            },
+impl<'a> Body<'a> {
+    fn new(text: &'a str, revealed: bool) -> Self {
+        Body { text, revealed }
+    }
+}
+
+impl std::fmt::Display for Body<'_> {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.revealed {
+            write!(formatter, "{}", self.text)
+        } else {
+            write!(formatter, "<redacted {} bytes>", self.text.len())
         }
     }
 }
+
+#[test]
+fn test_redacting_signal_body() {
+    let candidate = "candidate:1 1 udp 2122260223 192.0.2.1 50000 typ host";
+
+    assert_eq!(
+        Body::new(candidate, false).to_string(),
+        "<redacted 53 bytes>"
+    );
+    assert_eq!(Body::new(candidate, true).to_string(), candidate);
+}
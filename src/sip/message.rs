@@ -0,0 +1,275 @@
+use std::fmt;
+
+/// Compact header forms (RFC 3261 section 7.3.3) and their full names.
+const COMPACT_HEADERS: [(&str, &str); 7] = [
+    ("i", "Call-ID"),
+    ("m", "Contact"),
+    ("f", "From"),
+    ("t", "To"),
+    ("v", "Via"),
+    ("l", "Content-Length"),
+    ("c", "Content-Type"),
+];
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum StartLine {
+    Request { method: String, uri: String },
+    Response { status: u16, reason: String },
+}
+
+/// SIP request or response, limited to what the gateway needs: the start
+/// line, headers in their original order and a text body.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SipMessage {
+    pub start_line: StartLine,
+    headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl SipMessage {
+    pub fn request(method: &str, uri: String) -> Self {
+        SipMessage {
+            start_line: StartLine::Request {
+                method: method.to_owned(),
+                uri,
+            },
+            headers: Vec::new(),
+            body: String::new(),
+        }
+    }
+
+    /// Response to `request`, carrying over the headers that identify its
+    /// transaction.
+    pub fn response(request: &SipMessage, status: u16, reason: &str) -> Self {
+        let mut response = SipMessage {
+            start_line: StartLine::Response {
+                status,
+                reason: reason.to_owned(),
+            },
+            headers: Vec::new(),
+            body: String::new(),
+        };
+        for (name, value) in &request.headers {
+            if ["Via", "From", "To", "Call-ID", "CSeq"]
+                .iter()
+                .any(|header| name.eq_ignore_ascii_case(header))
+            {
+                response.headers.push((name.clone(), value.clone()));
+            }
+        }
+        response
+    }
+
+    pub fn parse(text: &str) -> Option<SipMessage> {
+        let (head, body) = match text.find("\r\n\r\n") {
+            Some(index) => (&text[..index], &text[index + 4..]),
+            None => match text.find("\n\n") {
+                Some(index) => (&text[..index], &text[index + 2..]),
+                None => (text.trim_end(), ""),
+            },
+        };
+        let mut lines = head.lines();
+        let start_line = parse_start_line(lines.next()?)?;
+
+        let mut headers: Vec<(String, String)> = Vec::new();
+        for line in lines {
+            if line.starts_with(' ') || line.starts_with('\t') {
+                // folded continuation of the previous header
+                let (_, value) = headers.last_mut()?;
+                value.push(' ');
+                value.push_str(line.trim());
+                continue;
+            }
+            let (name, value) = line.split_once(':')?;
+            headers.push((
+                canonical_name(name.trim()).to_owned(),
+                value.trim().to_owned(),
+            ));
+        }
+
+        let mut message = SipMessage {
+            start_line,
+            headers,
+            body: body.to_owned(),
+        };
+        if let Some(content_length) = message.header("Content-Length") {
+            let content_length = content_length.parse().ok()?;
+            message.body = body.get(..content_length)?.to_owned();
+        }
+        Some(message)
+    }
+
+    pub fn method(&self) -> Option<&str> {
+        match &self.start_line {
+            StartLine::Request { method, .. } => Some(method),
+            StartLine::Response { .. } => None,
+        }
+    }
+
+    pub fn uri(&self) -> Option<&str> {
+        match &self.start_line {
+            StartLine::Request { uri, .. } => Some(uri),
+            StartLine::Response { .. } => None,
+        }
+    }
+
+    pub fn status(&self) -> Option<u16> {
+        match &self.start_line {
+            StartLine::Request { .. } => None,
+            StartLine::Response { status, .. } => Some(*status),
+        }
+    }
+
+    /// First value of the header `name`, which may be given in compact form.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        let name = canonical_name(name);
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn call_id(&self) -> Option<&str> {
+        self.header("Call-ID")
+    }
+
+    /// Method of the transaction this message belongs to, from `CSeq`.
+    pub fn cseq_method(&self) -> Option<&str> {
+        self.header("CSeq")?.split_whitespace().nth(1)
+    }
+
+    pub fn with_header<T: Into<String>>(mut self, name: &str, value: T) -> Self {
+        self.headers.push((name.to_owned(), value.into()));
+        self
+    }
+
+    /// Replaces every value of the header `name` with `value`.
+    pub fn set_header<T: Into<String>>(mut self, name: &str, value: T) -> Self {
+        self.headers
+            .retain(|(header, _)| !header.eq_ignore_ascii_case(name));
+        self.with_header(name, value)
+    }
+
+    pub fn with_body(self, content_type: &str, body: String) -> Self {
+        let mut message = self.set_header("Content-Type", content_type);
+        message.body = body;
+        message
+    }
+}
+
+impl fmt::Display for SipMessage {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.start_line {
+            StartLine::Request { method, uri } => {
+                write!(formatter, "{} {} SIP/2.0\r\n", method, uri)?
+            }
+            StartLine::Response { status, reason } => {
+                write!(formatter, "SIP/2.0 {} {}\r\n", status, reason)?
+            }
+        }
+        for (name, value) in &self.headers {
+            if !name.eq_ignore_ascii_case("Content-Length") {
+                write!(formatter, "{}: {}\r\n", name, value)?;
+            }
+        }
+        write!(
+            formatter,
+            "Content-Length: {}\r\n\r\n{}",
+            self.body.len(),
+            self.body
+        )
+    }
+}
+
+fn parse_start_line(line: &str) -> Option<StartLine> {
+    let mut parts = line.splitn(3, ' ');
+    let first = parts.next()?;
+    let second = parts.next()?;
+    let third = parts.next()?;
+
+    if first == "SIP/2.0" {
+        Some(StartLine::Response {
+            status: second.parse().ok()?,
+            reason: third.to_owned(),
+        })
+    } else if third == "SIP/2.0" {
+        Some(StartLine::Request {
+            method: first.to_owned(),
+            uri: second.to_owned(),
+        })
+    } else {
+        None
+    }
+}
+
+fn canonical_name(name: &str) -> &str {
+    COMPACT_HEADERS
+        .iter()
+        .find(|(compact, _)| compact.eq_ignore_ascii_case(name))
+        .map(|(_, full)| *full)
+        .unwrap_or(name)
+}
+
+/// URI of a `From`/`To`/`Contact` value, e.g. `sip:alice@example.com` from
+/// `"Alice" <sip:alice@example.com>;tag=1928301774`.
+pub fn uri(value: &str) -> &str {
+    match (value.find('<'), value.find('>')) {
+        (Some(start), Some(end)) if start < end => &value[start + 1..end],
+        _ => value.split(';').next().unwrap_or(value).trim(),
+    }
+}
+
+/// User part of a SIP URI or of a header value containing one.
+pub fn uri_user(value: &str) -> Option<&str> {
+    let uri = uri(value);
+    let address = uri
+        .strip_prefix("sip:")
+        .or_else(|| uri.strip_prefix("sips:"))?;
+    let (user, _) = address.split_once('@')?;
+    Some(user.split(';').next().unwrap_or(user)).filter(|user| !user.is_empty())
+}
+
+/// `tag` parameter of a `From`/`To` value.
+pub fn tag(value: &str) -> Option<&str> {
+    let params = match value.find('>') {
+        Some(end) => &value[end + 1..],
+        None => value,
+    };
+    params
+        .split(';')
+        .filter_map(|param| param.trim().strip_prefix("tag="))
+        .next()
+}
+
+#[test]
+fn test_parsing_invite() {
+    let text = "INVITE sip:bob@example.com SIP/2.0\r\nVia: SIP/2.0/WSS df7jal23ls0d.invalid;branch=z9hG4bKhjhs8ass877\r\nf: \"Alice\" <sip:alice@example.com>;tag=1928301774\r\nTo: <sip:bob@example.com>\r\nCall-ID: a84b4c76e66710\r\nCSeq: 314159 INVITE\r\nContent-Type: application/sdp\r\nContent-Length: 4\r\n\r\nv=0\n";
+
+    let message = SipMessage::parse(text).unwrap();
+
+    assert_eq!(message.method(), Some("INVITE"));
+    assert_eq!(message.uri(), Some("sip:bob@example.com"));
+    assert_eq!(message.call_id(), Some("a84b4c76e66710"));
+    assert_eq!(message.cseq_method(), Some("INVITE"));
+    assert_eq!(message.body, "v=0\n");
+
+    let from = message.header("From").unwrap();
+    assert_eq!(uri_user(from), Some("alice"));
+    assert_eq!(tag(from), Some("1928301774"));
+    assert_eq!(tag(message.header("To").unwrap()), None);
+}
+
+#[test]
+fn test_serializing_response() {
+    let request = SipMessage::parse(
+        "BYE sip:alice@example.com SIP/2.0\r\nVia: SIP/2.0/WSS client.invalid;branch=z9hG4bK776asdhds\r\nMax-Forwards: 70\r\nFrom: <sip:bob@example.com>;tag=a6c85cf\r\nTo: <sip:alice@example.com>;tag=1928301774\r\nCall-ID: a84b4c76e66710\r\nCSeq: 231 BYE\r\nContent-Length: 0\r\n\r\n",
+    )
+    .unwrap();
+
+    let response = SipMessage::response(&request, 200, "OK");
+
+    assert_eq!(
+        response.to_string(),
+        "SIP/2.0 200 OK\r\nVia: SIP/2.0/WSS client.invalid;branch=z9hG4bK776asdhds\r\nFrom: <sip:bob@example.com>;tag=a6c85cf\r\nTo: <sip:alice@example.com>;tag=1928301774\r\nCall-ID: a84b4c76e66710\r\nCSeq: 231 BYE\r\nContent-Length: 0\r\n\r\n"
+    );
+}
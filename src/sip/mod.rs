@@ -0,0 +1,651 @@
+use actix::fut::{wrap_future, ActorFuture};
+use actix::prelude::{Actor, ActorContext, Addr, AsyncContext, Handler, StreamHandler};
+use actix_web::{web, HttpRequest, HttpResponse};
+use actix_web_actors::ws;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use uuid::Uuid;
+
+use super::admission::{AdmissionHook, AdmissionRequest};
+use super::signal::{Feature, HangupMessage, SessionDescriptionMessage};
+use super::signal_router::CloseMessage;
+use super::signal_socket::into_service_releated_error;
+use super::{
+    Error, ExitMessage, JoinMessage, Signal, SignalMessage, SignalRouter, SignalServerStateData,
+};
+use message::{tag, uri, uri_user, SipMessage};
+
+mod message;
+
+/// WebSocket subprotocol of SIP over WebSocket (RFC 7118).
+const SIP_PROTOCOL: &str = "sip";
+const SDP_CONTENT_TYPE: &str = "application/sdp";
+const ALLOWED_METHODS: &str = "REGISTER, INVITE, ACK, BYE, CANCEL, OPTIONS";
+const REGISTRATION_EXPIRES: u32 = 600;
+
+/// `GET /sip`: WebSocket speaking SIP, mounted when the gateway is enabled
+/// with `--sip-domain`.
+pub async fn socket(
+    state: SignalServerStateData,
+    request: HttpRequest,
+    stream: web::Payload,
+) -> Result<HttpResponse, actix_web::Error> {
+    let domain = match &state.adapters.sip_domain {
+        Some(domain) => domain.clone(),
+        None => return Ok(HttpResponse::NotFound().finish()),
+    };
+    if !state.origin_allowlist.is_allowed(&request) {
+        state.metrics.reject_origin();
+        return Ok(HttpResponse::Forbidden().body("origin is not allowed"));
+    }
+
+    let mut sip_socket = SipSocket::new(domain, &state.signal_router, request.peer_addr());
+    sip_socket.admission_hook = state.admission_hook.clone();
+    sip_socket.admission_request = Some(AdmissionRequest::new(&request));
+    ws::start_with_protocols(sip_socket, &[SIP_PROTOCOL], &request, stream)
+}
+
+/// Call between the SIP endpoint and a native peer, keyed by its Call-ID.
+/// `local` and `remote` are the `From` and `To` values of requests the
+/// gateway sends within the dialog.
+struct Dialog {
+    peer: String,
+    local: String,
+    remote: String,
+    remote_target: String,
+    cseq: u32,
+    /// Whether the initial INVITE was answered, so a failed re-INVITE
+    /// leaves the call up.
+    established: bool,
+    /// INVITE of the endpoint, until the peer answers or declines it.
+    pending_invite: Option<SipMessage>,
+    /// INVITE sent to the endpoint, until it gets a final response.
+    outgoing_invite: Option<SipMessage>,
+}
+
+/// Gateway translating SIP calls of a registered endpoint into `Signal`s,
+/// so SIP endpoints can call native clients and vice versa.
+pub struct SipSocket {
+    domain: String,
+    signal_router: Addr<SignalRouter>,
+    remote_addr: Option<SocketAddr>,
+    /// Asked whether the endpoint may register the user it asks for.
+    admission_hook: Option<AdmissionHook>,
+    admission_request: Option<AdmissionRequest>,
+    user_name: Option<String>,
+    dialogs: HashMap<String, Dialog>,
+}
+
+impl SipSocket {
+    fn new(
+        domain: String,
+        signal_router: &Addr<SignalRouter>,
+        remote_addr: Option<SocketAddr>,
+    ) -> Self {
+        SipSocket {
+            domain,
+            signal_router: signal_router.clone(),
+            remote_addr,
+            admission_hook: None,
+            admission_request: None,
+            user_name: None,
+            dialogs: HashMap::new(),
+        }
+    }
+
+    fn send(&self, message: SipMessage, context: &mut ws::WebsocketContext<Self>) {
+        context.text(message.to_string())
+    }
+
+    fn respond(
+        &self,
+        request: &SipMessage,
+        status: u16,
+        reason: &str,
+        context: &mut ws::WebsocketContext<Self>,
+    ) {
+        self.send(SipMessage::response(request, status, reason), context)
+    }
+
+    fn handle_message(&mut self, message: SipMessage, context: &mut ws::WebsocketContext<Self>) {
+        if message.call_id().is_none() || message.header("CSeq").is_none() {
+            if message.method().is_some() {
+                self.respond(&message, 400, "Bad Request", context);
+            }
+            return;
+        }
+
+        match message.method() {
+            Some("REGISTER") => self.handle_register(message, context),
+            Some("OPTIONS") => self.send(
+                SipMessage::response(&message, 200, "OK").with_header("Allow", ALLOWED_METHODS),
+                context,
+            ),
+            Some("ACK") => {}
+            Some(_) if self.user_name.is_none() => {
+                self.respond(&message, 403, "Forbidden", context)
+            }
+            Some("INVITE") => self.handle_invite(message, context),
+            Some("BYE") => self.handle_bye(message, context),
+            Some("CANCEL") => self.handle_cancel(message, context),
+            Some(_) => self.send(
+                SipMessage::response(&message, 501, "Not Implemented")
+                    .with_header("Allow", ALLOWED_METHODS),
+                context,
+            ),
+            None => self.handle_response(message, context),
+        }
+    }
+
+    /// Binds the user of the `To` address to this socket through the router
+    /// once the admission hook allowed it. Without a hook, no one may
+    /// register.
+    fn handle_register(&mut self, request: SipMessage, context: &mut ws::WebsocketContext<Self>) {
+        let user = match request.header("To").and_then(uri_user) {
+            Some(user) => user.to_owned(),
+            None => return self.respond(&request, 400, "Bad Request", context),
+        };
+        let unregister = request.header("Expires").map(str::trim) == Some("0")
+            || request
+                .header("Contact")
+                .map(|contact| contact.contains("expires=0"))
+                .unwrap_or(false);
+
+        match &self.user_name {
+            Some(user_name) if *user_name == user => {
+                if unregister {
+                    self.signal_router
+                        .do_send(ExitMessage::from(user_name.clone()));
+                    self.user_name = None;
+                }
+                return self.send(self.registered(&request, unregister), context);
+            }
+            Some(_) => return self.respond(&request, 403, "Forbidden", context),
+            None if unregister => return self.send(self.registered(&request, true), context),
+            None => {}
+        }
+
+        let (admission_hook, admission_request) =
+            match (&self.admission_hook, &self.admission_request) {
+                (Some(admission_hook), Some(admission_request)) => {
+                    (admission_hook.clone(), admission_request.clone())
+                }
+                _ => return self.respond(&request, 403, "Forbidden", context),
+            };
+        let requested_name = user.clone();
+        let admitting_future = async move {
+            admission_hook
+                .admit(&admission_request, Some(&requested_name))
+                .await
+        };
+        context.wait(wrap_future(admitting_future).map(
+            move |admission, socket: &mut Self, context| match admission {
+                Ok(admission)
+                    if admission.allow
+                        && admission.name.as_deref().is_none_or(|name| name == user) =>
+                {
+                    socket.join(user, admission.metadata, request, context)
+                }
+                Ok(_) => socket.respond(&request, 403, "Forbidden", context),
+                Err(_) => socket.respond(&request, 503, "Service Unavailable", context),
+            },
+        ));
+    }
+
+    fn join(
+        &mut self,
+        user: String,
+        metadata: Option<serde_json::Value>,
+        request: SipMessage,
+        context: &mut ws::WebsocketContext<Self>,
+    ) {
+        let joining_router_future = self.signal_router.send(
+            JoinMessage::new(user.clone(), context.address().recipient())
+                .protocol("sip")
+                .remote_addr(self.remote_addr)
+                .metadata(metadata)
+                .close_recipient(context.address().recipient()),
+        );
+        context.wait(wrap_future(joining_router_future).map(
+            move |join_result, socket: &mut Self, context| match join_result {
+                Ok(Ok(())) => {
+                    socket.user_name = Some(user);
+                    socket.send(socket.registered(&request, false), context);
+                }
+                Ok(Err(())) => socket.respond(&request, 403, "Forbidden", context),
+                Err(_) => socket.respond(&request, 503, "Service Unavailable", context),
+            },
+        ));
+    }
+
+    fn registered(&self, request: &SipMessage, unregister: bool) -> SipMessage {
+        let mut response = SipMessage::response(request, 200, "OK");
+        if let Some(contact) = request.header("Contact") {
+            if !unregister {
+                response = response.with_header(
+                    "Contact",
+                    format!("{};expires={}", contact, REGISTRATION_EXPIRES),
+                );
+            }
+        }
+        response
+    }
+
+    fn handle_invite(&mut self, invite: SipMessage, context: &mut ws::WebsocketContext<Self>) {
+        let callee = match invite.uri().and_then(uri_user) {
+            Some(callee) => callee.to_owned(),
+            None => return self.respond(&invite, 404, "Not Found", context),
+        };
+        if invite.body.is_empty() {
+            // offers in the ACK are not supported
+            return self.respond(&invite, 488, "Not Acceptable Here", context);
+        }
+        let call_id = invite.call_id().unwrap_or_default().to_owned();
+        if self.dialogs.contains_key(&call_id) {
+            return self.handle_reinvite(invite, context);
+        }
+        self.respond(&invite, 100, "Trying", context);
+
+        let user_name = self.user_name.clone().unwrap_or_default();
+        let to = invite.header("To").unwrap_or_default();
+        let from = invite.header("From").unwrap_or_default();
+        let local = match tag(to) {
+            Some(_) => to.to_owned(),
+            None => format!("{};tag={}", to, new_tag()),
+        };
+        let remote_target = uri(invite.header("Contact").unwrap_or(from)).to_owned();
+        let offer = Signal::Offer(SessionDescriptionMessage::new(
+            callee.clone(),
+            user_name,
+            invite.body.clone(),
+        ));
+
+        self.dialogs.insert(
+            call_id.clone(),
+            Dialog {
+                peer: callee,
+                local,
+                remote: from.to_owned(),
+                remote_target,
+                cseq: 0,
+                established: false,
+                pending_invite: Some(invite),
+                outgoing_invite: None,
+            },
+        );
+        self.route(offer, Some(call_id), context);
+    }
+
+    /// Relays the new offer of an INVITE within a call to the peer, whose
+    /// answer completes it like the initial one.
+    fn handle_reinvite(&mut self, invite: SipMessage, context: &mut ws::WebsocketContext<Self>) {
+        let call_id = invite.call_id().unwrap_or_default().to_owned();
+        let dialog = self.dialogs.get_mut(&call_id).unwrap();
+        if dialog.pending_invite.is_some() || dialog.outgoing_invite.is_some() {
+            return self.respond(&invite, 491, "Request Pending", context);
+        }
+
+        let offer = Signal::Offer(SessionDescriptionMessage::new(
+            dialog.peer.clone(),
+            self.user_name.clone().unwrap_or_default(),
+            invite.body.clone(),
+        ));
+        dialog.pending_invite = Some(invite.clone());
+        self.respond(&invite, 100, "Trying", context);
+        self.route(offer, Some(call_id), context);
+    }
+
+    fn handle_bye(&mut self, request: SipMessage, context: &mut ws::WebsocketContext<Self>) {
+        match self.dialogs.remove(request.call_id().unwrap_or_default()) {
+            Some(dialog) => {
+                self.respond(&request, 200, "OK", context);
+                self.hang_up(dialog.peer, context);
+            }
+            None => self.respond(&request, 481, "Call/Transaction Does Not Exist", context),
+        }
+    }
+
+    fn handle_cancel(&mut self, request: SipMessage, context: &mut ws::WebsocketContext<Self>) {
+        let call_id = request.call_id().unwrap_or_default();
+        let pending = self
+            .dialogs
+            .get(call_id)
+            .map(|dialog| dialog.pending_invite.is_some())
+            .unwrap_or(false);
+        if !pending {
+            return self.respond(&request, 481, "Call/Transaction Does Not Exist", context);
+        }
+
+        self.respond(&request, 200, "OK", context);
+        let dialog = self.dialogs.get_mut(call_id).unwrap();
+        let invite = dialog.pending_invite.take().unwrap();
+        let response = SipMessage::response(&invite, 487, "Request Terminated")
+            .set_header("To", dialog.local.clone());
+        self.send(response, context);
+        // cancelling a re-INVITE leaves the call up
+        if !self.dialogs[call_id].established {
+            let dialog = self.dialogs.remove(call_id).unwrap();
+            self.hang_up(dialog.peer, context);
+        }
+    }
+
+    /// Handles the endpoint's responses to the INVITEs the gateway sent it.
+    fn handle_response(&mut self, response: SipMessage, context: &mut ws::WebsocketContext<Self>) {
+        let status = response.status().unwrap_or_default();
+        if response.cseq_method() != Some("INVITE") || status < 200 {
+            return;
+        }
+        let call_id = response.call_id().unwrap_or_default().to_owned();
+        let invite = match self
+            .dialogs
+            .get_mut(&call_id)
+            .and_then(|dialog| dialog.outgoing_invite.take())
+        {
+            Some(invite) => invite,
+            None => return,
+        };
+        let dialog = self.dialogs.get_mut(&call_id).unwrap();
+
+        if status < 300 {
+            dialog.established = true;
+            if let Some(to) = response.header("To") {
+                dialog.remote = to.to_owned();
+            }
+            if let Some(contact) = response.header("Contact") {
+                dialog.remote_target = uri(contact).to_owned();
+            }
+            let dialog = &self.dialogs[&call_id];
+            let ack = self.in_dialog_request(&call_id, dialog, "ACK", dialog.cseq);
+            let answer = Signal::Answer(SessionDescriptionMessage::new(
+                dialog.peer.clone(),
+                self.user_name.clone().unwrap_or_default(),
+                response.body,
+            ));
+            self.send(ack, context);
+            self.route(answer, None, context);
+        } else {
+            // non-2xx responses are acknowledged within their transaction
+            let ack = SipMessage::request("ACK", invite.uri().unwrap_or_default().to_owned())
+                .with_header("Via", invite.header("Via").unwrap_or_default())
+                .with_header("Max-Forwards", "70")
+                .with_header("From", dialog.local.clone())
+                .with_header("To", response.header("To").unwrap_or_default())
+                .with_header("Call-ID", call_id.clone())
+                .with_header("CSeq", format!("{} ACK", dialog.cseq));
+            let established = dialog.established;
+            self.send(ack, context);
+            // a refused re-INVITE leaves the call up
+            if !established {
+                let dialog = self.dialogs.remove(&call_id).unwrap();
+                self.hang_up(dialog.peer, context);
+            }
+        }
+    }
+
+    fn in_dialog_request(
+        &self,
+        call_id: &str,
+        dialog: &Dialog,
+        method: &str,
+        cseq: u32,
+    ) -> SipMessage {
+        SipMessage::request(method, dialog.remote_target.clone())
+            .with_header("Via", self.via())
+            .with_header("Max-Forwards", "70")
+            .with_header("From", dialog.local.clone())
+            .with_header("To", dialog.remote.clone())
+            .with_header("Call-ID", call_id.to_owned())
+            .with_header("CSeq", format!("{} {}", cseq, method))
+    }
+
+    fn via(&self) -> String {
+        format!(
+            "SIP/2.0/WSS {};branch=z9hG4bK{}",
+            self.domain,
+            Uuid::new_v4().to_simple()
+        )
+    }
+
+    fn hang_up(&self, peer: String, context: &mut ws::WebsocketContext<Self>) {
+        let hangup = Signal::Hangup(HangupMessage::new(
+            peer,
+            self.user_name.clone().unwrap_or_default(),
+        ));
+        self.route(hangup, None, context)
+    }
+
+    /// Routes `signal`, failing the endpoint's INVITE of `call_id` if the
+    /// peer can't be reached.
+    fn route(
+        &self,
+        signal: Signal,
+        call_id: Option<String>,
+        context: &mut ws::WebsocketContext<Self>,
+    ) {
+        let mut signal_message = SignalMessage::translated(signal);
+        if let Some(user_name) = &self.user_name {
+            signal_message = signal_message.sender(user_name.clone());
+        }
+        let signal_routing_future = self.signal_router.send(signal_message);
+        context.spawn(wrap_future(signal_routing_future).map(
+            move |signal_routing_result, socket: &mut Self, context| {
+                let err = match signal_routing_result.unwrap_or_else(into_service_releated_error) {
+                    Ok(()) => return,
+                    Err(err) => err,
+                };
+                let invite = call_id.and_then(|call_id| {
+                    let dialog = socket.dialogs.get_mut(&call_id)?;
+                    let invite = dialog.pending_invite.take();
+                    if !dialog.established {
+                        socket.dialogs.remove(&call_id);
+                    }
+                    invite
+                });
+                if let Some(invite) = invite {
+                    let (status, reason) = match err {
+                        Error::TargetNotFound(_) => (404, "Not Found"),
+                        Error::Forbidden(_) => (403, "Forbidden"),
+                        Error::RequestPending(_) => (491, "Request Pending"),
+                        Error::ConnectionClosed | Error::ConnectionTimeout => {
+                            (480, "Temporarily Unavailable")
+                        }
+                        _ => (500, "Server Internal Error"),
+                    };
+                    socket.respond(&invite, status, reason, context);
+                }
+            },
+        ));
+    }
+
+    /// Sends an INVITE carrying the offer of a native peer, within the call
+    /// with the peer if there is one.
+    fn invite(
+        &mut self,
+        offer: SessionDescriptionMessage,
+        context: &mut ws::WebsocketContext<Self>,
+    ) -> Result<(), Error> {
+        if let Some(call_id) = self.call_with(&offer.name) {
+            return self.reinvite(call_id, offer, context);
+        }
+        let user_name = self.user_name.clone().unwrap_or_default();
+        let call_id = Uuid::new_v4().to_simple().to_string();
+        let remote_target = format!("sip:{}@{}", user_name, self.domain);
+        self.dialogs.insert(
+            call_id.clone(),
+            Dialog {
+                peer: offer.name.clone(),
+                local: format!("<sip:{}@{}>;tag={}", offer.name, self.domain, new_tag()),
+                remote: format!("<{}>", remote_target),
+                remote_target,
+                cseq: 1,
+                established: false,
+                pending_invite: None,
+                outgoing_invite: None,
+            },
+        );
+
+        let invite = self
+            .in_dialog_request(&call_id, &self.dialogs[&call_id], "INVITE", 1)
+            .with_header(
+                "Contact",
+                format!("<sip:{}@{};transport=ws>", offer.name, self.domain),
+            )
+            .with_body(SDP_CONTENT_TYPE, offer.sdp().to_owned());
+        self.dialogs.get_mut(&call_id).unwrap().outgoing_invite = Some(invite.clone());
+        self.send(invite, context);
+        Ok(())
+    }
+
+    /// Sends the new offer of a peer already in a call as an INVITE within
+    /// it, unless either side is still negotiating the call.
+    fn reinvite(
+        &mut self,
+        call_id: String,
+        offer: SessionDescriptionMessage,
+        context: &mut ws::WebsocketContext<Self>,
+    ) -> Result<(), Error> {
+        let dialog = self.dialogs.get_mut(&call_id).unwrap();
+        if !dialog.established
+            || dialog.pending_invite.is_some()
+            || dialog.outgoing_invite.is_some()
+        {
+            return Err(Error::RequestPending(
+                self.user_name.clone().unwrap_or_default(),
+            ));
+        }
+        dialog.cseq += 1;
+
+        let dialog = &self.dialogs[&call_id];
+        let invite = self
+            .in_dialog_request(&call_id, dialog, "INVITE", dialog.cseq)
+            .with_header(
+                "Contact",
+                format!("<sip:{}@{};transport=ws>", offer.name, self.domain),
+            )
+            .with_body(SDP_CONTENT_TYPE, offer.sdp().to_owned());
+        self.dialogs.get_mut(&call_id).unwrap().outgoing_invite = Some(invite.clone());
+        self.send(invite, context);
+        Ok(())
+    }
+
+    /// Call-ID of the call with `peer`, if there is one.
+    fn call_with(&self, peer: &str) -> Option<String> {
+        self.dialogs
+            .iter()
+            .find(|(_, dialog)| dialog.peer == peer)
+            .map(|(call_id, _)| call_id.clone())
+    }
+
+    /// Answers the pending INVITE the endpoint sent to `answer.name`, which
+    /// the router sets to the member that sent the answer.
+    fn answer(
+        &mut self,
+        answer: SessionDescriptionMessage,
+        context: &mut ws::WebsocketContext<Self>,
+    ) {
+        let dialog = self
+            .dialogs
+            .values_mut()
+            .find(|dialog| dialog.peer == answer.name && dialog.pending_invite.is_some());
+        if let Some(dialog) = dialog {
+            dialog.established = true;
+            let invite = dialog.pending_invite.take().unwrap();
+            let response = SipMessage::response(&invite, 200, "OK")
+                .set_header("To", dialog.local.clone())
+                .with_header(
+                    "Contact",
+                    format!("<sip:{}@{};transport=ws>", answer.name, self.domain),
+                )
+                .with_body(SDP_CONTENT_TYPE, answer.sdp().to_owned());
+            self.send(response, context);
+        }
+    }
+
+    /// Ends the call with `peer`, declining it if it wasn't answered yet.
+    fn end_call(&mut self, peer: &str, context: &mut ws::WebsocketContext<Self>) {
+        let call_id = match self.call_with(peer) {
+            Some(call_id) => call_id,
+            None => return,
+        };
+        let mut dialog = self.dialogs.remove(&call_id).unwrap();
+
+        if let Some(invite) = dialog.pending_invite.take() {
+            let response = SipMessage::response(&invite, 603, "Decline")
+                .set_header("To", dialog.local.clone());
+            self.send(response, context);
+        }
+        if dialog.established {
+            let bye = self.in_dialog_request(&call_id, &dialog, "BYE", dialog.cseq + 1);
+            self.send(bye, context);
+        } else if let Some(invite) = dialog.outgoing_invite.take() {
+            let cancel = SipMessage::request("CANCEL", invite.uri().unwrap_or_default().to_owned())
+                .with_header("Via", invite.header("Via").unwrap_or_default())
+                .with_header("Max-Forwards", "70")
+                .with_header("From", dialog.local.clone())
+                .with_header("To", dialog.remote.clone())
+                .with_header("Call-ID", call_id)
+                .with_header("CSeq", format!("{} CANCEL", dialog.cseq));
+            self.send(cancel, context);
+        }
+    }
+}
+
+fn new_tag() -> String {
+    Uuid::new_v4().to_simple().to_string()[..10].to_owned()
+}
+
+impl Actor for SipSocket {
+    type Context = ws::WebsocketContext<Self>;
+
+    fn stopped(&mut self, _: &mut Self::Context) {
+        if let Some(user_name) = self.user_name.take() {
+            self.signal_router.do_send(ExitMessage::from(user_name));
+        }
+    }
+}
+
+impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for SipSocket {
+    fn handle(
+        &mut self,
+        message: Result<ws::Message, ws::ProtocolError>,
+        context: &mut Self::Context,
+    ) {
+        match message {
+            Ok(ws::Message::Text(text_message)) => {
+                if let Some(message) = SipMessage::parse(&text_message) {
+                    self.handle_message(message, context)
+                }
+            }
+            Ok(ws::Message::Ping(message)) => context.pong(&message),
+            Ok(ws::Message::Close(_)) | Err(_) => context.stop(),
+            Ok(_) => {}
+        }
+    }
+}
+
+impl Handler<Signal> for SipSocket {
+    type Result = Result<(), Error>;
+
+    fn handle(&mut self, message: Signal, context: &mut Self::Context) -> Self::Result {
+        match message {
+            Signal::Offer(offer) => return self.invite(offer, context),
+            Signal::Answer(answer) => self.answer(answer, context),
+            // only the peer in the call can end it, as the router names the
+            // member that sent the hangup
+            Signal::Hangup(hangup_message) => self.end_call(&hangup_message.name, context),
+            Signal::Notice(_) => return Err(Error::FeatureNotNegotiated(Feature::Notice)),
+            // SIP endpoints gather their candidates into the SDP
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+impl Handler<CloseMessage> for SipSocket {
+    type Result = ();
+
+    fn handle(&mut self, message: CloseMessage, context: &mut Self::Context) -> Self::Result {
+        context.close(Some((ws::CloseCode::Normal, message.reason).into()));
+        context.stop();
+    }
+}
@@ -0,0 +1,283 @@
+use actix::prelude::{Actor, ActorContext, Addr, AsyncContext, Context, Handler};
+use actix_web::web::Bytes;
+use actix_web::{web, HttpRequest, HttpResponse};
+use futures::channel::mpsc;
+use futures::StreamExt;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use uuid::Uuid;
+
+use super::admin::bearer_token;
+use super::http_session::JoinedMessage;
+use super::signal::{Feature, Limits, PROTOCOL_VERSION};
+use super::signal_router::CloseMessage;
+use super::signal_socket::into_service_releated_error;
+use super::{
+    admission_name, admit, Error, ExitMessage, JoinMessage, Signal, SignalMessage, SignalQuery,
+    SignalRouter, SignalServerStateData,
+};
+
+/// Interval of comments written to idle streams, keeping proxies from
+/// timing them out.
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(15);
+/// How fast a stream the client went away from is noticed.
+const CLOSED_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+/// Events written ahead of a client reading its stream slowly. A session
+/// whose client falls further behind is closed.
+const EVENT_BUFFER: usize = 64;
+
+/// Peer connected through Server-Sent Events instead of a WebSocket. Signals
+/// routed to it are written to the event stream, signals it sends arrive
+/// as `POST /signal/send` requests bearing its session token.
+pub struct SseSession {
+    user_name: String,
+    token: String,
+    signal_router: Addr<SignalRouter>,
+    sse_sessions: Arc<SseSessions>,
+    event_sender: mpsc::Sender<Bytes>,
+    /// Whether the router took the name, so it has to be given back.
+    joined: bool,
+}
+
+impl SseSession {
+    fn write_event(&mut self, event: String, context: &mut Context<Self>) -> Result<(), Error> {
+        self.event_sender.try_send(Bytes::from(event)).map_err(|_| {
+            context.stop();
+            Error::ConnectionClosed
+        })
+    }
+}
+
+impl Actor for SseSession {
+    type Context = Context<Self>;
+
+    fn started(&mut self, context: &mut Self::Context) {
+        context.run_interval(KEEPALIVE_INTERVAL, |session, context| {
+            let _ = session.write_event(": keepalive\n\n".to_owned(), context);
+        });
+        context.run_interval(CLOSED_CHECK_INTERVAL, |session, context| {
+            if session.event_sender.is_closed() {
+                context.stop();
+            }
+        });
+    }
+
+    fn stopped(&mut self, _: &mut Self::Context) {
+        self.sse_sessions.remove(&self.token);
+        if self.joined {
+            self.signal_router
+                .do_send(ExitMessage::from(self.user_name.clone()));
+        }
+    }
+}
+
+impl Handler<JoinedMessage> for SseSession {
+    type Result = ();
+
+    fn handle(&mut self, _: JoinedMessage, _: &mut Self::Context) -> Self::Result {
+        self.joined = true;
+    }
+}
+
+impl Handler<Signal> for SseSession {
+    type Result = Result<(), Error>;
+
+    fn handle(&mut self, message: Signal, context: &mut Self::Context) -> Self::Result {
+        self.sse_sessions.check_feature(&self.token, &message)?;
+        let event = format!("data: {}\n\n", serde_json::to_string(&message)?);
+        self.write_event(event, context)
+    }
+}
+
+impl Handler<CloseMessage> for SseSession {
+    type Result = ();
+
+    fn handle(&mut self, message: CloseMessage, context: &mut Self::Context) -> Self::Result {
+        let event = format!(
+            "event: close\ndata: {}\n\n",
+            serde_json::json!({ "reason": message.reason })
+        );
+        let _ = self.write_event(event, context);
+        context.stop();
+    }
+}
+
+struct SessionEntry {
+    user_name: String,
+    /// Negotiated by a `hello` sent to `/signal/send`.
+    features: Vec<Feature>,
+}
+
+/// Live SSE sessions, addressed by their session token.
+#[derive(Default)]
+pub struct SseSessions {
+    sessions: Mutex<HashMap<String, SessionEntry>>,
+}
+
+impl SseSessions {
+    fn insert(&self, token: String, user_name: String) {
+        let entry = SessionEntry {
+            user_name,
+            features: Vec::new(),
+        };
+        self.sessions.lock().unwrap().insert(token, entry);
+    }
+
+    fn remove(&self, token: &str) {
+        self.sessions.lock().unwrap().remove(token);
+    }
+
+    fn user_name(&self, token: &str) -> Option<String> {
+        let sessions = self.sessions.lock().unwrap();
+        sessions.get(token).map(|entry| entry.user_name.clone())
+    }
+
+    fn set_features(&self, token: &str, features: Vec<Feature>) {
+        if let Some(entry) = self.sessions.lock().unwrap().get_mut(token) {
+            entry.features = features;
+        }
+    }
+
+    /// Fails if the session owning `token` didn't negotiate the feature
+    /// `signal` needs.
+    fn check_feature(&self, token: &str, signal: &Signal) -> Result<(), Error> {
+        let sessions = self.sessions.lock().unwrap();
+        let features = sessions.get(token).map(|entry| &entry.features);
+        match signal.required_feature() {
+            Some(feature) if !features.is_some_and(|features| features.contains(&feature)) => {
+                Err(Error::FeatureNotNegotiated(feature))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// `GET /signal/events`: joins the router and streams the signals addressed
+/// to the new user. The first event carries the token to send with.
+pub async fn events(
+    state: SignalServerStateData,
+    request: HttpRequest,
+    query: web::Query<SignalQuery>,
+) -> Result<HttpResponse, Error> {
+    if !state.origin_allowlist.is_allowed(&request) {
+        state.metrics.reject_origin();
+        return Ok(HttpResponse::Forbidden().body("origin is not allowed"));
+    }
+    let admission = match admit(&state, &request, query.name.as_deref()).await {
+        Ok(admission) => admission,
+        Err(response) => return Ok(response),
+    };
+    let user_name = admission_name(&admission);
+    let token = Uuid::new_v4().to_simple().to_string();
+    let (mut event_sender, event_receiver) = mpsc::channel(EVENT_BUFFER);
+
+    let session_addr = SseSession {
+        user_name: user_name.clone(),
+        token: token.clone(),
+        signal_router: state.signal_router.clone(),
+        sse_sessions: state.sse_sessions.clone(),
+        event_sender: event_sender.clone(),
+        joined: false,
+    }
+    .start();
+    let join_result = state
+        .signal_router
+        .send(
+            JoinMessage::new(user_name.clone(), session_addr.clone().recipient())
+                .protocol("sse")
+                .remote_addr(request.peer_addr())
+                .metadata(admission.metadata)
+                .close_recipient(session_addr.clone().recipient()),
+        )
+        .await
+        .map_err(|_| Error::ServiceUnavailable)?;
+    if join_result.is_err() {
+        session_addr.do_send(CloseMessage::new("join failed".to_owned()));
+        return Ok(HttpResponse::Conflict().body("user name is taken"));
+    }
+    session_addr.do_send(JoinedMessage);
+    state.sse_sessions.insert(token.clone(), user_name.clone());
+
+    let session_event = format!(
+        "event: session\ndata: {}\n\n",
+        serde_json::json!({ "token": token })
+    );
+    let assign_event = format!(
+        "data: {}\n\n",
+        serde_json::to_string(&Signal::assign(user_name))?
+    );
+    let _ = event_sender.try_send(Bytes::from(session_event));
+    let _ = event_sender.try_send(Bytes::from(assign_event));
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .header("Cache-Control", "no-cache")
+        .streaming(event_receiver.map(Ok::<_, actix_web::Error>)))
+}
+
+/// `POST /signal/send`: routes the signal in the body on behalf of the SSE
+/// session whose token the request bears, e.g. `Authorization: Bearer
+/// <token>`. Tokens stay out of URLs, which end up in access logs.
+pub async fn send(
+    state: SignalServerStateData,
+    request: HttpRequest,
+    body: Bytes,
+) -> Result<HttpResponse, Error> {
+    let token = bearer_token(&request).ok_or(Error::Unauthorized)?;
+    let user_name = state
+        .sse_sessions
+        .user_name(token)
+        .ok_or(Error::Unauthorized)?;
+
+    match serde_json::from_slice(&body)? {
+        Signal::Hello(hello) => {
+            if hello.version != PROTOCOL_VERSION {
+                return Err(Error::UnsupportedVersion(hello.version));
+            }
+            let server_hello = hello.negotiate(Limits::default());
+            state
+                .sse_sessions
+                .set_features(token, server_hello.features.clone());
+            Ok(HttpResponse::Ok().json(Signal::Hello(server_hello)))
+        }
+        signal if signal.is_server_signal() => Err(Error::ServerSignal(signal.kind())),
+        signal => {
+            state.sse_sessions.check_feature(token, &signal)?;
+            state
+                .signal_router
+                .send(SignalMessage::from(signal).sender(user_name))
+                .await
+                .unwrap_or_else(into_service_releated_error)?;
+            Ok(HttpResponse::Accepted().finish())
+        }
+    }
+}
+
+#[actix_rt::test]
+async fn test_closing_session_of_stalled_client() {
+    //given
+    let (event_sender, _event_receiver) = mpsc::channel(EVENT_BUFFER);
+    let session_addr = SseSession {
+        user_name: "callee".to_owned(),
+        token: "token".to_owned(),
+        signal_router: SignalRouter::default().start(),
+        sse_sessions: Arc::default(),
+        event_sender,
+        joined: false,
+    }
+    .start();
+
+    //when
+    let mut written_events = 0;
+    let result = loop {
+        match session_addr.send(Signal::assign("callee".to_owned())).await {
+            Ok(Ok(())) if written_events <= EVENT_BUFFER => written_events += 1,
+            result => break result,
+        }
+    };
+
+    //then
+    assert_eq!(written_events, EVENT_BUFFER + 1);
+    assert!(matches!(result, Ok(Err(Error::ConnectionClosed))));
+}
@@ -0,0 +1,356 @@
+//! OpenTelemetry export of the spans a signal passes through on its way
+//! from one peer to another: `receive` in the sending socket, `route` in
+//! the router and `deliver` in the target socket.
+//!
+//! The spans are ordinary `tracing` spans declaring a `traceparent` field.
+//! `OtlpLayer` gives each of them, and every span below them, a W3C trace
+//! context: the one recorded in `traceparent` as remote parent, the one of
+//! the parent span, or a new trace. Closed spans are sent to an OTLP/HTTP
+//! collector in JSON batches.
+
+use serde_json::{json, Value};
+use std::sync::mpsc::{sync_channel, Receiver, RecvTimeoutError, SyncSender};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::field::{self, Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{warn, Span, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::{LookupSpan, Registry};
+use uuid::Uuid;
+
+use super::signal::trace_parent::encode_hex;
+use super::signal::TraceParent;
+use super::Signal;
+
+const TRACEPARENT: &str = "traceparent";
+const ERROR: &str = "error";
+const MAX_BATCH_SIZE: usize = 512;
+
+const SPAN_KIND_INTERNAL: u8 = 1;
+const STATUS_CODE_ERROR: u8 = 2;
+
+/// Trace context of the span, if an `OtlpLayer` traces it.
+pub(crate) fn trace_parent(span: &Span) -> Option<TraceParent> {
+    span.with_subscriber(|(id, dispatch)| {
+        let registry = dispatch.downcast_ref::<Registry>()?;
+        let span = registry.span(id)?;
+        let extensions = span.extensions();
+        let context = extensions.get::<SpanContext>()?;
+        Some(context.trace_parent())
+    })
+    .flatten()
+}
+
+/// Continues the trace of a signal in `span`: records the trace context the
+/// signal came with and hands the span's own on with it.
+pub(crate) fn trace_through(span: &Span, signal: &mut Signal) {
+    if let Some(traceparent) = signal.traceparent() {
+        span.record(TRACEPARENT, field::display(traceparent));
+    }
+    if let Some(traceparent) = trace_parent(span) {
+        signal.set_traceparent(traceparent);
+    }
+}
+
+/// `tracing` layer exporting spans to an OTLP/HTTP collector.
+pub struct OtlpLayer {
+    sender: SyncSender<SpanData>,
+}
+
+impl OtlpLayer {
+    /// Exports to the collector at `endpoint`, e.g. `http://localhost:4318`.
+    pub fn builder<T: Into<String>>(endpoint: T) -> OtlpLayerBuilder {
+        OtlpLayerBuilder {
+            endpoint: endpoint.into(),
+            service_name: "signalling-server".to_owned(),
+            flush_interval: Duration::from_secs(1),
+            queue_size: 4096,
+        }
+    }
+}
+
+pub struct OtlpLayerBuilder {
+    endpoint: String,
+    service_name: String,
+    flush_interval: Duration,
+    queue_size: usize,
+}
+
+impl OtlpLayerBuilder {
+    /// `service.name` of the exported spans, `signalling-server` by default.
+    pub fn service_name<T: Into<String>>(mut self, service_name: T) -> Self {
+        self.service_name = service_name.into();
+        self
+    }
+
+    /// How long closed spans are collected before being sent together.
+    pub fn flush_interval(mut self, flush_interval: Duration) -> Self {
+        self.flush_interval = flush_interval;
+        self
+    }
+
+    /// Closed spans waiting to be sent, beyond which spans are dropped.
+    pub fn queue_size(mut self, queue_size: usize) -> Self {
+        self.queue_size = queue_size;
+        self
+    }
+
+    /// Starts the thread sending spans to the collector.
+    pub fn build(self) -> OtlpLayer {
+        let (sender, receiver) = sync_channel(self.queue_size);
+        let exporter = Exporter {
+            url: format!("{}/v1/traces", self.endpoint.trim_end_matches('/')),
+            service_name: self.service_name,
+            flush_interval: self.flush_interval,
+        };
+        std::thread::Builder::new()
+            .name("otlp-exporter".to_owned())
+            .spawn(move || exporter.run(receiver))
+            .expect("couldn't start OTLP exporter");
+        OtlpLayer { sender }
+    }
+}
+
+impl<S> Layer<S> for OtlpLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attributes: &Attributes<'_>, id: &Id, context: Context<'_, S>) {
+        let span = context.span(id).expect("span is missing");
+        let mut fields = Fields::default();
+        attributes.record(&mut fields);
+
+        let parent = span.parent().and_then(|parent| {
+            let extensions = parent.extensions();
+            let context = extensions.get::<SpanContext>()?;
+            Some(context.trace_parent())
+        });
+        let traced = attributes.metadata().fields().field(TRACEPARENT).is_some();
+        let (trace_id, parent_span_id, flags) = match fields.traceparent.take().or(parent) {
+            Some(parent) => (parent.trace_id, Some(parent.span_id), parent.flags),
+            None if traced => (*Uuid::new_v4().as_bytes(), None, TraceParent::SAMPLED),
+            None => return,
+        };
+
+        let mut span_context = SpanContext {
+            trace_id,
+            span_id: new_span_id(),
+            parent_span_id,
+            flags,
+            start: SystemTime::now(),
+            attributes: Vec::new(),
+            error: None,
+        };
+        span_context.apply(fields);
+        span.extensions_mut().insert(span_context);
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, context: Context<'_, S>) {
+        let span = context.span(id).expect("span is missing");
+        let mut extensions = span.extensions_mut();
+        if let Some(span_context) = extensions.get_mut::<SpanContext>() {
+            let mut fields = Fields::default();
+            values.record(&mut fields);
+            if let Some(traceparent) = fields.traceparent.take() {
+                span_context.trace_id = traceparent.trace_id;
+                span_context.parent_span_id = Some(traceparent.span_id);
+                span_context.flags = traceparent.flags;
+            }
+            span_context.apply(fields);
+        }
+    }
+
+    fn on_close(&self, id: Id, context: Context<'_, S>) {
+        let span = context.span(&id).expect("span is missing");
+        let mut extensions = span.extensions_mut();
+        if let Some(span_context) = extensions.remove::<SpanContext>() {
+            // unsampled traces are only propagated
+            if !span_context.trace_parent().is_sampled() {
+                return;
+            }
+            let span_data = SpanData {
+                name: span.name(),
+                context: span_context,
+                end: SystemTime::now(),
+            };
+            // a slow collector must not slow down signalling
+            let _ = self.sender.try_send(span_data);
+        }
+    }
+}
+
+fn new_span_id() -> [u8; 8] {
+    let mut span_id = [0; 8];
+    span_id.copy_from_slice(&Uuid::new_v4().as_bytes()[..8]);
+    span_id
+}
+
+struct SpanContext {
+    trace_id: [u8; 16],
+    span_id: [u8; 8],
+    parent_span_id: Option<[u8; 8]>,
+    /// Trace flags inherited from the parent, telling whether it samples.
+    flags: u8,
+    start: SystemTime,
+    attributes: Vec<(&'static str, Value)>,
+    error: Option<String>,
+}
+
+impl SpanContext {
+    fn trace_parent(&self) -> TraceParent {
+        TraceParent::new(self.trace_id, self.span_id, self.flags)
+    }
+
+    fn apply(&mut self, fields: Fields) {
+        if fields.error.is_some() {
+            self.error = fields.error;
+        }
+        for (key, value) in fields.attributes {
+            self.attributes.retain(|(existing, _)| *existing != key);
+            self.attributes.push((key, value));
+        }
+    }
+}
+
+/// Fields of a span as OTLP attribute values.
+#[derive(Default)]
+struct Fields {
+    traceparent: Option<TraceParent>,
+    error: Option<String>,
+    attributes: Vec<(&'static str, Value)>,
+}
+
+impl Fields {
+    fn record(&mut self, field: &Field, value: Value, text: impl FnOnce() -> String) {
+        match field.name() {
+            TRACEPARENT => self.traceparent = text().parse().ok(),
+            ERROR => self.error = Some(text()),
+            name => self.attributes.push((name, value)),
+        }
+    }
+}
+
+impl Visit for Fields {
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        let attribute = json!({ "intValue": value.to_string() });
+        self.record(field, attribute, || value.to_string())
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        let attribute = json!({ "intValue": value.to_string() });
+        self.record(field, attribute, || value.to_string())
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.record(field, json!({ "boolValue": value }), || value.to_string())
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.record(field, json!({ "stringValue": value }), || value.to_owned())
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        let text = format!("{:?}", value);
+        self.record(field, json!({ "stringValue": text }), || text.clone())
+    }
+}
+
+struct SpanData {
+    name: &'static str,
+    context: SpanContext,
+    end: SystemTime,
+}
+
+impl SpanData {
+    fn to_json(&self) -> Value {
+        let context = &self.context;
+        let attributes: Vec<Value> = context
+            .attributes
+            .iter()
+            .map(|(key, value)| json!({ "key": key, "value": value }))
+            .collect();
+        let status = match &context.error {
+            Some(error) => json!({ "code": STATUS_CODE_ERROR, "message": error }),
+            None => json!({}),
+        };
+
+        let mut span = json!({
+            "traceId": encode_hex(&context.trace_id),
+            "spanId": encode_hex(&context.span_id),
+            "name": self.name,
+            "kind": SPAN_KIND_INTERNAL,
+            "startTimeUnixNano": unix_nanos(context.start),
+            "endTimeUnixNano": unix_nanos(self.end),
+            "attributes": attributes,
+            "status": status,
+        });
+        if let Some(parent_span_id) = &context.parent_span_id {
+            span["parentSpanId"] = json!(encode_hex(parent_span_id));
+        }
+        span
+    }
+}
+
+fn unix_nanos(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    since_epoch.as_nanos().to_string()
+}
+
+struct Exporter {
+    url: String,
+    service_name: String,
+    flush_interval: Duration,
+}
+
+impl Exporter {
+    fn run(self, receiver: Receiver<SpanData>) {
+        let mut system = actix_rt::System::new("otlp-exporter");
+        let client = actix_web::client::Client::default();
+
+        while let Ok(span_data) = receiver.recv() {
+            let mut batch = vec![span_data];
+            let deadline = Instant::now() + self.flush_interval;
+            let mut disconnected = false;
+            while batch.len() < MAX_BATCH_SIZE {
+                let timeout = deadline.saturating_duration_since(Instant::now());
+                match receiver.recv_timeout(timeout) {
+                    Ok(span_data) => batch.push(span_data),
+                    Err(RecvTimeoutError::Timeout) => break,
+                    Err(RecvTimeoutError::Disconnected) => {
+                        disconnected = true;
+                        break;
+                    }
+                }
+            }
+
+            // sending starts the request's timeout, which needs the runtime
+            let request = client.post(&self.url);
+            let body = self.encode(&batch);
+            match system.block_on(async move { request.send_json(&body).await }) {
+                Ok(response) if response.status().is_success() => {}
+                Ok(response) => warn!(status = %response.status(), "collector rejected spans"),
+                Err(err) => warn!(error = %err, "couldn't export spans"),
+            }
+            if disconnected {
+                return;
+            }
+        }
+    }
+
+    fn encode(&self, batch: &[SpanData]) -> Value {
+        let spans: Vec<Value> = batch.iter().map(SpanData::to_json).collect();
+        json!({
+            "resourceSpans": [{
+                "resource": {
+                    "attributes": [
+                        { "key": "service.name", "value": { "stringValue": self.service_name } }
+                    ]
+                },
+                "scopeSpans": [{
+                    "scope": { "name": env!("CARGO_PKG_NAME") },
+                    "spans": spans,
+                }]
+            }]
+        })
+    }
+}
@@ -0,0 +1,342 @@
+//! Outbound webhooks telling another service about joins, exits, offers,
+//! answers and hangups.
+//!
+//! Every event is POSTed as JSON, e.g.
+//! `{"id":"…","event":"offer","occurred_at":1700000000,"sender":"…","target":"…"}`,
+//! with the hex HMAC-SHA256 of the body under the webhook's secret in the
+//! `X-Signalling-Signature: sha256=…` header. Failed deliveries are retried
+//! with exponential backoff; events that can't be delivered, or that find
+//! the delivery queue full, are appended to the dead-letter file as JSON
+//! lines by a thread of their own if there is one, and dropped otherwise.
+
+use actix::fut::{wrap_future, ActorFuture};
+use actix::prelude::{Actor, Addr, AsyncContext, Context, Handler};
+use actix_rt::time::delay_for;
+use actix_web::client::Client;
+use hmac::{Hmac, Mac};
+use serde_json::json;
+use sha2::Sha256;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
+use std::time::{Duration, SystemTime};
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use super::admin::unix_seconds;
+use super::signal::trace_parent::encode_hex;
+use super::signal_router::{RouterEvent, SubscribeMessage};
+use super::SignalRouter;
+
+pub const SIGNATURE_HEADER: &str = "X-Signalling-Signature";
+pub const EVENT_HEADER: &str = "X-Signalling-Event";
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Endpoint events are delivered to, and how hard delivering is tried.
+#[derive(Clone, Debug)]
+pub struct Webhook {
+    url: String,
+    secret: String,
+    queue_size: usize,
+    max_attempts: u32,
+    backoff: Duration,
+    dead_letter_file: Option<PathBuf>,
+}
+
+impl Webhook {
+    /// Delivers to `url`, signing with `secret`.
+    pub fn new<U: Into<String>, S: Into<String>>(url: U, secret: S) -> Self {
+        Webhook {
+            url: url.into(),
+            secret: secret.into(),
+            queue_size: 1024,
+            max_attempts: 5,
+            backoff: Duration::from_millis(500),
+            dead_letter_file: None,
+        }
+    }
+
+    /// Events waiting to be delivered, beyond which events are dead-lettered.
+    pub fn queue_size(mut self, queue_size: usize) -> Self {
+        self.queue_size = queue_size;
+        self
+    }
+
+    /// Attempts to deliver an event, the first one included.
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// Wait before the first retry, doubled for every further one.
+    pub fn backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// File undeliverable events are appended to, one JSON body per line.
+    pub fn dead_letter_file<T: Into<PathBuf>>(mut self, dead_letter_file: T) -> Self {
+        self.dead_letter_file = Some(dead_letter_file.into());
+        self
+    }
+
+    /// Starts the thread delivering the events of `signal_router`. Must be
+    /// called from within a running actix system.
+    pub(crate) fn start(self, signal_router: &Addr<SignalRouter>) -> Addr<WebhookPublisher> {
+        let (sender, receiver) = sync_channel(self.queue_size);
+        let dead_letters = DeadLetters::start(self.dead_letter_file.clone(), self.queue_size);
+        let deliverer = Deliverer {
+            dead_letters: dead_letters.clone(),
+            webhook: self,
+        };
+        std::thread::Builder::new()
+            .name("webhook-deliverer".to_owned())
+            .spawn(move || deliverer.run(receiver))
+            .expect("couldn't start webhook deliverer");
+
+        WebhookPublisher {
+            signal_router: signal_router.clone(),
+            sender,
+            dead_letters,
+        }
+        .start()
+    }
+}
+
+/// HMAC-SHA256 of `body` under `secret`, as sent in `SIGNATURE_HEADER`.
+pub fn signature(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any size");
+    mac.update(body);
+    format!("sha256={}", encode_hex(&mac.finalize().into_bytes()))
+}
+
+#[derive(Clone)]
+struct Event {
+    name: &'static str,
+    body: String,
+}
+
+impl Event {
+    fn from_router_event(router_event: RouterEvent) -> Option<Self> {
+        let (name, mut body) = match router_event {
+            RouterEvent::Joined(member) => (
+                "join",
+                json!({
+                    "user": member.name,
+                    "protocol": member.protocol,
+                    "remote_addr": member.remote_addr.map(|addr| addr.to_string()),
+                    "metadata": member.metadata,
+                }),
+            ),
+            RouterEvent::Exited(name) => ("exit", json!({ "user": name })),
+            RouterEvent::Routed(routed) if routed.error.is_none() => {
+                let name = match routed.kind {
+                    "offer" => "offer",
+                    "answer" => "answer",
+                    "hangup" => "hangup",
+                    _ => return None,
+                };
+                (
+                    name,
+                    json!({ "sender": routed.sender, "target": routed.target }),
+                )
+            }
+            RouterEvent::JoinedRoom { .. } | RouterEvent::Routed(_) => return None,
+        };
+        body["id"] = json!(Uuid::new_v4().to_hyphenated().to_string());
+        body["event"] = json!(name);
+        body["occurred_at"] = json!(unix_seconds(SystemTime::now()));
+        Some(Event {
+            name,
+            body: body.to_string(),
+        })
+    }
+}
+
+/// Turns the events of the router into webhook events.
+pub(crate) struct WebhookPublisher {
+    signal_router: Addr<SignalRouter>,
+    sender: SyncSender<Event>,
+    dead_letters: DeadLetters,
+}
+
+impl Actor for WebhookPublisher {
+    type Context = Context<Self>;
+
+    fn started(&mut self, context: &mut Self::Context) {
+        let subscribing_fut = self
+            .signal_router
+            .send(SubscribeMessage(context.address().recipient()));
+        context.wait(
+            wrap_future(subscribing_fut).map(|subscribing_result, _: &mut Self, _| {
+                if let Err(error) = subscribing_result {
+                    warn!(%error, "couldn't subscribe to router")
+                }
+            }),
+        );
+    }
+}
+
+impl Handler<RouterEvent> for WebhookPublisher {
+    type Result = ();
+
+    fn handle(&mut self, router_event: RouterEvent, _: &mut Self::Context) -> Self::Result {
+        if let Some(event) = Event::from_router_event(router_event) {
+            // a slow endpoint must not slow down signalling
+            if let Err(TrySendError::Full(event)) = self.sender.try_send(event) {
+                warn!(event = event.name, "webhook queue full");
+                self.dead_letters.write(event);
+            }
+        }
+    }
+}
+
+/// Hands undeliverable events to the thread appending them to the
+/// dead-letter file, so neither the publisher nor the deliverer waits on
+/// the disk.
+#[derive(Clone)]
+struct DeadLetters(Option<SyncSender<Event>>);
+
+impl DeadLetters {
+    fn start(path: Option<PathBuf>, queue_size: usize) -> Self {
+        let path = match path {
+            Some(path) => path,
+            None => return DeadLetters(None),
+        };
+        let (sender, receiver) = sync_channel(queue_size);
+        std::thread::Builder::new()
+            .name("webhook-dead-letters".to_owned())
+            .spawn(move || write_dead_letters(&path, receiver))
+            .expect("couldn't start webhook dead-letter writer");
+        DeadLetters(Some(sender))
+    }
+
+    fn write(&self, event: Event) {
+        let sender = match &self.0 {
+            Some(sender) => sender,
+            None => return warn!(event = event.name, "webhook event dropped"),
+        };
+        if let Err(TrySendError::Full(event)) = sender.try_send(event) {
+            warn!(
+                event = event.name,
+                "dead-letter queue full, webhook event dropped"
+            );
+        }
+    }
+}
+
+/// Appends the events of `receiver` to the file at `path`, which stays open
+/// until writing to it fails.
+fn write_dead_letters(path: &PathBuf, receiver: Receiver<Event>) {
+    let mut file: Option<File> = None;
+    while let Ok(event) = receiver.recv() {
+        let written = match &mut file {
+            Some(file) => Ok(file),
+            None => OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map(|opened| file.insert(opened)),
+        }
+        .and_then(|file| file.write_all(format!("{}\n", event.body).as_bytes()));
+        match written {
+            Ok(()) => info!(event = event.name, "webhook event dead-lettered"),
+            Err(error) => {
+                warn!(%error, event = event.name, "couldn't dead-letter webhook event");
+                file = None;
+            }
+        }
+    }
+}
+
+struct Deliverer {
+    webhook: Webhook,
+    dead_letters: DeadLetters,
+}
+
+impl Deliverer {
+    fn run(self, receiver: Receiver<Event>) {
+        let mut system = actix_rt::System::new("webhook-deliverer");
+        let client = Client::default();
+        let webhook = Rc::new(self.webhook);
+
+        while let Ok(event) = receiver.recv() {
+            // requests and backoff run on the runtime, which keeps the
+            // client's connection pool going between attempts
+            let event = Rc::new(event);
+            if !system.block_on(deliver(webhook.clone(), client.clone(), event.clone())) {
+                self.dead_letters.write(Event::clone(&event));
+            }
+        }
+    }
+}
+
+async fn deliver(webhook: Rc<Webhook>, client: Client, event: Rc<Event>) -> bool {
+    let signature = signature(&webhook.secret, event.body.as_bytes());
+    let mut backoff = webhook.backoff;
+    for attempt in 1..=webhook.max_attempts {
+        let sending = client
+            .post(&webhook.url)
+            .content_type("application/json")
+            .header(SIGNATURE_HEADER, signature.as_str())
+            .header(EVENT_HEADER, event.name)
+            .send_body(event.body.clone());
+        let retryable = match sending.await {
+            Ok(mut response) => {
+                // the connection is only reused once the body is read
+                let _ = response.body().await;
+                let status = response.status();
+                if status.is_success() {
+                    debug!(event = event.name, attempt, "webhook event delivered");
+                    return true;
+                }
+                warn!(event = event.name, attempt, %status, "webhook endpoint rejected event");
+                status.is_server_error() || status.as_u16() == 408 || status.as_u16() == 429
+            }
+            Err(error) => {
+                warn!(event = event.name, attempt, %error, "couldn't deliver webhook event");
+                true
+            }
+        };
+        if !retryable || attempt == webhook.max_attempts {
+            break;
+        }
+        delay_for(backoff).await;
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+    false
+}
+
+#[test]
+fn test_signing_body() {
+    assert_eq!(
+        signature("key", b"The quick brown fox jumps over the lazy dog"),
+        "sha256=f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+    );
+}
+
+#[test]
+fn test_ignoring_failed_and_other_signals() {
+    use super::signal_router::Routed;
+
+    let routed = |kind, error| {
+        RouterEvent::Routed(Routed {
+            kind,
+            sender: Some("caller".to_owned()),
+            target: Some("callee".to_owned()),
+            error,
+        })
+    };
+
+    let offer = Event::from_router_event(routed("offer", None)).unwrap();
+    let body: serde_json::Value = serde_json::from_str(&offer.body).unwrap();
+
+    assert_eq!(offer.name, "offer");
+    assert_eq!(body["sender"], "caller");
+    assert_eq!(body["target"], "callee");
+    assert!(Event::from_router_event(routed("offer", Some("TargetNotFound"))).is_none());
+    assert!(Event::from_router_event(routed("new_ice_candidate", None)).is_none());
+}
@@ -0,0 +1,36 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+
+use super::http_session;
+use super::{Error, SignalServerStateData};
+
+/// `POST /whep/{publisher}`: relays the SDP offer of a viewer to the
+/// `publisher` socket and responds with its answer.
+pub async fn offer(
+    state: SignalServerStateData,
+    publisher: web::Path<String>,
+    request: HttpRequest,
+    body: String,
+) -> Result<HttpResponse, Error> {
+    http_session::negotiate(&state, "/whep", &publisher, &request, body).await
+}
+
+/// `PATCH /whep/{publisher}/{session}`: trickle ICE.
+pub async fn trickle(
+    state: SignalServerStateData,
+    path: web::Path<(String, String)>,
+    request: HttpRequest,
+    body: String,
+) -> Result<HttpResponse, Error> {
+    let (publisher, session_name) = path.into_inner();
+    http_session::trickle(&state, &publisher, &session_name, &request, &body).await
+}
+
+/// `DELETE /whep/{publisher}/{session}`: ends the session.
+pub async fn teardown(
+    state: SignalServerStateData,
+    path: web::Path<(String, String)>,
+    request: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (publisher, session_name) = path.into_inner();
+    http_session::teardown(&state, &publisher, &session_name, &request).await
+}
@@ -0,0 +1,36 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+
+use super::http_session;
+use super::{Error, SignalServerStateData};
+
+/// `POST /whip/{target}`: relays the SDP offer of an ingest client to the
+/// `target` socket and responds with its answer.
+pub async fn offer(
+    state: SignalServerStateData,
+    target: web::Path<String>,
+    request: HttpRequest,
+    body: String,
+) -> Result<HttpResponse, Error> {
+    http_session::negotiate(&state, "/whip", &target, &request, body).await
+}
+
+/// `PATCH /whip/{target}/{session}`: trickle ICE.
+pub async fn trickle(
+    state: SignalServerStateData,
+    path: web::Path<(String, String)>,
+    request: HttpRequest,
+    body: String,
+) -> Result<HttpResponse, Error> {
+    let (target, session_name) = path.into_inner();
+    http_session::trickle(&state, &target, &session_name, &request, &body).await
+}
+
+/// `DELETE /whip/{target}/{session}`: ends the session.
+pub async fn teardown(
+    state: SignalServerStateData,
+    path: web::Path<(String, String)>,
+    request: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (target, session_name) = path.into_inner();
+    http_session::teardown(&state, &target, &session_name, &request).await
+}
@@ -0,0 +1,279 @@
+//! End-to-end tests of the `/admin` API against connected WebSocket users.
+
+use actix_web::http::{header, StatusCode};
+use actix_web::test::TestServer;
+use awc::ws;
+use futures::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use signalling_server::SignalServer;
+use std::time::Duration;
+
+mod common;
+
+use common::{receive, start_server_with, Connection, TestClient};
+
+const ADMIN_TOKEN: &str = "s3cret";
+
+fn start_server() -> TestServer {
+    start_server_with(SignalServer::builder().admin_token(ADMIN_TOKEN))
+}
+
+async fn get(server: &TestServer, path: &str) -> (StatusCode, Value) {
+    let mut response = server
+        .get(path)
+        .bearer_auth(ADMIN_TOKEN)
+        .send()
+        .await
+        .expect("couldn't request");
+    let body = response.json().await.unwrap_or(Value::Null);
+    (response.status(), body)
+}
+
+async fn post(server: &TestServer, path: &str, body: Value) -> (StatusCode, Value) {
+    let mut response = server
+        .post(path)
+        .bearer_auth(ADMIN_TOKEN)
+        .send_json(&body)
+        .await
+        .expect("couldn't request");
+    let body = response.json().await.unwrap_or(Value::Null);
+    (response.status(), body)
+}
+
+async fn watch_dashboard(server: &TestServer, token: &str) -> Connection {
+    let url = server.url("/admin/stream").replacen("http", "ws", 1);
+    let (_, mut connection) = awc::Client::new()
+        .ws(url)
+        .connect()
+        .await
+        .expect("couldn't connect");
+    connection
+        .send(ws::Message::Text(token.to_owned()))
+        .await
+        .expect("couldn't send");
+    connection
+}
+
+#[actix_rt::test]
+async fn test_rejecting_requests_without_valid_token() {
+    let server = start_server();
+
+    let missing = server.get("/admin/users").send().await.unwrap();
+    let wrong = server
+        .get("/admin/users")
+        .bearer_auth("guess")
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(missing.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(
+        missing.headers().get(header::WWW_AUTHENTICATE).unwrap(),
+        "Bearer"
+    );
+    assert_eq!(wrong.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[actix_rt::test]
+async fn test_not_mounting_admin_api_without_token() {
+    let server = start_server_with(SignalServer::builder());
+
+    let response = server.get("/admin/users").send().await.unwrap();
+
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_rt::test]
+async fn test_listing_users_with_their_counters() {
+    //given
+    let server = start_server();
+    let mut caller = TestClient::connect(&server).await;
+    let mut callee = TestClient::connect(&server).await;
+    caller
+        .send(json!({ "type": "offer", "name": caller.name, "target": callee.name, "sdp": "v=0" }))
+        .await;
+    callee.receive().await;
+
+    //when
+    let (status, users) = get(&server, "/admin/users").await;
+    let (_, callee_details) = get(&server, &format!("/admin/users/{}", callee.name)).await;
+    let (missing_status, _) = get(&server, "/admin/users/nobody").await;
+
+    //then
+    assert_eq!(status, StatusCode::OK);
+    let users = users.as_array().unwrap();
+    assert_eq!(users.len(), 2);
+    let caller_details = users
+        .iter()
+        .find(|user| user["name"] == caller.name.as_str())
+        .unwrap();
+    assert_eq!(caller_details["protocol"], "websocket");
+    assert_eq!(caller_details["signals_sent"], 1);
+    assert_eq!(caller_details["signals_received"], 0);
+    assert!(caller_details["remote_addr"]
+        .as_str()
+        .unwrap()
+        .starts_with("127.0.0.1:"));
+    assert!(caller_details["connected_at"].as_u64().unwrap() > 0);
+    assert_eq!(callee_details["signals_sent"], 0);
+    assert_eq!(callee_details["signals_received"], 1);
+    assert_eq!(missing_status, StatusCode::NOT_FOUND);
+}
+
+#[actix_rt::test]
+async fn test_disconnecting_user_with_reason() {
+    //given
+    let server = start_server();
+    let mut callee = TestClient::connect(&server).await;
+
+    //when
+    let (status, _) = post(
+        &server,
+        &format!("/admin/users/{}/disconnect", callee.name),
+        json!({ "reason": "maintenance" }),
+    )
+    .await;
+
+    //then
+    assert_eq!(status, StatusCode::ACCEPTED);
+    let frame = actix_rt::time::timeout(Duration::from_secs(5), callee.connection.next())
+        .await
+        .expect("timed out waiting for the close frame")
+        .expect("connection closed without a close frame")
+        .expect("protocol error");
+    match frame {
+        ws::Frame::Close(Some(reason)) => {
+            assert_eq!(reason.code, ws::CloseCode::Normal);
+            assert_eq!(reason.description.as_deref(), Some("maintenance"));
+        }
+        other => panic!("expected a close frame, got {:?}", other),
+    }
+    for _ in 0..50 {
+        let (status, _) = get(&server, &format!("/admin/users/{}", callee.name)).await;
+        if status == StatusCode::NOT_FOUND {
+            return;
+        }
+        actix_rt::time::delay_for(Duration::from_millis(20)).await;
+    }
+    panic!("disconnected user is still listed");
+}
+
+#[actix_rt::test]
+async fn test_broadcasting_notice_to_everyone() {
+    //given
+    let server = start_server();
+    let mut caller = TestClient::connect(&server).await;
+    let mut callee = TestClient::connect(&server).await;
+    caller.hello(&["notice"]).await;
+    callee.hello(&["notice"]).await;
+
+    //when
+    let (status, body) = post(
+        &server,
+        "/admin/notices",
+        json!({ "message": "restarting in 5 minutes" }),
+    )
+    .await;
+
+    //then
+    let notice = json!({ "type": "notice", "message": "restarting in 5 minutes" });
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, json!({ "recipients": 2 }));
+    assert_eq!(caller.receive().await, notice);
+    assert_eq!(callee.receive().await, notice);
+}
+
+#[actix_rt::test]
+async fn test_counting_only_users_taking_notice() {
+    //given
+    let server = start_server();
+    let mut caller = TestClient::connect(&server).await;
+    let _callee = TestClient::connect(&server).await;
+    caller.hello(&["notice"]).await;
+
+    //when
+    let (status, body) = post(
+        &server,
+        "/admin/notices",
+        json!({ "message": "restarting in 5 minutes" }),
+    )
+    .await;
+
+    //then
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, json!({ "recipients": 1 }));
+    assert_eq!(caller.receive().await["type"], "notice");
+}
+
+#[actix_rt::test]
+async fn test_streaming_dashboard_snapshots() {
+    //given
+    let server = start_server();
+    let mut caller = TestClient::connect(&server).await;
+    let mut callee = TestClient::connect(&server).await;
+    caller.hello(&["hangup"]).await;
+    caller
+        .send(json!({ "type": "offer", "name": caller.name, "target": callee.name, "sdp": "v=0" }))
+        .await;
+    callee.receive().await;
+    callee
+        .send(json!({ "type": "answer", "name": callee.name, "target": caller.name, "sdp": "v=0" }))
+        .await;
+    caller.receive().await;
+    caller
+        .send(json!({ "type": "hangup", "name": caller.name, "target": "nobody" }))
+        .await;
+    caller.receive().await;
+
+    //when
+    let mut dashboard = watch_dashboard(&server, ADMIN_TOKEN).await;
+
+    //then
+    for _ in 0..5 {
+        let snapshot = receive(&mut dashboard).await;
+        assert_eq!(snapshot["type"], "snapshot");
+        if snapshot["errors"]["by_variant"]["TargetNotFound"] != 1 {
+            continue;
+        }
+        assert_eq!(snapshot["connections"]["total"], 2);
+        assert_eq!(snapshot["connections"]["by_protocol"]["websocket"], 2);
+        assert_eq!(snapshot["calls"]["active"], 1);
+        assert_eq!(snapshot["calls"]["list"][0]["caller"], caller.name.as_str());
+        assert!(snapshot["rates"]["offer"].as_f64().unwrap() > 0.0);
+        assert_eq!(snapshot["errors"]["recent"][0]["signal_type"], "hangup");
+        assert_eq!(snapshot["users"].as_array().unwrap().len(), 2);
+        return;
+    }
+    panic!("dashboard never caught up with the router");
+}
+
+#[actix_rt::test]
+async fn test_closing_dashboard_with_wrong_token() {
+    let server = start_server();
+
+    let mut dashboard = watch_dashboard(&server, "guess").await;
+
+    let frame = actix_rt::time::timeout(Duration::from_secs(5), dashboard.next())
+        .await
+        .expect("timed out waiting for the close frame")
+        .expect("connection closed without a close frame")
+        .expect("protocol error");
+    match frame {
+        ws::Frame::Close(Some(reason)) => {
+            assert_eq!(reason.code, ws::CloseCode::Policy);
+            assert_eq!(reason.description.as_deref(), Some("unauthorized"));
+        }
+        other => panic!("expected a close frame, got {:?}", other),
+    }
+}
+
+#[actix_rt::test]
+async fn test_serving_dashboard_page() {
+    let server = start_server();
+
+    let mut response = server.get("/admin/dashboard").send().await.unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.body().await.unwrap();
+    assert!(String::from_utf8_lossy(&body).contains("/admin/stream"));
+}
@@ -0,0 +1,278 @@
+//! Admission of clients joining the router by a hook stubbed in-process.
+
+use actix_web::http::{header, StatusCode};
+use actix_web::test::TestServer;
+use actix_web::{test, web, App, HttpResponse};
+use awc::ws;
+use futures::StreamExt;
+use serde_json::{json, Value};
+use signalling_server::{AdmissionHook, SignalPolicy, SignalServer};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+mod common;
+
+use common::{receive, start_server_with, Connection, TestClient};
+
+type AskedAdmissions = Arc<Mutex<Vec<Value>>>;
+
+/// Denies `banned` and the `suspended` tenant and admits everyone else under
+/// the requested name, if any, with their tenant as metadata.
+async fn admit(asked: web::Data<AskedAdmissions>, request: web::Json<Value>) -> HttpResponse {
+    let request = request.into_inner();
+    asked.lock().unwrap().push(request.clone());
+    let suspended = request["headers"]["x-tenant"] == "suspended";
+    let answer = match request["requested_name"].as_str() {
+        Some("banned") => json!({ "allow": false, "reason": "tenant is suspended" }),
+        _ if suspended => json!({ "allow": false, "reason": "tenant is suspended" }),
+        requested_name => json!({
+            "allow": true,
+            "name": requested_name,
+            "metadata": { "tenant": request["headers"]["x-tenant"] },
+        }),
+    };
+    HttpResponse::Ok().json(answer)
+}
+
+fn start_hook(asked: AskedAdmissions) -> TestServer {
+    test::start(move || {
+        App::new()
+            .data(asked.clone())
+            .route("/admit", web::post().to(admit))
+    })
+}
+
+fn start_server(hook: &TestServer) -> TestServer {
+    start_server_with(
+        SignalServer::builder()
+            .admission_hook(AdmissionHook::new(hook.url("/admit")))
+            .admin_token("s3cret")
+            .sse(true)
+            .peerjs_key("peerjs")
+            .whip(true),
+    )
+}
+
+async fn open(server: &TestServer, name: &str) -> Connection {
+    let url = server
+        .url(&format!("/signal?name={}", name))
+        .replacen("http", "ws", 1);
+    let (_, connection) = awc::Client::new()
+        .ws(url)
+        .connect()
+        .await
+        .expect("couldn't connect");
+    connection
+}
+
+/// Reason of the close frame ending `connection`, skipping other frames.
+async fn receive_close(connection: &mut Connection) -> Option<ws::CloseReason> {
+    loop {
+        let frame = actix_rt::time::timeout(Duration::from_secs(5), connection.next())
+            .await
+            .expect("timed out waiting for a frame")
+            .expect("connection closed")
+            .expect("protocol error");
+        if let ws::Frame::Close(reason) = frame {
+            return reason;
+        }
+    }
+}
+
+/// Connects as `name` of `tenant`.
+async fn connect(server: &TestServer, name: &str, tenant: &str) -> TestClient {
+    let url = server
+        .url(&format!("/signal?name={}", name))
+        .replacen("http", "ws", 1);
+    let (_, mut connection) = awc::Client::new()
+        .ws(url)
+        .header("X-Tenant", tenant)
+        .connect()
+        .await
+        .expect("couldn't connect");
+    receive(&mut connection).await;
+    TestClient {
+        name: name.to_owned(),
+        connection,
+    }
+}
+
+#[actix_rt::test]
+async fn test_admitting_under_granted_name_with_metadata() {
+    //given
+    let asked = AskedAdmissions::default();
+    let hook = start_hook(asked.clone());
+    let server = start_server(&hook);
+    let url = server.url("/signal?name=alice").replacen("http", "ws", 1);
+
+    //when
+    let (_, mut connection) = awc::Client::new()
+        .ws(url)
+        .header("X-Tenant", "acme")
+        .connect()
+        .await
+        .expect("couldn't connect");
+    let assign = receive(&mut connection).await;
+    let mut user = server
+        .get("/admin/users/alice")
+        .bearer_auth("s3cret")
+        .send()
+        .await
+        .unwrap();
+
+    //then
+    assert_eq!(assign, json!({ "type": "assign", "name": "alice" }));
+    assert_eq!(
+        user.json::<Value>().await.unwrap()["metadata"],
+        json!({ "tenant": "acme" })
+    );
+    let asked = asked.lock().unwrap();
+    assert_eq!(asked[0]["requested_name"], "alice");
+    assert_eq!(asked[0]["remote_ip"], "127.0.0.1");
+    assert_eq!(asked[0]["headers"]["x-tenant"], "acme");
+}
+
+#[actix_rt::test]
+async fn test_assigning_random_name_if_none_is_granted() {
+    let hook = start_hook(AskedAdmissions::default());
+    let server = start_server(&hook);
+
+    let client = TestClient::connect(&server).await;
+
+    assert_eq!(client.name.len(), 36);
+}
+
+#[actix_rt::test]
+async fn test_denying_with_reason() {
+    let hook = start_hook(AskedAdmissions::default());
+    let server = start_server(&hook);
+
+    let mut response = server.get("/signal?name=banned").send().await.unwrap();
+
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(response.body().await.unwrap(), "tenant is suspended");
+}
+
+#[actix_rt::test]
+async fn test_refusing_name_already_taken() {
+    //given
+    let hook = start_hook(AskedAdmissions::default());
+    let server = start_server(&hook);
+    let mut first = open(&server, "alice").await;
+    receive(&mut first).await;
+
+    //when
+    let mut second = open(&server, "alice").await;
+
+    //then
+    assert_eq!(
+        receive_close(&mut second).await,
+        Some(ws::CloseReason {
+            code: ws::CloseCode::Policy,
+            description: Some("user name is taken".to_owned()),
+        })
+    );
+}
+
+#[actix_rt::test]
+async fn test_admitting_one_of_concurrent_connections_under_same_name() {
+    //given
+    let hook = start_hook(AskedAdmissions::default());
+    let server = start_server(&hook);
+
+    //when
+    let (mut first, mut second) = futures::join!(open(&server, "alice"), open(&server, "alice"));
+    let (first_frame, second_frame) = futures::join!(first.next(), second.next());
+
+    //then
+    let mut outcomes: Vec<&str> = [first_frame, second_frame]
+        .iter()
+        .map(|frame| match frame {
+            Some(Ok(ws::Frame::Text(_))) => "assigned",
+            Some(Ok(ws::Frame::Close(Some(reason)))) if reason.code == ws::CloseCode::Policy => {
+                "refused"
+            }
+            other => panic!("unexpected frame {:?}", other),
+        })
+        .collect();
+    outcomes.sort_unstable();
+    assert_eq!(outcomes, ["assigned", "refused"]);
+}
+
+#[actix_rt::test]
+async fn test_asking_hook_on_every_transport() {
+    //given
+    let hook = start_hook(AskedAdmissions::default());
+    let server = start_server(&hook);
+    let target = TestClient::connect(&server).await;
+
+    //when
+    let sse = server
+        .get("/signal/events?name=banned")
+        .send()
+        .await
+        .unwrap();
+    let peerjs = server
+        .get("/peerjs?key=peerjs&id=banned&token=token")
+        .send()
+        .await
+        .unwrap();
+    let whip = server
+        .post(format!("/whip/{}", target.name))
+        .header(header::CONTENT_TYPE, "application/sdp")
+        .header("X-Tenant", "suspended")
+        .send_body("v=0 offer")
+        .await
+        .unwrap();
+
+    //then
+    assert_eq!(sse.status(), StatusCode::FORBIDDEN);
+    assert_eq!(peerjs.status(), StatusCode::FORBIDDEN);
+    assert_eq!(whip.status(), StatusCode::FORBIDDEN);
+}
+
+#[actix_rt::test]
+async fn test_refusing_while_hook_is_down() {
+    let hook = start_hook(AskedAdmissions::default());
+    let unreachable_url = hook.url("/missing");
+    let server = start_server_with(
+        SignalServer::builder().admission_hook(AdmissionHook::new(unreachable_url)),
+    );
+
+    let response = server.get("/signal").send().await.unwrap();
+
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+}
+
+#[actix_rt::test]
+async fn test_forbidding_calls_across_tenants() {
+    //given
+    let hook = start_hook(AskedAdmissions::default());
+    let signal_policy: SignalPolicy = serde_json::from_value(json!({
+        "default": "deny",
+        "rules": [{ "effect": "allow", "same": ["tenant"] }],
+    }))
+    .unwrap();
+    let server = start_server_with(
+        SignalServer::builder()
+            .admission_hook(AdmissionHook::new(hook.url("/admit")))
+            .signal_policy(signal_policy),
+    );
+    let mut alice = connect(&server, "alice", "acme").await;
+    let mut bob = connect(&server, "bob", "acme").await;
+    let mut mallory = connect(&server, "mallory", "initech").await;
+
+    //when
+    mallory
+        .send(json!({ "type": "offer", "name": "mallory", "target": "alice", "sdp": "v=0" }))
+        .await;
+    bob.send(json!({ "type": "offer", "name": "bob", "target": "alice", "sdp": "v=0" }))
+        .await;
+
+    //then
+    assert_eq!(
+        mallory.receive().await,
+        json!({ "type": "forbidden", "message": "signalling user alice is not allowed" })
+    );
+    assert_eq!(alice.receive().await["name"], "bob");
+}
@@ -0,0 +1,112 @@
+//! WebSocket clients of a real server on an ephemeral port, speaking the
+//! JSON protocol. Each test crate uses its own part of it.
+#![allow(dead_code)]
+
+use actix_web::{test, App};
+use awc::ws;
+use futures::{SinkExt, StreamExt};
+use serde_json::Value;
+use signalling_server::{SignalServer, SignalServerBuilder};
+use std::time::Duration;
+
+const RECEIVE_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub type Connection = actix_codec::Framed<awc::BoxedSocket, ws::Codec>;
+
+pub fn start_server() -> test::TestServer {
+    start_server_with(SignalServer::builder())
+}
+
+pub fn start_server_with(signal_server_builder: SignalServerBuilder) -> test::TestServer {
+    serve(&signal_server_builder.build())
+}
+
+/// Serves `signal_server`, whose router the test can then ask directly.
+pub fn serve(signal_server: &SignalServer) -> test::TestServer {
+    let signal_server = signal_server.clone();
+    test::start(move || {
+        let signal_server = signal_server.clone();
+        App::new().configure(move |config| signal_server.configure(config))
+    })
+}
+
+pub struct TestClient {
+    pub name: String,
+    pub connection: Connection,
+}
+
+impl TestClient {
+    pub async fn connect(server: &test::TestServer) -> Self {
+        let url = server.url("/signal").replacen("http", "ws", 1);
+        let (_, mut connection) = awc::Client::new()
+            .ws(url)
+            .connect()
+            .await
+            .expect("couldn't connect");
+        let assign = receive(&mut connection).await;
+        assert_eq!(assign["type"], "assign");
+        let name = assign["name"].as_str().expect("name is missing").to_owned();
+
+        TestClient { name, connection }
+    }
+
+    pub async fn send(&mut self, message: Value) {
+        self.send_text(to_text(message)).await
+    }
+
+    /// Negotiates `features`, e.g. `hangup`, needed to send or receive the
+    /// signals gated on them.
+    pub async fn hello(&mut self, features: &[&str]) {
+        self.send(serde_json::json!({ "type": "hello", "version": 1, "features": features }))
+            .await;
+        let hello = self.receive().await;
+        assert_eq!(hello["type"], "hello", "expected a hello, got {}", hello);
+    }
+
+    pub async fn send_text(&mut self, text: String) {
+        self.connection
+            .send(ws::Message::Text(text))
+            .await
+            .expect("couldn't send");
+    }
+
+    pub async fn receive(&mut self) -> Value {
+        receive(&mut self.connection).await
+    }
+
+    pub async fn receive_text(&mut self) -> String {
+        receive_text(&mut self.connection).await
+    }
+}
+
+/// Encodes `message` with its `type` first, as the server reads it before
+/// the other fields.
+pub fn to_text(message: Value) -> String {
+    let mut fields = message.as_object().expect("not an object").clone();
+    let kind = fields.remove("type").expect("type is missing");
+    if fields.is_empty() {
+        return format!("{{\"type\":{}}}", kind);
+    }
+    let fields = Value::Object(fields).to_string();
+    format!("{{\"type\":{},{}", kind, &fields[1..])
+}
+
+pub async fn receive(connection: &mut Connection) -> Value {
+    let text = receive_text(connection).await;
+    serde_json::from_str(&text).unwrap_or_else(|_| panic!("not JSON: {}", text))
+}
+
+async fn receive_text(connection: &mut Connection) -> String {
+    loop {
+        let frame = actix_rt::time::timeout(RECEIVE_TIMEOUT, connection.next())
+            .await
+            .expect("timed out waiting for a frame")
+            .expect("connection closed")
+            .expect("protocol error");
+        match frame {
+            ws::Frame::Text(bytes) => return String::from_utf8(bytes.to_vec()).unwrap(),
+            ws::Frame::Ping(_) | ws::Frame::Pong(_) => continue,
+            other => panic!("unexpected frame {:?}", other),
+        }
+    }
+}
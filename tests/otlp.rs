@@ -0,0 +1,128 @@
+//! Export of signal traces to an OTLP collector, stubbed in-process.
+
+use actix_web::{test, web, App, HttpResponse};
+use serde_json::{json, Value};
+use signalling_server::signal::TraceParent;
+use signalling_server::OtlpLayer;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing_subscriber::prelude::*;
+
+mod common;
+
+use common::{start_server, TestClient};
+
+type ExportedSpans = Arc<Mutex<Vec<Value>>>;
+
+async fn collect(spans: web::Data<ExportedSpans>, request: web::Json<Value>) -> HttpResponse {
+    let mut collected = spans.lock().unwrap();
+    for resource_spans in request["resourceSpans"].as_array().unwrap() {
+        assert_eq!(
+            resource_spans["resource"]["attributes"][0],
+            json!({ "key": "service.name", "value": { "stringValue": "signalling-server" } })
+        );
+        for scope_spans in resource_spans["scopeSpans"].as_array().unwrap() {
+            collected.extend(scope_spans["spans"].as_array().unwrap().iter().cloned());
+        }
+    }
+    HttpResponse::Ok().json(json!({}))
+}
+
+fn start_collector(spans: ExportedSpans) -> test::TestServer {
+    test::start(move || {
+        App::new()
+            .data(spans.clone())
+            .route("/v1/traces", web::post().to(collect))
+    })
+}
+
+async fn wait_for_span(spans: &ExportedSpans, trace_id: &str, name: &str) -> Value {
+    for _ in 0..100 {
+        let found = spans
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|span| span["traceId"] == trace_id && span["name"] == name)
+            .cloned();
+        if let Some(span) = found {
+            return span;
+        }
+        actix_rt::time::delay_for(Duration::from_millis(50)).await;
+    }
+    panic!("span {} of trace {} wasn't exported", name, trace_id)
+}
+
+#[actix_rt::test]
+async fn test_exporting_receive_route_and_deliver_of_a_traced_offer() {
+    //given
+    let spans = ExportedSpans::default();
+    let collector = start_collector(spans.clone());
+    tracing_subscriber::registry()
+        .with(
+            OtlpLayer::builder(collector.url(""))
+                .flush_interval(Duration::from_millis(50))
+                .build(),
+        )
+        .init();
+    let server = start_server();
+    let mut caller = TestClient::connect(&server).await;
+    let mut callee = TestClient::connect(&server).await;
+    let traceparent = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+    let unsampled_traceparent = "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-00";
+
+    //when
+    caller
+        .send(json!({
+            "type": "offer",
+            "name": caller.name,
+            "target": callee.name,
+            "sdp": "v=0",
+            "traceparent": unsampled_traceparent
+        }))
+        .await;
+    let unsampled_offer = callee.receive().await;
+    caller
+        .send(json!({
+            "type": "offer",
+            "name": caller.name,
+            "target": callee.name,
+            "sdp": "v=0",
+            "traceparent": traceparent
+        }))
+        .await;
+    let offer = callee.receive().await;
+
+    //then
+    let trace_id = "4bf92f3577b34da6a3ce929d0e0e4736";
+    let received: TraceParent = offer["traceparent"].as_str().unwrap().parse().unwrap();
+    let receive = wait_for_span(&spans, trace_id, "receive").await;
+    let route = wait_for_span(&spans, trace_id, "route").await;
+    let deliver = wait_for_span(&spans, trace_id, "deliver").await;
+
+    assert_eq!(received.to_string()[3..35], *trace_id);
+    assert_eq!(receive["parentSpanId"], "00f067aa0ba902b7");
+    assert_eq!(route["parentSpanId"], receive["spanId"]);
+    assert_eq!(deliver["parentSpanId"], route["spanId"]);
+    assert_eq!(received.to_string()[36..52], deliver["spanId"]);
+    assert!(receive["attributes"]
+        .as_array()
+        .unwrap()
+        .contains(&json!({ "key": "signal_type", "value": { "stringValue": "offer" } })));
+    assert_eq!(route["status"], json!({}));
+    // spans close in order, so those of the unsampled offer would be in by now
+    let unsampled: TraceParent = unsampled_offer["traceparent"]
+        .as_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!(!unsampled.is_sampled());
+    assert_eq!(
+        unsampled.to_string()[3..35],
+        *"0af7651916cd43dd8448eb211c80319c"
+    );
+    assert!(!spans
+        .lock()
+        .unwrap()
+        .iter()
+        .any(|span| span["traceId"] == "0af7651916cd43dd8448eb211c80319c"));
+}
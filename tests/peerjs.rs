@@ -0,0 +1,69 @@
+//! End-to-end tests of the PeerJS server protocol.
+
+use actix_web::http::{header, StatusCode};
+use actix_web::test::TestServer;
+use signalling_server::{OriginAllowlist, SignalServer};
+
+mod common;
+
+use common::{receive, start_server_with, Connection};
+
+async fn connect(server: &TestServer, id: &str, token: &str) -> Connection {
+    let url = server
+        .url(&format!("/peerjs?key=peerjs&id={}&token={}", id, token))
+        .replacen("http", "ws", 1);
+    let (_, connection) = awc::Client::new()
+        .ws(url)
+        .connect()
+        .await
+        .expect("couldn't connect");
+    connection
+}
+
+#[actix_rt::test]
+async fn test_handing_out_id_under_key() {
+    let server = start_server_with(SignalServer::builder().peerjs_key("app"));
+
+    let mut response = server
+        .get("/app/id")
+        .send()
+        .await
+        .expect("couldn't request");
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.body().await.unwrap().len(), 36);
+}
+
+#[actix_rt::test]
+async fn test_taking_id_only_with_its_token() {
+    //given
+    let server = start_server_with(SignalServer::builder().peerjs_key("peerjs"));
+    let mut first = connect(&server, "peer", "first").await;
+    assert_eq!(receive(&mut first).await["type"], "OPEN");
+
+    //when
+    let mut impostor = connect(&server, "peer", "guess").await;
+    let mut reconnected = connect(&server, "peer", "first").await;
+
+    //then
+    assert_eq!(receive(&mut impostor).await["type"], "ID-TAKEN");
+    assert_eq!(receive(&mut reconnected).await["type"], "OPEN");
+}
+
+#[actix_rt::test]
+async fn test_rejecting_disallowed_origin() {
+    let server = start_server_with(
+        SignalServer::builder()
+            .peerjs_key("peerjs")
+            .origin_allowlist(OriginAllowlist::new(vec!["app.example.com"])),
+    );
+
+    let response = server
+        .get("/peerjs?key=peerjs&id=peer&token=token")
+        .header(header::ORIGIN, "https://evil.example.com")
+        .send()
+        .await
+        .expect("couldn't request");
+
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
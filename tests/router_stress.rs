@@ -0,0 +1,508 @@
+//! Seeded randomized stress test of `SignalRouter` under concurrent joins,
+//! leaves, disconnects and signals.
+//!
+//! Each seed drives a fresh router and a crowd of mock sockets with
+//! batches of operations that are issued without waiting for each other,
+//! so their messages interleave in the router's and the sockets' mailboxes.
+//! After every batch the outcomes are checked against a model of the
+//! router's membership.
+//!
+//! Only the operations are seeded: how their messages interleave is up to
+//! actix's scheduling, so the model accepts every outcome a race may lead
+//! to, e.g. a signal to a member leaving in the same batch may be delivered
+//! or fail with `ConnectionClosed`. A failing seed is reported and its
+//! operations can be replayed with
+//! `STRESS_SEED=<seed> cargo test --test router_stress`, which may take a
+//! few runs to hit the same interleaving.
+
+use actix::prelude::{Actor, ActorContext, Addr, Context, Handler, Message};
+use futures::future::{join_all, BoxFuture, FutureExt};
+use signalling_server::signal::{HangupMessage, IceCandidate, SessionDescriptionMessage, Signal};
+use signalling_server::{
+    Error, ExitMessage, JoinMessage, MembersMessage, SignalMessage, SignalRouter,
+};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const SEEDS: u64 = 24;
+const BATCHES: usize = 120;
+const MAX_BATCH_SIZE: u64 = 8;
+const NAMES: [&str; 6] = ["peer-0", "peer-1", "peer-2", "peer-3", "peer-4", "peer-5"];
+const STRANGER: &str = "stranger";
+
+/// xorshift64*, so a seed replays the same run on every platform.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Rng(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn below(&mut self, bound: u64) -> u64 {
+        self.next() % bound
+    }
+
+    fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.below(items.len() as u64) as usize]
+    }
+}
+
+#[derive(Debug)]
+struct Delivery {
+    name: String,
+    incarnation: u64,
+    sequence: u64,
+    after_departure: bool,
+}
+
+type Deliveries = Arc<Mutex<Vec<Delivery>>>;
+
+/// Stand-in for a socket: records the signals routed to it and, like
+/// `SignalSocket`, exits the router when it stops after a disconnect.
+struct MockSocket {
+    name: String,
+    incarnation: u64,
+    router: Addr<SignalRouter>,
+    deliveries: Deliveries,
+    member: bool,
+    departed: bool,
+    exit_on_stop: bool,
+}
+
+impl Actor for MockSocket {
+    type Context = Context<Self>;
+
+    fn stopped(&mut self, _: &mut Self::Context) {
+        if self.member && self.exit_on_stop {
+            self.router.do_send(ExitMessage::from(self.name.clone()));
+        }
+    }
+}
+
+impl Handler<Signal> for MockSocket {
+    type Result = Result<(), Error>;
+
+    fn handle(&mut self, signal: Signal, _: &mut Self::Context) -> Self::Result {
+        self.deliveries.lock().unwrap().push(Delivery {
+            name: self.name.clone(),
+            incarnation: self.incarnation,
+            sequence: sequence(&signal),
+            after_departure: self.departed,
+        });
+        Ok(())
+    }
+}
+
+/// Tells the socket its join was accepted.
+struct Member;
+
+impl Message for Member {
+    type Result = ();
+}
+
+impl Handler<Member> for MockSocket {
+    type Result = ();
+
+    fn handle(&mut self, _: Member, _: &mut Self::Context) {
+        self.member = true;
+    }
+}
+
+/// Stops the socket, exiting the router on the way out unless the harness
+/// already did.
+struct Depart {
+    exit_on_stop: bool,
+}
+
+impl Message for Depart {
+    type Result = ();
+}
+
+impl Handler<Depart> for MockSocket {
+    type Result = ();
+
+    fn handle(&mut self, depart: Depart, context: &mut Self::Context) {
+        self.departed = true;
+        self.exit_on_stop = depart.exit_on_stop;
+        context.stop();
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum SignalKind {
+    Offer,
+    Answer,
+    Candidate,
+    Hangup,
+}
+
+const SIGNAL_KINDS: [SignalKind; 4] = [
+    SignalKind::Offer,
+    SignalKind::Answer,
+    SignalKind::Candidate,
+    SignalKind::Hangup,
+];
+
+/// Signal of `kind` carrying `sequence`, so deliveries can be told apart.
+fn signal(kind: SignalKind, sender: &str, target: &str, sequence: u64) -> Signal {
+    let (target, sender, tag) = (target.to_owned(), sender.to_owned(), sequence.to_string());
+    match kind {
+        SignalKind::Offer => Signal::Offer(SessionDescriptionMessage::new(target, sender, tag)),
+        SignalKind::Answer => Signal::Answer(SessionDescriptionMessage::new(target, sender, tag)),
+        SignalKind::Candidate => Signal::NewIceCandidate(IceCandidate::new(target, tag)),
+        SignalKind::Hangup => Signal::Hangup(HangupMessage::new(target, tag)),
+    }
+}
+
+fn sequence(signal: &Signal) -> u64 {
+    let tag = match signal {
+        Signal::Offer(message) | Signal::Answer(message) => message.sdp(),
+        Signal::NewIceCandidate(ice_candidate) => ice_candidate.candidate(),
+        Signal::Hangup(hangup_message) => &hangup_message.name,
+        other => panic!("unexpected signal {:?}", other),
+    };
+    tag.parse().expect("signal without a sequence number")
+}
+
+#[derive(Debug)]
+enum Operation {
+    Join(String),
+    Leave(String),
+    Disconnect(String),
+    Send {
+        kind: SignalKind,
+        sender: String,
+        target: String,
+        sequence: u64,
+    },
+}
+
+/// Router membership as the harness expects it to be.
+enum Presence {
+    Joined {
+        incarnation: u64,
+        socket: Addr<MockSocket>,
+    },
+    /// Stopped, its exit not necessarily processed by the router yet.
+    Disconnecting,
+}
+
+/// Outcomes an operation may resolve to. For a send they depend on the
+/// target when it was issued and on whether it departs later in the batch.
+#[derive(Debug)]
+enum Expected {
+    Accepted,
+    Rejected,
+    DeliveredTo(String, u64),
+    DeliveredToOrClosed(String, u64),
+    NotFound,
+    ClosedOrNotFound,
+}
+
+enum Outcome {
+    Joined(Result<(), ()>),
+    Left(Result<(), ()>),
+    Sent(Result<(), Error>),
+}
+
+struct StressRun {
+    rng: Rng,
+    router: Addr<SignalRouter>,
+    deliveries: Deliveries,
+    presences: HashMap<String, Presence>,
+    incarnations: u64,
+    sequences: u64,
+}
+
+impl StressRun {
+    fn new(seed: u64) -> Self {
+        StressRun {
+            rng: Rng::new(seed),
+            router: SignalRouter::default().start(),
+            deliveries: Deliveries::default(),
+            presences: HashMap::new(),
+            incarnations: 0,
+            sequences: 0,
+        }
+    }
+
+    fn joined_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .presences
+            .iter()
+            .filter(|(_, presence)| matches!(presence, Presence::Joined { .. }))
+            .map(|(name, _)| name.clone())
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// Picks an operation that the model can predict, i.e. no join of a
+    /// name whose exit may still be in flight.
+    fn next_operation(&mut self) -> Operation {
+        let joined = self.joined_names();
+        let joinable: Vec<&str> = NAMES
+            .iter()
+            .copied()
+            .filter(|name| !matches!(self.presences.get(*name), Some(Presence::Disconnecting)))
+            .collect();
+
+        match self.rng.below(10) {
+            0..=2 if !joinable.is_empty() => Operation::Join(self.rng.pick(&joinable).to_string()),
+            3 if !joined.is_empty() => Operation::Leave(self.rng.pick(&joined).clone()),
+            4 if !joined.is_empty() => Operation::Disconnect(self.rng.pick(&joined).clone()),
+            _ => {
+                self.sequences += 1;
+                let target = if self.rng.below(8) == 0 {
+                    STRANGER
+                } else {
+                    self.rng.pick(&NAMES)
+                };
+                Operation::Send {
+                    kind: *self.rng.pick(&SIGNAL_KINDS),
+                    sender: self.rng.pick(&NAMES).to_string(),
+                    target: target.to_owned(),
+                    sequence: self.sequences,
+                }
+            }
+        }
+    }
+
+    async fn run_batch(&mut self) -> Result<(), String> {
+        let size = 1 + self.rng.below(MAX_BATCH_SIZE);
+        let mut operations = Vec::new();
+        let mut pending: Vec<BoxFuture<'static, Outcome>> = Vec::new();
+        let mut expectations = Vec::new();
+        for _ in 0..size {
+            let operation = self.next_operation();
+            let (future, expected) = self.issue(&operation);
+            operations.push(operation);
+            pending.push(future);
+            expectations.push(expected);
+        }
+        let outcomes = join_all(pending).await;
+
+        // a target departing later in the batch may stop before the router
+        // forwards a signal issued while it was still joined
+        for (index, expected) in expectations.iter_mut().enumerate() {
+            if let Expected::DeliveredTo(name, incarnation) = expected {
+                let departs_later = operations[index + 1..].iter().any(|later| match later {
+                    Operation::Leave(departing) | Operation::Disconnect(departing) => {
+                        departing == name
+                    }
+                    _ => false,
+                });
+                if departs_later {
+                    *expected = Expected::DeliveredToOrClosed(name.clone(), *incarnation);
+                }
+            }
+        }
+
+        for ((operation, expected), outcome) in operations.iter().zip(expectations).zip(outcomes) {
+            self.check(operation, expected, outcome)?;
+        }
+        self.settle().await
+    }
+
+    fn issue(&mut self, operation: &Operation) -> (BoxFuture<'static, Outcome>, Expected) {
+        let router = self.router.clone();
+        match operation {
+            Operation::Join(name) => {
+                self.incarnations += 1;
+                let socket = MockSocket {
+                    name: name.clone(),
+                    incarnation: self.incarnations,
+                    router: self.router.clone(),
+                    deliveries: self.deliveries.clone(),
+                    member: false,
+                    departed: false,
+                    exit_on_stop: false,
+                }
+                .start();
+                let join = router.send(JoinMessage::new(name.clone(), socket.clone().recipient()));
+                let expected = if self.presences.contains_key(name) {
+                    Expected::Rejected
+                } else {
+                    socket.do_send(Member);
+                    let presence = Presence::Joined {
+                        incarnation: self.incarnations,
+                        socket,
+                    };
+                    self.presences.insert(name.clone(), presence);
+                    Expected::Accepted
+                };
+                let future = join.map(|result| Outcome::Joined(result.expect("router is gone")));
+                (future.boxed(), expected)
+            }
+            Operation::Leave(name) => {
+                let exit = router.send(ExitMessage::from(name.clone()));
+                if let Some(Presence::Joined { socket, .. }) = self.presences.remove(name) {
+                    socket.do_send(Depart {
+                        exit_on_stop: false,
+                    });
+                }
+                let future = exit.map(|result| Outcome::Left(result.expect("router is gone")));
+                (future.boxed(), Expected::Accepted)
+            }
+            Operation::Disconnect(name) => {
+                let presence = self.presences.insert(name.clone(), Presence::Disconnecting);
+                if let Some(Presence::Joined { socket, .. }) = presence {
+                    socket.do_send(Depart { exit_on_stop: true });
+                }
+                let future = futures::future::ready(Outcome::Left(Ok(())));
+                (future.boxed(), Expected::Accepted)
+            }
+            Operation::Send {
+                kind,
+                sender,
+                target,
+                sequence,
+            } => {
+                let expected = match self.presences.get(target) {
+                    Some(Presence::Joined { incarnation, .. }) => {
+                        Expected::DeliveredTo(target.clone(), *incarnation)
+                    }
+                    Some(Presence::Disconnecting) => Expected::ClosedOrNotFound,
+                    None => Expected::NotFound,
+                };
+                let signal = signal(*kind, sender, target, *sequence);
+                let future = router
+                    .send(SignalMessage::from(signal))
+                    .map(|result| Outcome::Sent(result.expect("router is gone")));
+                (future.boxed(), expected)
+            }
+        }
+    }
+
+    fn check(
+        &self,
+        operation: &Operation,
+        expected: Expected,
+        outcome: Outcome,
+    ) -> Result<(), String> {
+        let consistent = match (&expected, outcome) {
+            (_, Outcome::Sent(result)) => return self.check_send(operation, expected, result),
+            (Expected::Accepted, Outcome::Joined(Ok(())))
+            | (Expected::Rejected, Outcome::Joined(Err(())))
+            | (Expected::Accepted, Outcome::Left(Ok(()))) => true,
+            _ => false,
+        };
+        if consistent {
+            Ok(())
+        } else {
+            Err(format!("{:?} wasn't {:?}", operation, expected))
+        }
+    }
+
+    fn check_send(
+        &self,
+        operation: &Operation,
+        expected: Expected,
+        result: Result<(), Error>,
+    ) -> Result<(), String> {
+        let sequence = match operation {
+            Operation::Send { sequence, .. } => *sequence,
+            _ => unreachable!("outcome of another operation"),
+        };
+        let deliveries = self.deliveries.lock().unwrap();
+        let delivered: Vec<&Delivery> = deliveries
+            .iter()
+            .filter(|delivery| delivery.sequence == sequence)
+            .collect();
+        if let Some(delivery) = delivered.iter().find(|delivery| delivery.after_departure) {
+            return Err(format!(
+                "{:?} delivered after departure: {:?}",
+                operation, delivery
+            ));
+        }
+
+        let consistent = match (&expected, &result, delivered.as_slice()) {
+            (Expected::DeliveredTo(name, incarnation), Ok(()), [delivery])
+            | (Expected::DeliveredToOrClosed(name, incarnation), Ok(()), [delivery]) => {
+                delivery.name == *name && delivery.incarnation == *incarnation
+            }
+            (Expected::DeliveredToOrClosed(..), Err(Error::ConnectionClosed), [])
+            | (Expected::ClosedOrNotFound, Err(Error::ConnectionClosed), [])
+            | (Expected::ClosedOrNotFound, Err(Error::TargetNotFound(_)), [])
+            | (Expected::NotFound, Err(Error::TargetNotFound(_)), []) => true,
+            _ => false,
+        };
+        if consistent {
+            Ok(())
+        } else {
+            Err(format!(
+                "{:?} expected {:?}, resolved to {:?} with deliveries {:?}",
+                operation, expected, result, delivered
+            ))
+        }
+    }
+
+    /// Waits for the exits of disconnected sockets, then checks the router
+    /// holds exactly the joined names.
+    async fn settle(&mut self) -> Result<(), String> {
+        self.presences
+            .retain(|_, presence| matches!(presence, Presence::Joined { .. }));
+        let expected = self.joined_names();
+
+        let mut members = Vec::new();
+        for _ in 0..200 {
+            members = self
+                .router
+                .send(MembersMessage)
+                .await
+                .expect("router is gone");
+            if members == expected {
+                return Ok(());
+            }
+            actix_rt::time::delay_for(Duration::from_millis(1)).await;
+        }
+        Err(format!(
+            "router members {:?}, expected {:?}",
+            members, expected
+        ))
+    }
+}
+
+async fn stress(seed: u64) -> Result<(), String> {
+    let mut run = StressRun::new(seed);
+    for batch in 0..BATCHES {
+        run.run_batch()
+            .await
+            .map_err(|err| format!("batch {}: {}", batch, err))?;
+    }
+
+    for name in run.joined_names() {
+        let operation = Operation::Leave(name);
+        let (future, _) = run.issue(&operation);
+        future.await;
+    }
+    run.settle().await
+}
+
+#[actix_rt::test]
+async fn test_stressing_router() {
+    let seeds = match std::env::var("STRESS_SEED") {
+        Ok(seed) => {
+            let seed = seed.parse().expect("STRESS_SEED must be a number");
+            seed..seed + 1
+        }
+        Err(_) => 0..SEEDS,
+    };
+
+    for seed in seeds {
+        if let Err(err) = stress(seed).await {
+            panic!(
+                "seed {} failed, replay with STRESS_SEED={}: {}",
+                seed, seed, err
+            );
+        }
+    }
+}
@@ -0,0 +1,133 @@
+//! End-to-end tests of `/signal`: a real server on an ephemeral port and
+//! real WebSocket clients speaking the JSON protocol.
+
+use awc::ws;
+use futures::SinkExt;
+use serde_json::json;
+use signalling_server::{MembersMessage, SignalServer};
+use std::time::Duration;
+
+mod common;
+
+use common::{serve, start_server, TestClient};
+
+#[actix_rt::test]
+async fn test_assigning_distinct_names_on_connect() {
+    let server = start_server();
+
+    let caller = TestClient::connect(&server).await;
+    let callee = TestClient::connect(&server).await;
+
+    assert!(!caller.name.is_empty());
+    assert_ne!(caller.name, callee.name);
+}
+
+#[actix_rt::test]
+async fn test_relaying_offer_answer_and_candidate() {
+    //given
+    let server = start_server();
+    let mut caller = TestClient::connect(&server).await;
+    let mut callee = TestClient::connect(&server).await;
+    let offer = json!({
+        "type": "offer",
+        "name": caller.name,
+        "target": callee.name,
+        "sdp": "v=0\r\no=- 1 2 IN IP4 127.0.0.1\r\n"
+    });
+    let answer = json!({
+        "type": "answer",
+        "name": callee.name,
+        "target": caller.name,
+        "sdp": "v=0\r\no=- 3 4 IN IP4 127.0.0.1\r\n"
+    });
+    let candidate = json!({
+        "type": "new_ice_candidate",
+        "name": caller.name,
+        "target": callee.name,
+        "candidate": "candidate:1 1 udp 2122260223 192.0.2.1 50000 typ host"
+    });
+
+    //when
+    caller.send(offer.clone()).await;
+    let received_offer = callee.receive().await;
+    callee.send(answer.clone()).await;
+    let received_answer = caller.receive().await;
+    caller.send(candidate.clone()).await;
+    let received_candidate = callee.receive().await;
+
+    //then
+    assert_eq!(received_offer, offer);
+    assert_eq!(received_answer, answer);
+    assert_eq!(received_candidate, candidate);
+}
+
+#[actix_rt::test]
+async fn test_reporting_target_not_found() {
+    //given
+    let server = start_server();
+    let mut caller = TestClient::connect(&server).await;
+
+    //when
+    caller
+        .send(json!({ "type": "offer", "name": caller.name, "target": "nobody", "sdp": "v=0" }))
+        .await;
+
+    //then
+    assert_eq!(
+        caller.receive().await,
+        json!({ "type": "target user not found", "message": "user nobody is not in connection" })
+    );
+}
+
+#[actix_rt::test]
+async fn test_replying_to_unparsable_input() {
+    //given
+    let server = start_server();
+    let mut caller = TestClient::connect(&server).await;
+
+    //when
+    caller.send_text("{ not a signal".to_owned()).await;
+
+    //then
+    assert_eq!(caller.receive_text().await, "couldn't parse your message");
+}
+
+#[actix_rt::test]
+async fn test_removing_closed_socket_from_router() {
+    //given
+    let signal_server = SignalServer::builder().build();
+    let server = serve(&signal_server);
+    let mut caller = TestClient::connect(&server).await;
+    let mut callee = TestClient::connect(&server).await;
+
+    //when
+    callee
+        .connection
+        .send(ws::Message::Close(None))
+        .await
+        .expect("couldn't close");
+
+    //then
+    wait_for_exit(&signal_server, &callee.name).await;
+    caller
+        .send(json!({ "type": "offer", "name": caller.name, "target": callee.name, "sdp": "v=0" }))
+        .await;
+    assert_eq!(caller.receive().await["type"], "target user not found");
+}
+
+/// Polls the router until `name` is no longer a member, as sockets exit
+/// after their connection closed.
+async fn wait_for_exit(signal_server: &SignalServer, name: &str) {
+    for _ in 0..50 {
+        let members = signal_server
+            .signal_router()
+            .send(MembersMessage)
+            .await
+            .expect("router is gone");
+        if !members.iter().any(|member| member == name) {
+            return;
+        }
+        actix_rt::time::delay_for(Duration::from_millis(20)).await;
+    }
+    panic!("{} is still a member", name);
+}
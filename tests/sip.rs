@@ -0,0 +1,461 @@
+//! End-to-end tests of the SIP over WebSocket gateway between a SIP
+//! endpoint and a native WebSocket peer.
+
+use actix_web::test::TestServer;
+use actix_web::{test, web, App, HttpResponse};
+use awc::ws;
+use futures::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use signalling_server::{AdmissionHook, SignalServer};
+use std::time::Duration;
+
+mod common;
+
+use common::{start_server_with, Connection, TestClient};
+
+const DOMAIN: &str = "example.com";
+const CONTACT: &str = "<sip:alice@client.invalid;transport=ws>";
+
+/// Admits every user but `mallory` under the name asked for.
+async fn admit(request: web::Json<Value>) -> HttpResponse {
+    match request["requested_name"].as_str() {
+        Some("mallory") => HttpResponse::Ok().json(json!({ "allow": false })),
+        requested_name => HttpResponse::Ok().json(json!({ "allow": true, "name": requested_name })),
+    }
+}
+
+fn start_hook() -> TestServer {
+    test::start(|| App::new().route("/admit", web::post().to(admit)))
+}
+
+fn start_server(hook: &TestServer) -> TestServer {
+    start_server_with(
+        SignalServer::builder()
+            .sip_domain(DOMAIN)
+            .admission_hook(AdmissionHook::new(hook.url("/admit"))),
+    )
+}
+
+/// SIP endpoint speaking to the gateway over `/sip`.
+struct SipClient {
+    connection: Connection,
+}
+
+impl SipClient {
+    async fn connect(server: &TestServer) -> Self {
+        let url = server.url("/sip").replacen("http", "ws", 1);
+        let (_, connection) = awc::Client::new()
+            .ws(url)
+            .protocols(["sip"])
+            .connect()
+            .await
+            .expect("couldn't connect");
+        SipClient { connection }
+    }
+
+    async fn register(&mut self, user: &str) -> SipMessage {
+        let to = format!("<sip:{}@{}>", user, DOMAIN);
+        self.send(&request(
+            "REGISTER",
+            &format!("sip:{}", DOMAIN),
+            &[("From", &format!("{};tag=reg", to)), ("To", &to)],
+            "register",
+            1,
+            "",
+        ))
+        .await;
+        self.receive().await
+    }
+
+    async fn send(&mut self, text: &str) {
+        self.connection
+            .send(ws::Message::Text(text.to_owned()))
+            .await
+            .expect("couldn't send");
+    }
+
+    async fn receive(&mut self) -> SipMessage {
+        loop {
+            let frame = actix_rt::time::timeout(Duration::from_secs(5), self.connection.next())
+                .await
+                .expect("timed out waiting for a SIP message")
+                .expect("connection closed")
+                .expect("protocol error");
+            if let ws::Frame::Text(bytes) = frame {
+                return SipMessage::parse(std::str::from_utf8(&bytes).unwrap());
+            }
+        }
+    }
+
+    /// Next message other than a provisional response.
+    async fn receive_final(&mut self) -> SipMessage {
+        loop {
+            let message = self.receive().await;
+            if message.status().is_none_or(|status| status >= 200) {
+                return message;
+            }
+        }
+    }
+}
+
+/// Start line, headers and body of a SIP message.
+struct SipMessage {
+    start_line: String,
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+impl SipMessage {
+    fn parse(text: &str) -> Self {
+        let (head, body) = text.split_once("\r\n\r\n").expect("no empty line");
+        let mut lines = head.split("\r\n");
+        let start_line = lines.next().unwrap().to_owned();
+        let headers = lines
+            .map(|line| {
+                let (name, value) = line.split_once(':').expect("not a header");
+                (name.trim().to_owned(), value.trim().to_owned())
+            })
+            .collect();
+        SipMessage {
+            start_line,
+            headers,
+            body: body.to_owned(),
+        }
+    }
+
+    fn status(&self) -> Option<u16> {
+        let status = self.start_line.strip_prefix("SIP/2.0 ")?;
+        status.split(' ').next()?.parse().ok()
+    }
+
+    fn method(&self) -> Option<&str> {
+        match self.status() {
+            Some(_) => None,
+            None => self.start_line.split(' ').next(),
+        }
+    }
+
+    fn header(&self, name: &str) -> &str {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+            .unwrap_or_else(|| panic!("{} is missing", name))
+    }
+
+    /// Response to this request, as the endpoint sends it.
+    fn response(&self, status: &str, body: &str) -> String {
+        let mut text = format!("SIP/2.0 {}\r\n", status);
+        for name in &["Via", "From", "To", "Call-ID", "CSeq"] {
+            let mut value = self.header(name).to_owned();
+            if *name == "To" {
+                value.push_str(";tag=callee");
+            }
+            text.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        text.push_str(&format!("Contact: {}\r\n", CONTACT));
+        text + &body_part(body)
+    }
+}
+
+fn body_part(body: &str) -> String {
+    if body.is_empty() {
+        "Content-Length: 0\r\n\r\n".to_owned()
+    } else {
+        format!(
+            "Content-Type: application/sdp\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        )
+    }
+}
+
+fn request(
+    method: &str,
+    uri: &str,
+    addresses: &[(&str, &str)],
+    call_id: &str,
+    cseq: u32,
+    body: &str,
+) -> String {
+    let mut text = format!(
+        "{} {} SIP/2.0\r\nVia: SIP/2.0/WSS client.invalid;branch=z9hG4bK{}{}\r\nMax-Forwards: 70\r\n",
+        method, uri, call_id, cseq
+    );
+    for (name, value) in addresses {
+        text.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    text.push_str(&format!(
+        "Call-ID: {}\r\nCSeq: {} {}\r\nContact: {}\r\n",
+        call_id, cseq, method, CONTACT
+    ));
+    text + &body_part(body)
+}
+
+/// Request of alice to `peer` within the call `call_id`.
+fn call_request(method: &str, peer: &str, call_id: &str, cseq: u32, body: &str) -> String {
+    let from = format!("<sip:alice@{}>;tag=alice", DOMAIN);
+    let to = format!("<sip:{}@{}>", peer, DOMAIN);
+    let uri = format!("sip:{}@{}", peer, DOMAIN);
+    request(
+        method,
+        &uri,
+        &[("From", &from), ("To", &to)],
+        call_id,
+        cseq,
+        body,
+    )
+}
+
+/// Registers alice and connects a native peer negotiating hangups.
+async fn connect_pair(server: &TestServer) -> (SipClient, TestClient) {
+    let mut alice = SipClient::connect(server).await;
+    assert_eq!(alice.register("alice").await.status(), Some(200));
+    let mut peer = TestClient::connect(server).await;
+    peer.hello(&["hangup"]).await;
+    (alice, peer)
+}
+
+/// Calls `peer` from alice, returning the final response to the INVITE.
+async fn call(alice: &mut SipClient, peer: &mut TestClient, call_id: &str) -> SipMessage {
+    alice
+        .send(&call_request("INVITE", &peer.name, call_id, 1, "v=0 offer"))
+        .await;
+    let offer = peer.receive().await;
+    assert_eq!(offer["type"], "offer");
+    peer.send(
+        json!({ "type": "answer", "name": peer.name, "target": "alice", "sdp": "v=0 answer" }),
+    )
+    .await;
+    alice.receive_final().await
+}
+
+#[actix_rt::test]
+async fn test_registering_only_admitted_users() {
+    let hook = start_hook();
+    let server = start_server(&hook);
+    let mut alice = SipClient::connect(&server).await;
+    let mut mallory = SipClient::connect(&server).await;
+
+    assert_eq!(alice.register("alice").await.status(), Some(200));
+    assert_eq!(mallory.register("mallory").await.status(), Some(403));
+}
+
+#[actix_rt::test]
+async fn test_refusing_registration_without_admission_hook() {
+    let server = start_server_with(SignalServer::builder().sip_domain(DOMAIN));
+    let mut alice = SipClient::connect(&server).await;
+
+    assert_eq!(alice.register("alice").await.status(), Some(403));
+}
+
+#[actix_rt::test]
+async fn test_calling_native_peer_and_hanging_up() {
+    //given
+    let hook = start_hook();
+    let server = start_server(&hook);
+    let (mut alice, mut peer) = connect_pair(&server).await;
+
+    //when
+    alice
+        .send(&call_request(
+            "INVITE",
+            &peer.name,
+            "call-1",
+            1,
+            "v=0 offer",
+        ))
+        .await;
+    let offer = peer.receive().await;
+    peer.send(
+        json!({ "type": "answer", "name": peer.name, "target": "alice", "sdp": "v=0 answer" }),
+    )
+    .await;
+    let ok = alice.receive_final().await;
+    alice
+        .send(&call_request("ACK", &peer.name, "call-1", 1, ""))
+        .await;
+    alice
+        .send(&call_request("BYE", &peer.name, "call-1", 2, ""))
+        .await;
+
+    //then
+    assert_eq!(offer["name"], "alice");
+    assert_eq!(offer["sdp"], "v=0 offer");
+    assert_eq!(ok.status(), Some(200));
+    assert_eq!(ok.body, "v=0 answer");
+    assert_eq!(alice.receive().await.status(), Some(200));
+    assert_eq!(
+        peer.receive().await,
+        json!({ "type": "hangup", "name": "alice", "target": peer.name })
+    );
+}
+
+#[actix_rt::test]
+async fn test_calling_sip_endpoint() {
+    //given
+    let hook = start_hook();
+    let server = start_server(&hook);
+    let (mut alice, mut peer) = connect_pair(&server).await;
+
+    //when
+    peer.send(json!({ "type": "offer", "name": peer.name, "target": "alice", "sdp": "v=0 offer" }))
+        .await;
+    let invite = alice.receive().await;
+    alice.send(&invite.response("200 OK", "v=0 answer")).await;
+    let ack = alice.receive().await;
+    let answer = peer.receive().await;
+    peer.send(json!({ "type": "hangup", "name": peer.name, "target": "alice" }))
+        .await;
+
+    //then
+    assert_eq!(invite.method(), Some("INVITE"));
+    assert_eq!(invite.body, "v=0 offer");
+    assert_eq!(ack.method(), Some("ACK"));
+    assert_eq!(answer["type"], "answer");
+    assert_eq!(answer["sdp"], "v=0 answer");
+    assert_eq!(alice.receive().await.method(), Some("BYE"));
+}
+
+#[actix_rt::test]
+async fn test_cancelling_invite() {
+    //given
+    let hook = start_hook();
+    let server = start_server(&hook);
+    let (mut alice, mut peer) = connect_pair(&server).await;
+    alice
+        .send(&call_request(
+            "INVITE",
+            &peer.name,
+            "call-1",
+            1,
+            "v=0 offer",
+        ))
+        .await;
+    assert_eq!(peer.receive().await["type"], "offer");
+
+    //when
+    alice
+        .send(&call_request("CANCEL", &peer.name, "call-1", 1, ""))
+        .await;
+
+    //then
+    let cancelled = alice.receive_final().await;
+    assert_eq!(cancelled.status(), Some(200));
+    assert_eq!(cancelled.header("CSeq"), "1 CANCEL");
+    let terminated = alice.receive_final().await;
+    assert_eq!(terminated.status(), Some(487));
+    assert_eq!(terminated.header("CSeq"), "1 INVITE");
+    assert_eq!(peer.receive().await["type"], "hangup");
+}
+
+#[actix_rt::test]
+async fn test_renegotiating_call_with_reinvite() {
+    //given
+    let hook = start_hook();
+    let server = start_server(&hook);
+    let (mut alice, mut peer) = connect_pair(&server).await;
+    assert_eq!(
+        call(&mut alice, &mut peer, "call-1").await.status(),
+        Some(200)
+    );
+    alice
+        .send(&call_request("ACK", &peer.name, "call-1", 1, ""))
+        .await;
+
+    //when
+    alice
+        .send(&call_request(
+            "INVITE",
+            &peer.name,
+            "call-1",
+            2,
+            "v=0 new offer",
+        ))
+        .await;
+    let offer = peer.receive().await;
+    peer.send(
+        json!({ "type": "answer", "name": peer.name, "target": "alice", "sdp": "v=0 new answer" }),
+    )
+    .await;
+
+    //then
+    assert_eq!(offer["sdp"], "v=0 new offer");
+    let ok = alice.receive_final().await;
+    assert_eq!(ok.status(), Some(200));
+    assert_eq!(ok.header("CSeq"), "2 INVITE");
+    assert_eq!(ok.body, "v=0 new answer");
+}
+
+#[actix_rt::test]
+async fn test_ignoring_answer_and_hangup_of_other_member() {
+    //given
+    let hook = start_hook();
+    let server = start_server(&hook);
+    let (mut alice, mut peer) = connect_pair(&server).await;
+    let mut mallory = TestClient::connect(&server).await;
+    mallory.hello(&["hangup"]).await;
+    alice
+        .send(&call_request(
+            "INVITE",
+            &peer.name,
+            "call-1",
+            1,
+            "v=0 offer",
+        ))
+        .await;
+    assert_eq!(peer.receive().await["type"], "offer");
+
+    //when
+    mallory
+        .send(
+            json!({ "type": "answer", "name": peer.name, "target": "alice", "sdp": "v=0 forged" }),
+        )
+        .await;
+    mallory
+        .send(json!({ "type": "hangup", "name": peer.name, "target": "alice" }))
+        .await;
+    peer.send(
+        json!({ "type": "answer", "name": peer.name, "target": "alice", "sdp": "v=0 answer" }),
+    )
+    .await;
+
+    //then
+    let ok = alice.receive_final().await;
+    assert_eq!(ok.status(), Some(200));
+    assert_eq!(ok.body, "v=0 answer");
+}
+
+#[actix_rt::test]
+async fn test_renegotiating_call_of_native_peer_within_dialog() {
+    //given
+    let hook = start_hook();
+    let server = start_server(&hook);
+    let (mut alice, mut peer) = connect_pair(&server).await;
+    peer.send(json!({ "type": "offer", "name": peer.name, "target": "alice", "sdp": "v=0 offer" }))
+        .await;
+    let invite = alice.receive().await;
+    alice.send(&invite.response("200 OK", "v=0 answer")).await;
+    assert_eq!(alice.receive().await.method(), Some("ACK"));
+    assert_eq!(peer.receive().await["type"], "answer");
+
+    //when
+    peer.send(
+        json!({ "type": "offer", "name": peer.name, "target": "alice", "sdp": "v=0 new offer" }),
+    )
+    .await;
+    let reinvite = alice.receive().await;
+    alice
+        .send(&reinvite.response("200 OK", "v=0 new answer"))
+        .await;
+    let ack = alice.receive().await;
+
+    //then
+    assert_eq!(reinvite.method(), Some("INVITE"));
+    assert_eq!(reinvite.header("Call-ID"), invite.header("Call-ID"));
+    assert_eq!(reinvite.header("CSeq"), "2 INVITE");
+    assert_eq!(reinvite.body, "v=0 new offer");
+    assert_eq!(ack.header("CSeq"), "2 ACK");
+    let answer = peer.receive().await;
+    assert_eq!(answer["sdp"], "v=0 new answer");
+}
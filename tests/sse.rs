@@ -0,0 +1,253 @@
+//! End-to-end tests of Server-Sent Events sessions signalling WebSocket
+//! users.
+
+use actix_web::http::{header, StatusCode};
+use actix_web::test::TestServer;
+use actix_web::web::Bytes;
+use awc::error::PayloadError;
+use futures::stream::LocalBoxStream;
+use futures::StreamExt;
+use serde_json::{json, Value};
+use signalling_server::{OriginAllowlist, SignalServer};
+use std::time::Duration;
+
+mod common;
+
+use common::{start_server_with, to_text, TestClient};
+
+const RECEIVE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Client of `/signal/events`, reading the stream event by event.
+struct EventStream {
+    stream: LocalBoxStream<'static, Result<Bytes, PayloadError>>,
+    buffer: String,
+    token: String,
+    name: String,
+}
+
+impl EventStream {
+    async fn connect(server: &TestServer) -> Self {
+        let response = server
+            .get("/signal/events")
+            .send()
+            .await
+            .expect("couldn't request");
+        assert_eq!(response.status(), StatusCode::OK);
+        let mut event_stream = EventStream {
+            stream: response.boxed_local(),
+            buffer: String::new(),
+            token: String::new(),
+            name: String::new(),
+        };
+
+        let (event, session) = event_stream.receive_event().await;
+        assert_eq!(event.as_deref(), Some("session"));
+        event_stream.token = session["token"].as_str().unwrap().to_owned();
+        let assign = event_stream.receive().await;
+        assert_eq!(assign["type"], "assign");
+        event_stream.name = assign["name"].as_str().unwrap().to_owned();
+        event_stream
+    }
+
+    /// Data of the next unnamed event, skipping keepalives.
+    async fn receive(&mut self) -> Value {
+        let (event, data) = self.receive_event().await;
+        assert_eq!(event, None);
+        data
+    }
+
+    async fn receive_event(&mut self) -> (Option<String>, Value) {
+        loop {
+            if let Some(end) = self.buffer.find("\n\n") {
+                let block: String = self.buffer.drain(..end + 2).collect();
+                let (mut event, mut data) = (None, None);
+                for line in block.lines() {
+                    if let Some(name) = line.strip_prefix("event: ") {
+                        event = Some(name.to_owned());
+                    } else if let Some(text) = line.strip_prefix("data: ") {
+                        data = Some(serde_json::from_str(text).unwrap());
+                    }
+                }
+                match data {
+                    Some(data) => return (event, data),
+                    None => continue,
+                }
+            }
+
+            let chunk = actix_rt::time::timeout(RECEIVE_TIMEOUT, self.stream.next())
+                .await
+                .expect("timed out waiting for an event")
+                .expect("stream ended")
+                .expect("couldn't read the stream");
+            self.buffer.push_str(std::str::from_utf8(&chunk).unwrap());
+        }
+    }
+}
+
+async fn send(server: &TestServer, token: &str, signal: Value) -> (StatusCode, Value) {
+    let mut response = server
+        .post("/signal/send")
+        .bearer_auth(token)
+        .content_type("application/json")
+        .send_body(to_text(signal))
+        .await
+        .expect("couldn't request");
+    let body = response.json().await.unwrap_or(Value::Null);
+    (response.status(), body)
+}
+
+#[actix_rt::test]
+async fn test_exchanging_offer_and_answer() {
+    //given
+    let server = start_server_with(SignalServer::builder().sse(true));
+    let mut callee = EventStream::connect(&server).await;
+    let mut caller = TestClient::connect(&server).await;
+
+    //when
+    caller
+        .send(json!({ "type": "offer", "name": caller.name, "target": callee.name, "sdp": "v=0" }))
+        .await;
+    let offer = callee.receive().await;
+    let (status, _) = send(
+        &server,
+        &callee.token,
+        json!({ "type": "answer", "name": callee.name, "target": caller.name, "sdp": "v=0" }),
+    )
+    .await;
+
+    //then
+    assert_eq!(offer["type"], "offer");
+    assert_eq!(offer["name"], caller.name.as_str());
+    assert_eq!(status, StatusCode::ACCEPTED);
+    let answer = caller.receive().await;
+    assert_eq!(answer["type"], "answer");
+    assert_eq!(answer["name"], callee.name.as_str());
+}
+
+#[actix_rt::test]
+async fn test_rejecting_unknown_token() {
+    let server = start_server_with(SignalServer::builder().sse(true));
+    let callee = EventStream::connect(&server).await;
+
+    let (status, body) = send(
+        &server,
+        "guess",
+        json!({ "type": "offer", "name": "caller", "target": callee.name, "sdp": "v=0" }),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["type"], "unauthorized");
+}
+
+#[actix_rt::test]
+async fn test_rejecting_token_in_query() {
+    let server = start_server_with(SignalServer::builder().sse(true));
+    let callee = EventStream::connect(&server).await;
+
+    let response = server
+        .post(format!("/signal/send?token={}", callee.token))
+        .content_type("application/json")
+        .send_body(to_text(
+            json!({ "type": "offer", "name": "caller", "target": callee.name, "sdp": "v=0" }),
+        ))
+        .await
+        .expect("couldn't request");
+
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[actix_rt::test]
+async fn test_rejecting_disallowed_origin() {
+    let server = start_server_with(
+        SignalServer::builder()
+            .sse(true)
+            .origin_allowlist(OriginAllowlist::new(vec!["app.example.com"])),
+    );
+
+    let response = server
+        .get("/signal/events")
+        .header(header::ORIGIN, "https://evil.example.com")
+        .send()
+        .await
+        .expect("couldn't request");
+
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[actix_rt::test]
+async fn test_keeping_features_negotiated_by_hello() {
+    //given
+    let server = start_server_with(SignalServer::builder().sse(true));
+    let mut callee = EventStream::connect(&server).await;
+    let mut caller = TestClient::connect(&server).await;
+    caller.hello(&["hangup"]).await;
+    let hangup = json!({ "type": "hangup", "name": callee.name, "target": caller.name });
+
+    //when
+    let (status_before_hello, _) = send(&server, &callee.token, hangup.clone()).await;
+    let (_, hello) = send(
+        &server,
+        &callee.token,
+        json!({ "type": "hello", "version": 1, "features": ["hangup"] }),
+    )
+    .await;
+    let (status_after_hello, _) = send(&server, &callee.token, hangup.clone()).await;
+    caller
+        .send(json!({ "type": "hangup", "name": caller.name, "target": callee.name }))
+        .await;
+
+    //then
+    assert_eq!(status_before_hello, StatusCode::BAD_REQUEST);
+    assert_eq!(hello["features"], json!(["hangup"]));
+    assert_eq!(status_after_hello, StatusCode::ACCEPTED);
+    assert_eq!(caller.receive().await, hangup);
+    assert_eq!(callee.receive().await["type"], "hangup");
+}
+
+#[actix_rt::test]
+async fn test_rejecting_notice_from_client() {
+    let server = start_server_with(SignalServer::builder().sse(true));
+    let callee = EventStream::connect(&server).await;
+
+    let (status, body) = send(
+        &server,
+        &callee.token,
+        json!({ "type": "notice", "message": "restarting" }),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["type"], "server signal");
+}
+
+#[actix_rt::test]
+async fn test_removing_session_of_closed_stream() {
+    //given
+    let server = start_server_with(SignalServer::builder().sse(true));
+    let callee = EventStream::connect(&server).await;
+    let mut caller = TestClient::connect(&server).await;
+    let (token, callee_name) = (callee.token.clone(), callee.name.clone());
+
+    //when
+    drop(callee);
+
+    //then
+    for _ in 0..50 {
+        // a stream the client went away from shows when it is written to
+        caller
+            .send(json!({ "type": "offer", "name": caller.name, "target": callee_name, "sdp": "v=0" }))
+            .await;
+        let (status, _) = send(
+            &server,
+            &token,
+            json!({ "type": "offer", "name": callee_name, "target": caller.name, "sdp": "v=0" }),
+        )
+        .await;
+        if status == StatusCode::UNAUTHORIZED {
+            return;
+        }
+        actix_rt::time::delay_for(Duration::from_millis(100)).await;
+    }
+    panic!("session of the closed stream is still live");
+}
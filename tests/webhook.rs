@@ -0,0 +1,157 @@
+//! Delivery of webhook events to an endpoint stubbed in-process.
+
+use actix_web::{test, web, App, HttpRequest, HttpResponse};
+use serde_json::{json, Value};
+use signalling_server::webhook::{signature, Webhook, EVENT_HEADER, SIGNATURE_HEADER};
+use signalling_server::SignalServer;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+mod common;
+
+use common::{start_server_with, TestClient};
+
+const SECRET: &str = "s3cret";
+
+type ReceivedEvents = Arc<Mutex<Vec<Value>>>;
+
+async fn receive(
+    events: web::Data<ReceivedEvents>,
+    request: HttpRequest,
+    body: String,
+) -> HttpResponse {
+    let header = |name| {
+        request
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_owned()
+    };
+    assert_eq!(header(SIGNATURE_HEADER), signature(SECRET, body.as_bytes()));
+    let event: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(header(EVENT_HEADER), event["event"].as_str().unwrap());
+    events.lock().unwrap().push(event);
+    HttpResponse::NoContent().finish()
+}
+
+async fn fail(_body: String) -> HttpResponse {
+    HttpResponse::ServiceUnavailable().finish()
+}
+
+async fn wait_for_events(events: &ReceivedEvents, count: usize) -> Vec<Value> {
+    for _ in 0..100 {
+        let received = events.lock().unwrap().clone();
+        if received.len() >= count {
+            return received;
+        }
+        actix_rt::time::delay_for(Duration::from_millis(50)).await;
+    }
+    panic!("{} webhook events weren't delivered", count)
+}
+
+#[actix_rt::test]
+async fn test_delivering_signed_call_events() {
+    //given
+    let events = ReceivedEvents::default();
+    let endpoint_events = events.clone();
+    let endpoint = test::start(move || {
+        App::new()
+            .data(endpoint_events.clone())
+            .route("/hooks", web::post().to(receive))
+    });
+    let server = start_server_with(
+        SignalServer::builder().webhook(Webhook::new(endpoint.url("/hooks"), SECRET)),
+    );
+    let mut caller = TestClient::connect(&server).await;
+    let mut callee = TestClient::connect(&server).await;
+    caller.hello(&["hangup"]).await;
+    callee.hello(&["hangup"]).await;
+
+    //when
+    caller
+        .send(json!({ "type": "offer", "name": caller.name, "target": callee.name, "sdp": "v=0" }))
+        .await;
+    callee.receive().await;
+    callee
+        .send(json!({ "type": "answer", "name": callee.name, "target": caller.name, "sdp": "v=0" }))
+        .await;
+    caller.receive().await;
+    caller
+        .send(json!({ "type": "hangup", "name": caller.name, "target": callee.name }))
+        .await;
+    callee.receive().await;
+    // the outcome of routing the hangup is only known once delivered
+    wait_for_events(&events, 5).await;
+    drop(callee);
+
+    //then
+    let events = wait_for_events(&events, 6).await;
+    let summary: Vec<(&str, &str)> = events
+        .iter()
+        .map(|event| {
+            let user = event
+                .get("user")
+                .or_else(|| event.get("sender"))
+                .and_then(Value::as_str)
+                .unwrap();
+            (event["event"].as_str().unwrap(), user)
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            ("join", caller.name.as_str()),
+            ("join", callee_name(&events)),
+            ("offer", caller.name.as_str()),
+            ("answer", callee_name(&events)),
+            ("hangup", caller.name.as_str()),
+            ("exit", callee_name(&events)),
+        ]
+    );
+    assert_eq!(events[0]["protocol"], "websocket");
+    assert_eq!(events[3]["target"], caller.name.as_str());
+    assert!(events[2]["occurred_at"].as_u64().unwrap() > 0);
+    assert_ne!(events[2]["id"], events[3]["id"]);
+}
+
+fn callee_name(events: &[Value]) -> &str {
+    events[1]["user"].as_str().unwrap()
+}
+
+#[actix_rt::test]
+async fn test_dead_lettering_events_the_endpoint_keeps_failing() {
+    //given
+    let endpoint = test::start(|| App::new().route("/hooks", web::post().to(fail)));
+    let dead_letter_file =
+        std::env::temp_dir().join(format!("webhook-dead-letters-{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&dead_letter_file);
+    let webhook = Webhook::new(endpoint.url("/hooks"), SECRET)
+        .max_attempts(3)
+        .backoff(Duration::from_millis(10))
+        .dead_letter_file(&dead_letter_file);
+
+    //when
+    let server = start_server_with(SignalServer::builder().webhook(webhook));
+    let caller = TestClient::connect(&server).await;
+    let caller_name = caller.name.clone();
+    drop(caller);
+
+    //then
+    for _ in 0..100 {
+        let dead_letters = std::fs::read_to_string(&dead_letter_file).unwrap_or_default();
+        let dead_letters: Vec<Value> = dead_letters
+            .lines()
+            .map(|line| serde_json::from_str(line).expect("dead letter isn't JSON"))
+            .collect();
+        if dead_letters.len() == 2 {
+            std::fs::remove_file(&dead_letter_file).unwrap();
+            assert_eq!(dead_letters[0]["event"], "join");
+            assert_eq!(dead_letters[0]["user"], caller_name.as_str());
+            assert_eq!(dead_letters[1]["event"], "exit");
+            return;
+        }
+        actix_rt::time::delay_for(Duration::from_millis(50)).await;
+    }
+    panic!("undeliverable events weren't dead-lettered");
+}
@@ -0,0 +1,208 @@
+//! End-to-end tests of WHIP and WHEP sessions against a connected WebSocket
+//! target.
+
+use actix_web::http::{header, Method, StatusCode};
+use actix_web::test::TestServer;
+use serde_json::json;
+use signalling_server::SignalServer;
+
+mod common;
+
+use common::{start_server, start_server_with, TestClient};
+
+const TRICKLE_FRAGMENT: &str =
+    "a=mid:0\r\na=candidate:1387637174 1 udp 2122260223 192.0.2.1 61764 typ host\r\n";
+
+fn post_offer(server: &TestServer, resource: &str, target: &str) -> awc::ClientRequest {
+    server
+        .post(format!("{}/{}", resource, target))
+        .header(header::CONTENT_TYPE, "application/sdp")
+}
+
+/// Answers the next offer `target` receives.
+async fn answer(target: &mut TestClient) {
+    let offer = target.receive().await;
+    assert_eq!(offer["type"], "offer");
+    assert_eq!(offer["sdp"], "v=0 offer");
+    target
+        .send(json!({ "type": "answer", "name": target.name, "target": offer["name"], "sdp": "v=0 answer" }))
+        .await;
+}
+
+#[actix_rt::test]
+async fn test_publishing_trickling_and_tearing_down() {
+    //given
+    let server = start_server_with(SignalServer::builder().whip(true));
+    let mut target = TestClient::connect(&server).await;
+    target.hello(&["hangup"]).await;
+
+    //when
+    let target_name = target.name.clone();
+    let (response, _) = futures::join!(
+        post_offer(&server, "/whip", &target_name).send_body("v=0 offer"),
+        answer(&mut target)
+    );
+
+    //then
+    let mut response = response.expect("couldn't request");
+    assert_eq!(response.status(), StatusCode::CREATED);
+    assert_eq!(response.body().await.unwrap(), "v=0 answer");
+    let location = response
+        .headers()
+        .get(header::LOCATION)
+        .expect("Location is missing")
+        .to_str()
+        .unwrap()
+        .to_owned();
+    let session_name = location
+        .strip_prefix(&format!("/whip/{}/", target.name))
+        .unwrap_or_else(|| panic!("unexpected Location {}", location))
+        .to_owned();
+
+    let response = server
+        .request(Method::PATCH, server.url(&location))
+        .header(header::CONTENT_TYPE, "application/trickle-ice-sdpfrag")
+        .send_body(TRICKLE_FRAGMENT)
+        .await
+        .expect("couldn't request");
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let ice_candidate = target.receive().await;
+    assert_eq!(ice_candidate["type"], "new_ice_candidate");
+    assert_eq!(ice_candidate["sdpMid"], "0");
+
+    let response = server
+        .delete(&location)
+        .send()
+        .await
+        .expect("couldn't request");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        target.receive().await,
+        json!({ "type": "hangup", "name": session_name, "target": target.name })
+    );
+
+    let response = server
+        .delete(&location)
+        .send()
+        .await
+        .expect("couldn't request");
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_rt::test]
+async fn test_playing_back_and_refusing_candidates_of_publisher() {
+    //given
+    let server = start_server_with(SignalServer::builder().whep(true));
+    let mut publisher = TestClient::connect(&server).await;
+    publisher.hello(&["hangup"]).await;
+
+    //when
+    let publisher_name = publisher.name.clone();
+    let (response, _) = futures::join!(
+        post_offer(&server, "/whep", &publisher_name).send_body("v=0 offer"),
+        answer(&mut publisher)
+    );
+
+    //then
+    let mut response = response.expect("couldn't request");
+    assert_eq!(response.status(), StatusCode::CREATED);
+    assert_eq!(response.body().await.unwrap(), "v=0 answer");
+    let location = response
+        .headers()
+        .get(header::LOCATION)
+        .expect("Location is missing")
+        .to_str()
+        .unwrap()
+        .to_owned();
+    let session_name = location
+        .strip_prefix(&format!("/whep/{}/", publisher.name))
+        .unwrap_or_else(|| panic!("unexpected Location {}", location))
+        .to_owned();
+
+    let response = server
+        .request(Method::PATCH, server.url(&location))
+        .header(header::CONTENT_TYPE, "application/trickle-ice-sdpfrag")
+        .send_body(TRICKLE_FRAGMENT)
+        .await
+        .expect("couldn't request");
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    assert_eq!(publisher.receive().await["type"], "new_ice_candidate");
+
+    publisher
+        .send(json!({ "type": "new_ice_candidate", "target": session_name, "candidate": "candidate:1" }))
+        .await;
+    assert_eq!(
+        publisher.receive().await,
+        json!({
+            "type": "trickle not supported",
+            "message": format!("user {} only takes the candidates of the answer", session_name)
+        })
+    );
+
+    let response = server
+        .delete(&location)
+        .send()
+        .await
+        .expect("couldn't request");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(publisher.receive().await["type"], "hangup");
+}
+
+#[actix_rt::test]
+async fn test_refusing_answer_of_other_member() {
+    //given
+    let server = start_server_with(SignalServer::builder().whip(true));
+    let mut target = TestClient::connect(&server).await;
+    let mut impostor = TestClient::connect(&server).await;
+
+    //when
+    let target_name = target.name.clone();
+    let (response, _) = futures::join!(
+        post_offer(&server, "/whip", &target_name).send_body("v=0 offer"),
+        async {
+            let offer = target.receive().await;
+            impostor
+                .send(json!({ "type": "answer", "name": target.name, "target": offer["name"], "sdp": "v=0 forged" }))
+                .await;
+            assert_eq!(impostor.receive().await["type"], "forbidden");
+            target
+                .send(json!({ "type": "answer", "name": target.name, "target": offer["name"], "sdp": "v=0 answer" }))
+                .await;
+        }
+    );
+
+    //then
+    let mut response = response.expect("couldn't request");
+    assert_eq!(response.status(), StatusCode::CREATED);
+    assert_eq!(response.body().await.unwrap(), "v=0 answer");
+}
+
+#[actix_rt::test]
+async fn test_rejecting_offer_without_token() {
+    //given
+    let server = start_server_with(SignalServer::builder().whip(true).whip_token("s3cret"));
+    let target = TestClient::connect(&server).await;
+
+    //when
+    let response = post_offer(&server, "/whip", &target.name)
+        .send_body("v=0 offer")
+        .await
+        .expect("couldn't request");
+
+    //then
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert!(response.headers().get(header::LOCATION).is_none());
+}
+
+#[actix_rt::test]
+async fn test_not_mounting_whip_unless_enabled() {
+    let server = start_server();
+    let target = TestClient::connect(&server).await;
+
+    let response = post_offer(&server, "/whip", &target.name)
+        .send_body("v=0 offer")
+        .await
+        .expect("couldn't request");
+
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}